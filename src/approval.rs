@@ -0,0 +1,160 @@
+//! Local approval of sensitive roles with biometrics or a confirmation prompt.
+
+use crate::prompt;
+use anyhow::{bail, Context as _, Result};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Returns whether `text` matches `pattern`, where `*` matches any sequence of characters and `?`
+/// matches any single character.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp + 1;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Asks the user to approve assuming `role_arn`, preferring biometric authentication.
+pub async fn approve(role_arn: &str) -> Result<()> {
+    let reason = format!("assume {role_arn}");
+    let approved = if cfg!(target_os = "macos") {
+        run_script(
+            Command::new("osascript"),
+            &["-l", "JavaScript", "-e"],
+            MACOS,
+            &reason,
+        )
+        .await?
+    } else if cfg!(windows) {
+        run_script(
+            Command::new("powershell"),
+            &["-NoProfile", "-Command"],
+            WINDOWS,
+            &reason,
+        )
+        .await?
+    } else {
+        prompt::confirm(&format!("Do you want to {reason}?")).await?
+    };
+
+    if !approved {
+        bail!("assuming `{role_arn}` was not approved");
+    }
+
+    Ok(())
+}
+
+async fn run_script(mut cmd: Command, args: &[&str], script: &str, reason: &str) -> Result<bool> {
+    let status = cmd
+        .args(args)
+        .arg(script)
+        .env("ASSUME_ROLE_REASON", reason)
+        .stdout(Stdio::null())
+        .status()
+        .await
+        .context("failed to request biometric authentication")?;
+    Ok(status.success())
+}
+
+/// Evaluates the device owner authentication policy (Touch ID with password fallback).
+const MACOS: &str = r#"
+ObjC.import('stdlib');
+ObjC.import('LocalAuthentication');
+var reason = $.getenv('ASSUME_ROLE_REASON');
+var context = $.LAContext.alloc.init;
+var done = false, approved = false;
+context.evaluatePolicyLocalizedReasonReply(2, reason, function (success, error) {
+    approved = success;
+    done = true;
+});
+while (!done) {
+    $.NSRunLoop.currentRunLoop.runUntilDate($.NSDate.dateWithTimeIntervalSinceNow(0.1));
+}
+$.exit(approved ? 0 : 1);
+"#;
+
+/// Requests the verification of Windows Hello.
+const WINDOWS: &str = r#"
+Add-Type -AssemblyName System.Runtime.WindowsRuntime
+$null = [Windows.Security.Credentials.UI.UserConsentVerifier, Windows.Security.Credentials.UI, ContentType = WindowsRuntime]
+$asTask = [System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object {
+    $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and
+    $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1'
+} | Select-Object -First 1
+$operation = [Windows.Security.Credentials.UI.UserConsentVerifier]::RequestVerificationAsync($env:ASSUME_ROLE_REASON)
+$task = $asTask.MakeGenericMethod([Windows.Security.Credentials.UI.UserConsentVerificationResult]).Invoke($null, @($operation))
+if ($task.Result -eq 'Verified') { exit 0 } else { exit 1 }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal() {
+        assert!(matches(
+            "arn:aws:iam::111122223333:role/Admin",
+            "arn:aws:iam::111122223333:role/Admin"
+        ));
+        assert!(!matches(
+            "arn:aws:iam::111122223333:role/Admin",
+            "arn:aws:iam::111122223333:role/AdminX"
+        ));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches(
+            "arn:aws:iam::*:role/Admin*",
+            "arn:aws:iam::111122223333:role/AdminAccess"
+        ));
+        assert!(matches(
+            "arn:aws:iam::*:role/Admin*",
+            "arn:aws:iam::111122223333:role/Admin"
+        ));
+        assert!(!matches(
+            "arn:aws:iam::*:role/Admin*",
+            "arn:aws:iam::111122223333:role/ReadOnly"
+        ));
+        assert!(matches("*", ""));
+        assert!(matches("**", "abc"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("*/Admin", "arn:aws:iam::1:role/path/Admin"));
+        assert!(!matches("*/Admin", "arn:aws:iam::1:role/Admin/x"));
+    }
+
+    #[test]
+    fn backtracking() {
+        assert!(matches("*ab*ab", "aabxabab"));
+        assert!(matches("a*b*c", "abxbyc"));
+        assert!(!matches("a*b*c", "abxbyd"));
+        assert!(matches("*?", "x"));
+        assert!(!matches("*??", "x"));
+    }
+}
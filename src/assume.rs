@@ -0,0 +1,1227 @@
+use crate::cache::{self, Cache};
+use crate::config::{ChainHop, ConfigFile};
+use crate::mfa::Mfa;
+use crate::{
+    approval, entra, git, mfa, oidc, okta, organizations, preflight, prompt, roles, saml, validate,
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use aws_config::SdkConfig;
+use aws_sdk_iam::error::ProvideErrorMetadata as _;
+use aws_sdk_iam::types::PolicyScopeType;
+use aws_sdk_sts::config::SharedCredentialsProvider;
+use aws_sdk_sts::operation::assume_role::builders::AssumeRoleFluentBuilder;
+use aws_sdk_sts::operation::assume_role::AssumeRoleOutput;
+use aws_sdk_sts::types::{AssumedRoleUser, Credentials, PolicyDescriptorType, Tag};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt as _;
+use tokio::sync::OnceCell;
+
+/// The maximum length of the plaintext of an inline session policy.
+const MAX_POLICY_LENGTH: usize = 2048;
+
+#[derive(Clone, clap::Args)]
+pub struct AssumeArgs {
+    /// The name or the Amazon Resource Name (ARN) of the role to assume. `ACCOUNT/NAME` specifies the
+    /// role in the account of the ID or the alias. Defaults to `role` in the config file or the role
+    /// of `--profile`, otherwise chosen from the presets, the chains, the recently assumed roles and
+    /// the roles found by `list-roles`.
+    #[arg(short, long, value_name = "NAME", env = "ASSUME_ROLE_ROLE")]
+    role: Option<String>,
+
+    /// An IAM tag that the role to assume has, instead of `--role`. Can be repeated to match
+    /// multiple tags. The roles are looked up in the account of the source credentials.
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        conflicts_with_all = ["role", "account_id", "account", "via", "saml_assertion", "okta", "okta_app_url", "entra", "entra_tenant_id", "github_oidc", "oidc"],
+        env = "ASSUME_ROLE_ROLE_TAG"
+    )]
+    role_tag: Vec<String>,
+
+    /// The ID or the alias of the account that the role belongs to. The ARN of the role is built
+    /// from it instead of looking up the role with IAM.
+    #[arg(long, value_name = "ID", env = "ASSUME_ROLE_ACCOUNT_ID")]
+    account_id: Option<String>,
+
+    /// The name of the account that the role belongs to in AWS Organizations. The accounts are
+    /// listed with the source credentials, which must be allowed `organizations:ListAccounts`.
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "account_id",
+        env = "ASSUME_ROLE_ACCOUNT"
+    )]
+    account: Option<String>,
+
+    /// An intermediate role to assume before `--role`, using the credentials of the previous one.
+    /// Can be repeated to chain multiple roles. `--role` must be an ARN.
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["saml_assertion", "okta", "okta_app_url", "entra", "entra_tenant_id", "github_oidc", "oidc"],
+        env = "ASSUME_ROLE_VIA"
+    )]
+    via: Vec<String>,
+
+    /// An identifier for the assumed role session.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_ROLE_SESSION_NAME")]
+    role_session_name: Option<String>,
+
+    #[command(flatten)]
+    session: SessionOptions,
+
+    /// The duration, in seconds, of the role session.
+    #[arg(long, value_name = "NUMBER", env = "ASSUME_ROLE_DURATION_SECONDS")]
+    duration_seconds: Option<i32>,
+
+    /// A key for session tags that you want to set as transitive.
+    #[arg(long, value_name = "KEY", env = "ASSUME_ROLE_TRANSITIVE_TAG_KEY")]
+    transitive_tag_key: Vec<String>,
+
+    /// Check with the policy simulator of IAM that the policies of the caller allow assuming the
+    /// role before calling STS, to explain why it would be denied.
+    #[arg(long, env = "ASSUME_ROLE_PREFLIGHT")]
+    preflight: bool,
+
+    /// A unique identifier that might be required when you assume a role in another account.
+    #[arg(long, env = "ASSUME_ROLE_EXTERNAL_ID")]
+    external_id: Option<String>,
+
+    #[command(flatten)]
+    mfa: mfa::MfaArgs,
+
+    /// The source identity specified by the principal that is calling the `AssumeRole` operation.
+    #[arg(long, env = "ASSUME_ROLE_SOURCE_IDENTITY")]
+    source_identity: Option<String>,
+
+    /// A file containing the base64-encoded SAML authentication response provided by the IdP.
+    /// Specify `-` to read it from the standard input. `--role` must be an ARN.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["tag", "transitive_tag_key", "external_id", "serial_number", "source_identity"],
+        env = "ASSUME_ROLE_SAML_ASSERTION"
+    )]
+    saml_assertion: Option<String>,
+
+    /// The ARN of the SAML provider in IAM that describes the IdP. Defaults to the provider paired with
+    /// the role in the SAML assertion.
+    #[arg(long, value_name = "ARN", env = "ASSUME_ROLE_PRINCIPAL_ARN")]
+    principal_arn: Option<String>,
+
+    #[command(flatten)]
+    okta: okta::OktaArgs,
+
+    #[command(flatten)]
+    entra: entra::EntraArgs,
+
+    /// Exchange the OIDC token of the running GitHub Actions job for the credentials. `--role` must be an ARN.
+    #[arg(
+        long,
+        conflicts_with_all = ["saml_assertion", "oidc", "tag", "transitive_tag_key", "external_id", "serial_number", "source_identity"],
+        env = "ASSUME_ROLE_GITHUB_OIDC"
+    )]
+    github_oidc: bool,
+
+    /// Exchange the OIDC token of the workload for the credentials: `github`, `env:NAME` or `file:PATH`.
+    /// Defaults to `oidc.token_source` in the config file. `--role` must be an ARN.
+    #[arg(
+        long,
+        value_name = "SOURCE",
+        num_args = 0..=1,
+        conflicts_with_all = ["saml_assertion", "tag", "transitive_tag_key", "external_id", "serial_number", "source_identity"],
+        env = "ASSUME_ROLE_OIDC"
+    )]
+    oidc: Option<Option<oidc::TokenSource>>,
+
+    /// The audience of the OIDC token requested from the CI system. Defaults to `oidc.audience` in the
+    /// config file or `sts.amazonaws.com`.
+    #[arg(long, value_name = "AUDIENCE", env = "ASSUME_ROLE_OIDC_AUDIENCE")]
+    oidc_audience: Option<String>,
+
+    #[command(flatten)]
+    cache: cache::Options,
+}
+
+/// The session policies and tags, shared by the commands that start a session.
+#[derive(Clone, clap::Args)]
+pub struct SessionOptions {
+    /// The Amazon Resource Names (ARNs) of the IAM managed policy that you want to use as managed session policies.
+    /// The name of an AWS managed policy such as `ReadOnlyAccess`, or `customer:NAME` for a customer managed policy,
+    /// is resolved to its ARN.
+    #[arg(long, value_name = "ARN", env = "ASSUME_ROLE_POLICY_ARN")]
+    pub policy_arn: Vec<String>,
+
+    /// An IAM policy in JSON or YAML that you want to use as an inline session policy, or the path
+    /// to it. `-` reads it from the standard input. The statements of multiple policies are merged.
+    #[arg(short, long, value_name = "POLICY", env = "ASSUME_ROLE_POLICY")]
+    pub policy: Vec<String>,
+
+    /// A variable in the form of `KEY=VALUE` that replaces `${KEY}` in the strings of the inline
+    /// session policy. Other placeholders, such as the policy variables of IAM, are kept.
+    #[arg(long, value_name = "KEY=VALUE", env = "ASSUME_ROLE_POLICY_VAR")]
+    pub policy_var: Vec<String>,
+
+    /// The name of a policy in `policies` of the config file to use as an inline session policy,
+    /// merged with the others.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_POLICY_PRESET")]
+    pub policy_preset: Vec<String>,
+
+    /// A session tag that you want to pass. `KEY` alone takes the value of the environment variable
+    /// of the name.
+    #[arg(long, value_name = "KEY=VALUE", env = "ASSUME_ROLE_TAG")]
+    pub tag: Vec<String>,
+
+    /// A JSON or YAML file of a mapping from the keys to the values of the session tags to pass,
+    /// overridden by `--tag`.
+    #[arg(long, value_name = "PATH", env = "ASSUME_ROLE_TAGS_FILE")]
+    pub tags_file: Option<PathBuf>,
+
+    /// Pass the environment variables whose names start with this prefix, such as `CI_`, as
+    /// session tags of the same names, overridden by `--tag`. The ones that look like secrets are
+    /// skipped.
+    #[arg(long, value_name = "PREFIX", env = "ASSUME_ROLE_TAGS_FROM_ENV")]
+    pub tags_from_env: Vec<String>,
+
+    /// Pass the name of the git repository in the current directory, the branch and the short
+    /// commit SHA as the session tags `GitRepository`, `GitBranch` and `GitCommit`. The characters
+    /// not allowed in the values of session tags are replaced with `_`.
+    #[arg(long, env = "ASSUME_ROLE_TAG_GIT")]
+    pub tag_git: bool,
+
+    /// Warn when the session policies and tags use this percentage of the packed size limit or
+    /// more, before they start to be rejected.
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 90,
+        value_parser = clap::value_parser!(i32).range(0..=100),
+        env = "ASSUME_ROLE_PACKED_SIZE_WARNING"
+    )]
+    pub packed_size_warning: i32,
+}
+
+impl SessionOptions {
+    /// Returns the session tags of `--tag` in the form of `KEY=VALUE`, followed by the ones of
+    /// `--tags-from-env`, `--tag-git` and `--tags-file` whose keys are not specified before.
+    pub async fn tags(&self) -> Result<Vec<String>> {
+        let mut tags = expand_tags(&self.tag)?;
+        merge_tags(&mut tags, &env_tags(&self.tags_from_env));
+        if self.tag_git {
+            merge_tags(&mut tags, &git::tags().await);
+        }
+        if let Some(path) = &self.tags_file {
+            merge_tags(&mut tags, &load_tags(path).await?);
+        }
+        Ok(tags)
+    }
+
+    /// Loads the inline session policy of `--policy-preset` and `--policy`, merged into one as
+    /// JSON, or `None` if no policy is specified.
+    pub async fn policy(&self, config_file: &ConfigFile) -> Result<Option<String>> {
+        load_policies(
+            config_file,
+            &self.policy_preset,
+            &self.policy,
+            &self.policy_var,
+        )
+        .await
+    }
+}
+
+impl AssumeArgs {
+    /// Returns the role, preset or chain specified by `--role`.
+    pub fn role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
+
+    /// Reuses the cached credentials only if they remain valid for at least `min_ttl`.
+    pub fn require_min_ttl(&mut self, min_ttl: Duration) {
+        self.cache.min_ttl = self.cache.min_ttl.max(min_ttl);
+    }
+}
+
+/// The parameters that identify an `AssumeRole` request in the cache.
+#[derive(serde::Serialize)]
+struct Parameters<'a> {
+    via: &'a [ChainHop],
+    role_arn: &'a str,
+    role_session_name: Option<&'a str>,
+    policy_arns: &'a [String],
+    policy: Option<&'a str>,
+    duration_seconds: Option<i32>,
+    tags: &'a [(String, String)],
+    transitive_tag_keys: &'a [String],
+    external_id: Option<&'a str>,
+    serial_number: Option<&'a str>,
+    source_identity: Option<&'a str>,
+    principal_arn: Option<&'a str>,
+    oidc_audience: Option<&'a str>,
+}
+
+/// The result of a successful role assumption.
+pub struct Session {
+    pub credentials: Credentials,
+    pub assumed_role_user: Option<AssumedRoleUser>,
+    pub packed_policy_size: Option<i32>,
+    pub source_identity: Option<String>,
+}
+
+impl Session {
+    pub fn new(
+        credentials: Option<Credentials>,
+        assumed_role_user: Option<AssumedRoleUser>,
+        packed_policy_size: Option<i32>,
+        source_identity: Option<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            credentials: credentials.ok_or_else(|| anyhow!("no credentials provided"))?,
+            assumed_role_user,
+            packed_policy_size,
+            source_identity,
+        })
+    }
+
+    /// Reports how much of the packed size limit the session policies and tags use, and warns if
+    /// it is `threshold` percent or more.
+    pub fn check_packed_size(&self, threshold: i32) {
+        // STS returns the packed size only if session policies or tags are passed.
+        if let Some(size) = self.packed_policy_size {
+            tracing::debug!("the session policies and tags use {size}% of the packed size limit");
+            if size >= threshold {
+                eprintln!("The session policies and tags use {size}% of the packed size limit");
+            }
+        }
+    }
+}
+
+pub async fn assume_role(
+    config: &SdkConfig,
+    config_file: &ConfigFile,
+    mut args: AssumeArgs,
+) -> Result<Session> {
+    let stdin = [
+        (
+            "--policy",
+            args.session.policy.iter().any(|policy| policy == "-"),
+        ),
+        (
+            "--saml-assertion",
+            args.saml_assertion.as_deref() == Some("-"),
+        ),
+        ("--token-code", args.mfa.reads_stdin()),
+    ]
+    .into_iter()
+    .filter_map(|(name, reads)| reads.then_some(format!("`{name} -`")))
+    .collect::<Vec<_>>();
+    if stdin.len() > 1 {
+        bail!(
+            "{} cannot read the standard input together",
+            stdin.join(" and ")
+        );
+    }
+
+    let local_cache = args.cache.local(config_file)?;
+    let aws_cli_cache = Cache::aws_cli()?;
+
+    let mut role = match (args.role.take(), &config_file.role) {
+        (Some(role), _) => role,
+        (None, _) if !args.role_tag.is_empty() => {
+            roles::find_by_tags(config, &parse_tags(&args.role_tag)?).await?
+        }
+        (None, Some(role)) => role.clone(),
+        (None, None) => {
+            let mut candidates = config_file
+                .presets
+                .keys()
+                .chain(config_file.chains.keys())
+                .cloned()
+                .collect::<Vec<_>>();
+            for role in cache::recent_role_arns(&[&local_cache, &aws_cli_cache])
+                .await?
+                .into_iter()
+                .chain(roles::cached().await)
+            {
+                if !candidates.contains(&role) {
+                    candidates.push(role);
+                }
+            }
+            prompt::select("Role", candidates).await?
+        }
+    };
+
+    args.session.tag = args.session.tags().await?;
+
+    // A preset supplies the defaults of the parameters not specified on the command line.
+    if let Some(preset) = config_file.preset(&role)? {
+        role = preset.role.clone();
+        args.role_session_name = args
+            .role_session_name
+            .or_else(|| preset.role_session_name.clone());
+        args.duration_seconds = args.duration_seconds.or(preset.duration_seconds);
+        args.external_id = args.external_id.or_else(|| preset.external_id.clone());
+        args.mfa.serial_number = args
+            .mfa
+            .serial_number
+            .or_else(|| preset.serial_number.clone());
+        merge_tags(&mut args.session.tag, &preset.tags);
+        if args.transitive_tag_key.is_empty() {
+            args.transitive_tag_key = preset.transitive_tag_keys.clone();
+        }
+        if args.session.policy_arn.is_empty() {
+            args.session.policy_arn = preset.policy_arns.clone();
+        }
+        if args.session.policy.is_empty() {
+            args.session.policy = preset.policy.iter().cloned().collect();
+        }
+        args.source_identity = args
+            .source_identity
+            .or_else(|| preset.source_identity.clone());
+    }
+    args.mfa.apply_defaults(&config_file.mfa);
+
+    // A named chain supplies the intermediate roles and the defaults of the last one.
+    let via = if let Some(chain) = config_file.chains.get(&role) {
+        if !args.via.is_empty() {
+            bail!("`--via` cannot be used with the chain `{role}`");
+        }
+
+        let (last, via) = chain
+            .split_last()
+            .ok_or_else(|| anyhow!("the chain `{role}` is empty"))?;
+        role = last.role.clone();
+        args.external_id = args.external_id.or_else(|| last.external_id.clone());
+        args.duration_seconds = args.duration_seconds.or(last.duration_seconds);
+        merge_tags(&mut args.session.tag, &last.tags);
+        via.to_vec()
+    } else {
+        args.via
+            .iter()
+            .map(|role| ChainHop {
+                role: role.clone(),
+                ..Default::default()
+            })
+            .collect()
+    };
+
+    let oidc_source = match args.oidc {
+        _ if args.github_oidc => Some(oidc::TokenSource::Github),
+        Some(Some(source)) => Some(source),
+        Some(None) => Some(
+            config_file
+                .oidc
+                .token_source
+                .clone()
+                .ok_or_else(|| anyhow!("`oidc.token_source` is not configured"))?,
+        ),
+        None => None,
+    };
+    let oidc_audience = args
+        .oidc_audience
+        .or_else(|| config_file.oidc.audience.clone())
+        .unwrap_or_else(|| oidc::DEFAULT_AUDIENCE.to_string());
+
+    // Federated requests are authenticated by the IdP, not by the credentials of the caller.
+    let idp = args.okta.enabled() || args.entra.enabled();
+    let saml = args.saml_assertion.is_some() || idp;
+    let federated = saml || oidc_source.is_some();
+
+    // Signing in to the IdP reveals the roles available, so it is done early to resolve the name.
+    let mut assertion = None;
+
+    let (account_id, role_name) = match (&args.account_id, role.split_once('/')) {
+        (Some(account), _) => (
+            Some(config_file.account_id(account).to_string()),
+            role.as_str(),
+        ),
+        (None, Some((account, name)))
+            if config_file.accounts.contains_key(account) || validate::is_account_id(account) =>
+        {
+            (Some(config_file.account_id(account).to_string()), name)
+        }
+        _ => (None, role.as_str()),
+    };
+    let account_id = match (&args.account, account_id) {
+        (Some(name), _) if !role.starts_with("arn:") => {
+            Some(organizations::account_id(config, name).await?)
+        }
+        (_, account_id) => account_id,
+    };
+
+    let role_arn = if role.starts_with("arn:") {
+        role
+    } else if let Some(account_id) = account_id {
+        format!(
+            "arn:{}:iam::{account_id}:role/{role_name}",
+            partition(config)
+        )
+    } else if args.cache.offline {
+        cache::find_role_arn(&[&local_cache, &aws_cli_cache], &role)
+            .await?
+            .ok_or_else(|| anyhow!("no cached session of `{role}` (offline mode)"))?
+    } else if idp {
+        let saml_assertion = sign_in(&args.okta, &args.entra, config_file).await?;
+        let role = saml::select(&saml_assertion, &role)?;
+        assertion = Some(saml_assertion);
+        role.role_arn
+    } else if federated {
+        bail!("`--role` must be an ARN for federated access");
+    } else if !via.is_empty() {
+        bail!("`--role` must be an ARN when chaining roles");
+    } else {
+        lookup_role_arn(config, &role).await?
+    };
+
+    let tags = parse_tags(&args.session.tag)?;
+    let policy = args.session.policy(config_file).await?;
+
+    // Requests that can be expressed in an AWS CLI profile share the cache with the AWS CLI, unless
+    // the cache is placed elsewhere on purpose.
+    let aws_cli_compatible = matches!(args.cache.backend(config_file), cache::Backend::File)
+        && !args.cache.cache_memory_only
+        && args.cache.cache_dir.is_none()
+        && args.session.policy_arn.is_empty()
+        && policy.is_none()
+        && tags.is_empty()
+        && args.transitive_tag_key.is_empty()
+        && args.source_identity.is_none()
+        && !federated
+        && via.is_empty()
+        && args.mfa.serial_number.as_deref() != Some(mfa::AUTO);
+
+    let (cache, key) = if aws_cli_compatible {
+        let key = cache::aws_cli_key(
+            &role_arn,
+            args.external_id.as_deref(),
+            args.mfa.serial_number.as_deref(),
+            args.duration_seconds,
+        );
+        (aws_cli_cache, key)
+    } else {
+        let key = cache::key(&Parameters {
+            via: &via,
+            role_arn: &role_arn,
+            role_session_name: args.role_session_name.as_deref(),
+            policy_arns: &args.session.policy_arn,
+            policy: policy.as_deref(),
+            duration_seconds: args.duration_seconds,
+            tags: &tags,
+            transitive_tag_keys: &args.transitive_tag_key,
+            external_id: args.external_id.as_deref(),
+            serial_number: args.mfa.serial_number.as_deref(),
+            source_identity: args.source_identity.as_deref(),
+            principal_arn: args.principal_arn.as_deref(),
+            oidc_audience: oidc_source.is_some().then_some(oidc_audience.as_str()),
+        })?;
+        (local_cache, key)
+    };
+
+    let (session, _lock) = args.cache.lookup(&cache, &key).await?;
+    if let Some(session) = session {
+        return Ok(session);
+    }
+
+    if config_file
+        .approval
+        .roles
+        .iter()
+        .any(|pattern| approval::matches(pattern, &role_arn))
+    {
+        approval::approve(&role_arn).await?;
+    }
+
+    let role_session_name = args
+        .role_session_name
+        .unwrap_or_else(|| format!("assume-role@{}", Utc::now().timestamp()));
+    // The managed policies must be in the account of the role.
+    let partition = role_arn.split(':').nth(1).unwrap_or("aws");
+    let account = role_arn.split(':').nth(4);
+    let policy_arns = resolve_policy_arns(config, partition, account, &args.session.policy_arn)
+        .await?
+        .into_iter()
+        .map(|arn| PolicyDescriptorType::builder().arn(arn).build())
+        .collect();
+
+    let session = if saml {
+        let assertion = match (assertion, &args.saml_assertion) {
+            (Some(assertion), _) => assertion,
+            (None, Some(path)) => read_input(path).await?.trim().to_string(),
+            (None, None) => sign_in(&args.okta, &args.entra, config_file).await?,
+        };
+        let principal_arn = match args.principal_arn {
+            Some(principal_arn) => principal_arn,
+            None => saml::select(&assertion, &role_arn)?.principal_arn,
+        };
+
+        let response = anonymous(config)
+            .assume_role_with_saml()
+            .role_arn(&role_arn)
+            .principal_arn(principal_arn)
+            .saml_assertion(assertion)
+            .set_policy_arns(Some(policy_arns))
+            .set_policy(policy)
+            .set_duration_seconds(args.duration_seconds)
+            .send()
+            .await?;
+
+        Session::new(
+            response.credentials,
+            response.assumed_role_user,
+            response.packed_policy_size,
+            response.source_identity,
+        )?
+    } else if let Some(source) = &oidc_source {
+        let response = anonymous(config)
+            .assume_role_with_web_identity()
+            .role_arn(&role_arn)
+            .role_session_name(role_session_name)
+            .web_identity_token(source.token(&oidc_audience).await?)
+            .set_policy_arns(Some(policy_arns))
+            .set_policy(policy)
+            .set_duration_seconds(args.duration_seconds)
+            .send()
+            .await?;
+
+        Session::new(
+            response.credentials,
+            response.assumed_role_user,
+            response.packed_policy_size,
+            response.source_identity,
+        )?
+    } else {
+        let mut mfa = args.mfa.resolve(config, config_file).await?;
+
+        // The MFA authenticates the first hop; the later hops are authorized by the previous role.
+        let mut config = config.clone();
+        for hop in &via {
+            config = assume_hop(&config, hop, &role_session_name, &mut mfa).await?;
+            mfa = Mfa::default();
+        }
+
+        if args.preflight {
+            preflight::check(&config, &role_arn, mfa.serial_number.is_some()).await?;
+        }
+
+        let mut request = aws_sdk_sts::Client::new(&config)
+            .assume_role()
+            .role_arn(&role_arn)
+            .role_session_name(role_session_name)
+            .set_policy_arns(Some(policy_arns))
+            .set_policy(policy)
+            .set_duration_seconds(args.duration_seconds)
+            .set_transitive_tag_keys(Some(args.transitive_tag_key))
+            .set_external_id(args.external_id)
+            .set_serial_number(mfa.serial_number.clone())
+            .set_source_identity(args.source_identity);
+
+        for (key, value) in tags {
+            request = request.tags(Tag::builder().key(key).value(value).build()?);
+        }
+
+        let response = send(request, &mut mfa).await?;
+
+        Session::new(
+            response.credentials,
+            response.assumed_role_user,
+            response.packed_policy_size,
+            response.source_identity,
+        )?
+    };
+
+    session.check_packed_size(args.session.packed_size_warning);
+
+    if !args.cache.no_cache {
+        if let Err(e) = cache.store(&key, Some(&role_arn), &session).await {
+            tracing::warn!("failed to cache credentials: {e:#}");
+        }
+    }
+
+    Ok(session)
+}
+
+/// Sends the `AssumeRole` request, obtaining a fresh MFA code while it is rejected.
+async fn send(request: AssumeRoleFluentBuilder, mfa: &mut Mfa) -> Result<AssumeRoleOutput> {
+    loop {
+        match request
+            .clone()
+            .set_token_code(mfa.token_code.clone())
+            .send()
+            .await
+        {
+            Ok(response) => return Ok(response),
+            Err(e) if mfa.retry(&e).await? => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Assumes the intermediate role of a chain and returns the configuration with its credentials.
+async fn assume_hop(
+    config: &SdkConfig,
+    hop: &ChainHop,
+    role_session_name: &str,
+    mfa: &mut Mfa,
+) -> Result<SdkConfig> {
+    let role_arn = if hop.role.starts_with("arn:") {
+        hop.role.clone()
+    } else {
+        lookup_role_arn(config, &hop.role).await?
+    };
+    tracing::debug!(role_arn, "assuming intermediate role");
+
+    let mut request = aws_sdk_sts::Client::new(config)
+        .assume_role()
+        .role_arn(role_arn)
+        .role_session_name(role_session_name)
+        .set_external_id(hop.external_id.clone())
+        .set_duration_seconds(hop.duration_seconds)
+        .set_serial_number(mfa.serial_number.clone());
+    for (key, value) in &hop.tags {
+        request = request.tags(Tag::builder().key(key).value(value).build()?);
+    }
+    let credentials = send(request, mfa)
+        .await?
+        .credentials
+        .ok_or_else(|| anyhow!("no credentials provided"))?;
+
+    let credentials = aws_sdk_sts::config::Credentials::new(
+        credentials.access_key_id(),
+        credentials.secret_access_key(),
+        Some(credentials.session_token().to_string()),
+        SystemTime::try_from(*credentials.expiration()).ok(),
+        "assume-role",
+    );
+    Ok(config
+        .to_builder()
+        .credentials_provider(SharedCredentialsProvider::new(credentials))
+        .build())
+}
+
+/// Signs in to the IdP and returns the SAML assertion.
+async fn sign_in(
+    okta: &okta::OktaArgs,
+    entra: &entra::EntraArgs,
+    config_file: &ConfigFile,
+) -> Result<String> {
+    if entra.enabled() {
+        entra.saml_assertion(&config_file.entra).await
+    } else {
+        okta.saml_assertion(&config_file.okta).await
+    }
+}
+
+/// Looks up the ARN of the role `name` in the account of the caller.
+///
+/// If the caller is not allowed to get the role, the ARN is built from the account of the caller
+/// since `AssumeRole` may still be permitted.
+async fn lookup_role_arn(config: &SdkConfig, name: &str) -> Result<String> {
+    let iam = aws_sdk_iam::Client::new(config);
+    match iam.get_role().role_name(name).send().await {
+        Ok(response) => Ok(response
+            .role()
+            .ok_or_else(|| anyhow!("role is not provided"))
+            .map(|r| r.arn())?
+            .to_string()),
+        Err(e) if e.code() == Some("AccessDenied") => {
+            tracing::debug!("iam:GetRole is denied, building the ARN from the caller identity");
+            let identity = aws_sdk_sts::Client::new(config)
+                .get_caller_identity()
+                .send()
+                .await?;
+            let caller_arn = identity
+                .arn()
+                .ok_or_else(|| anyhow!("caller ARN is not provided"))?;
+            let partition = caller_arn.split(':').nth(1).unwrap_or("aws");
+            let account = identity
+                .account()
+                .ok_or_else(|| anyhow!("account is not provided"))?;
+            Ok(format!("arn:{partition}:iam::{account}:role/{name}"))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Resolves the values of `--policy-arn` into the ARNs of managed policies in `partition`.
+///
+/// `NAME` or `aws:NAME`, optionally with the path such as `job-function/ViewOnlyAccess`, is an AWS
+/// managed policy. `customer:NAME` is a customer managed policy found in the account of the
+/// caller, or in `account` if it cannot be listed there.
+pub async fn resolve_policy_arns(
+    config: &SdkConfig,
+    partition: &str,
+    account: Option<&str>,
+    values: &[String],
+) -> Result<Vec<String>> {
+    let customer_policies = if values.iter().any(|v| v.starts_with("customer:")) {
+        list_customer_policies(config).await
+    } else {
+        Ok(Vec::new())
+    };
+
+    let mut arns = Vec::with_capacity(values.len());
+    for value in values {
+        if value.starts_with("arn:") {
+            arns.push(value.clone());
+        } else if let Some(name) = value.strip_prefix("customer:") {
+            let found = match &customer_policies {
+                Ok(policies) => policies
+                    .iter()
+                    .find(|(n, arn)| {
+                        n == name && account.is_none_or(|a| arn.split(':').nth(4) == Some(a))
+                    })
+                    .map(|(_, arn)| arn.clone()),
+                Err(e) => {
+                    tracing::debug!("failed to list the customer managed policies: {e:#}");
+                    None
+                }
+            };
+            let arn = match (found, account) {
+                (Some(arn), _) => arn,
+                (None, Some(account)) => format!("arn:{partition}:iam::{account}:policy/{name}"),
+                (None, None) => bail!("no customer managed policy named `{name}`"),
+            };
+            arns.push(arn);
+        } else {
+            let name = value.strip_prefix("aws:").unwrap_or(value);
+            arns.push(format!("arn:{partition}:iam::aws:policy/{name}"));
+        }
+    }
+
+    Ok(arns)
+}
+
+/// Returns the names and the ARNs of the customer managed policies in the account of the caller.
+async fn list_customer_policies(config: &SdkConfig) -> Result<Vec<(String, String)>> {
+    let iam = aws_sdk_iam::Client::new(config);
+    let mut stream = iam
+        .list_policies()
+        .scope(PolicyScopeType::Local)
+        .into_paginator()
+        .items()
+        .send();
+    let mut policies = Vec::new();
+    while let Some(policy) = stream.next().await {
+        let policy = policy?;
+        if let (Some(name), Some(arn)) = (policy.policy_name(), policy.arn()) {
+            policies.push((name.to_string(), arn.to_string()));
+        }
+    }
+
+    Ok(policies)
+}
+
+/// Returns the partition of the region of `config`.
+pub fn partition(config: &SdkConfig) -> &'static str {
+    match config.region().map(|r| r.as_ref()) {
+        Some(region) if region.starts_with("cn-") => "aws-cn",
+        Some(region) if region.starts_with("us-gov-") => "aws-us-gov",
+        _ => "aws",
+    }
+}
+
+/// Adds `defaults` to the session tags in the form of `KEY=VALUE` unless the key is specified.
+fn merge_tags(tags: &mut Vec<String>, defaults: &BTreeMap<String, String>) {
+    for (key, value) in defaults {
+        if !tags.iter().any(|t| t.split('=').next() == Some(key)) {
+            tags.push(format!("{key}={value}"));
+        }
+    }
+}
+
+/// Returns the session tags of `--tag` in the form of `KEY=VALUE`, where `KEY` alone takes the
+/// value of the environment variable of the name.
+fn expand_tags(tags: &[String]) -> Result<Vec<String>> {
+    let mut expanded = Vec::with_capacity(tags.len());
+    for tag in tags {
+        if tag.contains('=') {
+            expanded.push(tag.clone());
+        } else if let Ok(value) = std::env::var(tag) {
+            expanded.push(format!("{tag}={value}"));
+        } else {
+            bail!(
+                "illegal tag: `{tag}` is neither `KEY=VALUE` nor the name of an environment variable"
+            );
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Parses session tags in the form of `KEY=VALUE`.
+pub fn parse_tags(tags: &[String]) -> Result<Vec<(String, String)>> {
+    let mut parsed = Vec::with_capacity(tags.len());
+    for tag in tags {
+        if let Some((key, value)) = tag.split_once('=') {
+            parsed.push((key.to_string(), value.to_string()));
+        } else {
+            return Err(anyhow!("illegal tag: `{tag}`"));
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Loads the IAM policies named `names` in the config file and at `paths`, with the variables
+/// `vars` in the form of `KEY=VALUE` replaced, and returns them merged into one as JSON, or `None`
+/// if no policy is specified.
+async fn load_policies(
+    config_file: &ConfigFile,
+    names: &[String],
+    paths: &[String],
+    vars: &[String],
+) -> Result<Option<String>> {
+    let mut placeholders = Vec::with_capacity(vars.len());
+    for var in vars {
+        let Some((key, value)) = var.split_once('=') else {
+            bail!("illegal policy variable: `{var}`");
+        };
+        placeholders.push((format!("${{{key}}}"), value));
+    }
+
+    let mut policies = Vec::with_capacity(names.len() + paths.len());
+    for name in names {
+        let policy = config_file
+            .policies
+            .get(name)
+            .ok_or_else(|| anyhow!("no policy named `{name}` in the config file"))?;
+        policies.push(serde_json::to_value(policy).context("malformed policy")?);
+    }
+    for path in paths {
+        policies.push(load_policy(path).await?);
+    }
+    for policy in &mut policies {
+        replace_placeholders(policy, &placeholders);
+    }
+
+    let policy = match policies.len() {
+        0 => return Ok(None),
+        // A single policy is sent as is to keep its `Version` and `Id`.
+        1 => policies.remove(0),
+        _ => merge_policies(policies)?,
+    };
+    // The policy is serialized without whitespace, which counts toward the limit.
+    let policy = serde_json::to_string(&policy).context("malformed policy")?;
+    let length = policy.chars().count();
+    if length > MAX_POLICY_LENGTH {
+        bail!(
+            "the inline session policy is {length} characters, {} over the limit of \
+             {MAX_POLICY_LENGTH}",
+            length - MAX_POLICY_LENGTH
+        );
+    }
+    Ok(Some(policy))
+}
+
+/// Replaces the placeholders in the keys and the strings of `value` with their values.
+///
+/// The values are not scanned again, so that a value cannot expand into another placeholder.
+fn replace_placeholders(value: &mut serde_json::Value, placeholders: &[(String, &str)]) {
+    let replace = |s: &str| {
+        let mut output = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find("${") {
+            output.push_str(&rest[..i]);
+            rest = &rest[i..];
+            match placeholders
+                .iter()
+                .find(|(placeholder, _)| rest.starts_with(placeholder.as_str()))
+            {
+                Some((placeholder, value)) => {
+                    output.push_str(value);
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    output.push_str("${");
+                    rest = &rest[2..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    };
+    match value {
+        serde_json::Value::String(s) => *s = replace(s),
+        serde_json::Value::Array(values) => {
+            for value in values {
+                replace_placeholders(value, placeholders);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    replace_placeholders(&mut value, placeholders);
+                    (replace(&key), value)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+/// Merges the statements of `policies` into one policy, dropping the duplicates of a statement
+/// with the same `Sid`.
+fn merge_policies(policies: Vec<serde_json::Value>) -> Result<serde_json::Value> {
+    let mut statements: Vec<serde_json::Value> = Vec::new();
+    for mut policy in policies {
+        let statement = match policy.get_mut("Statement").map(serde_json::Value::take) {
+            Some(serde_json::Value::Array(statements)) => statements,
+            Some(statement @ serde_json::Value::Object(_)) => vec![statement],
+            _ => bail!("no statement in policy"),
+        };
+        for statement in statement {
+            let sid = statement.get("Sid").and_then(|sid| sid.as_str());
+            match statements
+                .iter()
+                .find(|s| sid.is_some() && s.get("Sid").and_then(|sid| sid.as_str()) == sid)
+            {
+                Some(existing) if *existing == statement => {}
+                Some(_) => bail!(
+                    "conflicting statements with Sid `{}`",
+                    sid.unwrap_or_default()
+                ),
+                None => statements.push(statement),
+            }
+        }
+    }
+
+    Ok(serde_json::json!({
+        "Version": "2012-10-17",
+        "Statement": statements,
+    }))
+}
+
+/// Loads the IAM policy in JSON or YAML at `path`, or from the standard input if `path` is `-`.
+/// `path` that starts with `{` is the policy itself.
+async fn load_policy(path: &str) -> Result<serde_json::Value> {
+    // The standard input can be read only once, but the policy is loaded whenever the role is
+    // assumed again.
+    static STDIN: OnceCell<String> = OnceCell::const_new();
+
+    let value: serde_yaml::Value = if path.trim_start().starts_with('{') {
+        serde_yaml::from_str(path).context("malformed policy")?
+    } else if path == "-" {
+        let content = STDIN
+            .get_or_try_init(|| async {
+                let mut content = String::new();
+                tokio::io::stdin().read_to_string(&mut content).await?;
+                anyhow::Ok(content)
+            })
+            .await
+            .context("failed to read the policy from the standard input")?;
+        serde_yaml::from_str(content)
+            .context("failed to read the policy from the standard input")?
+    } else {
+        let f = File::open(path)
+            .await
+            .with_context(|| format!("failed to open `{path}`"))?
+            .into_std()
+            .await;
+        serde_yaml::from_reader(f).with_context(|| format!("failed to read `{path}`"))?
+    };
+
+    serde_json::to_value(&value).context("malformed policy")
+}
+
+/// Returns an STS client that sends requests without signing them.
+fn anonymous(config: &SdkConfig) -> aws_sdk_sts::Client {
+    let mut builder = aws_sdk_sts::config::Builder::from(config);
+    builder.set_credentials_provider(None);
+    aws_sdk_sts::Client::from_conf(builder.build())
+}
+
+/// Loads the session tags from the mapping in JSON or YAML at `path`.
+async fn load_tags(path: &Path) -> Result<BTreeMap<String, String>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let mapping: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(&content)
+        .with_context(|| format!("failed to parse `{}`", path.display()))?;
+
+    let mut tags = BTreeMap::new();
+    for (key, value) in mapping {
+        let value = match value {
+            serde_yaml::Value::String(s) => s,
+            serde_yaml::Value::Number(n) => n.to_string(),
+            serde_yaml::Value::Bool(b) => b.to_string(),
+            _ => bail!(
+                "the value of the tag `{key}` in `{}` is not a scalar",
+                path.display()
+            ),
+        };
+        tags.insert(key, value);
+    }
+
+    Ok(tags)
+}
+
+/// Returns the environment variables whose names start with any of `prefixes` as session tags.
+///
+/// The variables that look like secrets, such as `CI_JOB_TOKEN` of GitLab, are skipped since the
+/// tags are recorded in CloudTrail.
+fn env_tags(prefixes: &[String]) -> BTreeMap<String, String> {
+    const SECRETS: &[&str] = &["TOKEN", "PASSWORD", "SECRET", "KEY"];
+
+    if prefixes.is_empty() {
+        return BTreeMap::new();
+    }
+
+    std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(key, _)| {
+            prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix.as_str()))
+        })
+        .filter(|(key, _)| {
+            let secret = SECRETS.iter().any(|s| key.to_uppercase().contains(s));
+            if secret {
+                tracing::debug!("skipped `{key}` that may be a secret");
+            }
+            !secret
+        })
+        .collect()
+}
+
+/// Reads the content of the file at `path`, or the standard input if `path` is `-`.
+async fn read_input(path: &str) -> Result<String> {
+    if path == "-" {
+        let mut input = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut input)
+            .await
+            .context("failed to read the standard input")?;
+        Ok(input)
+    } else {
+        tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read `{path}`"))
+    }
+}
+
+/// Returns the environment variables that expose `credentials` to the AWS SDKs and CLI.
+pub fn environment(credentials: &Credentials) -> [(&'static str, &str); 3] {
+    [
+        ("AWS_ACCESS_KEY_ID", credentials.access_key_id()),
+        ("AWS_SECRET_ACCESS_KEY", credentials.secret_access_key()),
+        ("AWS_SESSION_TOKEN", credentials.session_token()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_statements() {
+        let merged = merge_policies(vec![
+            json!({
+                "Version": "2012-10-17",
+                "Id": "base",
+                "Statement": [{"Effect": "Deny", "Action": "iam:*", "Resource": "*"}],
+            }),
+            json!({
+                "Statement": {"Effect": "Allow", "Action": "s3:Get*", "Resource": "*"},
+            }),
+        ])
+        .unwrap();
+        assert_eq!(
+            merged,
+            json!({
+                "Version": "2012-10-17",
+                "Statement": [
+                    {"Effect": "Deny", "Action": "iam:*", "Resource": "*"},
+                    {"Effect": "Allow", "Action": "s3:Get*", "Resource": "*"},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn merge_same_sid() {
+        let statement =
+            json!({"Sid": "Read", "Effect": "Allow", "Action": "s3:Get*", "Resource": "*"});
+        let merged = merge_policies(vec![
+            json!({"Statement": [statement.clone()]}),
+            json!({"Statement": [statement.clone()]}),
+        ])
+        .unwrap();
+        assert_eq!(merged["Statement"], json!([statement]));
+
+        let conflicting =
+            json!({"Sid": "Read", "Effect": "Deny", "Action": "s3:*", "Resource": "*"});
+        let error = merge_policies(vec![
+            json!({"Statement": [statement]}),
+            json!({"Statement": [conflicting]}),
+        ])
+        .unwrap_err();
+        assert_eq!(error.to_string(), "conflicting statements with Sid `Read`");
+    }
+
+    #[test]
+    fn merge_without_statement() {
+        assert!(merge_policies(vec![
+            json!({"Statement": []}),
+            json!({"Version": "2012-10-17"})
+        ])
+        .is_err());
+        assert!(merge_policies(vec![json!({"Statement": "*"})]).is_err());
+    }
+
+    #[test]
+    fn placeholders() {
+        let mut policy = json!({
+            "Statement": [{
+                "Effect": "Allow",
+                "Action": "s3:GetObject",
+                "Resource": "arn:aws:s3:::${Bucket}/${Prefix}/*",
+                "Condition": {"StringEquals": {"${Key}": "${aws:username}"}},
+                "Sid": 1,
+            }],
+        });
+        replace_placeholders(
+            &mut policy,
+            &[
+                ("${Bucket}".to_string(), "my-bucket"),
+                ("${Prefix}".to_string(), "${Bucket}"),
+                ("${Key}".to_string(), "s3:prefix"),
+            ],
+        );
+        assert_eq!(
+            policy,
+            json!({
+                "Statement": [{
+                    "Effect": "Allow",
+                    "Action": "s3:GetObject",
+                    "Resource": "arn:aws:s3:::my-bucket/${Bucket}/*",
+                    "Condition": {"StringEquals": {"s3:prefix": "${aws:username}"}},
+                    "Sid": 1,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let tags = ["Team=core=infra", "CARGO_PKG_NAME"].map(String::from);
+        // Cargo sets the name of the package when it runs the tests.
+        let expanded = expand_tags(&tags).unwrap();
+        assert_eq!(expanded, ["Team=core=infra", "CARGO_PKG_NAME=assume-role"]);
+        assert_eq!(
+            parse_tags(&expanded).unwrap(),
+            [
+                ("Team".to_string(), "core=infra".to_string()),
+                ("CARGO_PKG_NAME".to_string(), "assume-role".to_string()),
+            ]
+        );
+
+        assert!(expand_tags(&["ASSUME_ROLE_NO_SUCH_VARIABLE".to_string()]).is_err());
+        // Only `--tag` takes the values from the environment.
+        assert!(parse_tags(&["CARGO_PKG_NAME".to_string()]).is_err());
+    }
+}
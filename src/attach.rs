@@ -0,0 +1,92 @@
+use crate::exec;
+use crate::registry::{self, Address, Server};
+use crate::serve::ATTACH_PATH;
+use anyhow::{bail, Result};
+use aws_sdk_sts::types::Credentials;
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use hyper::{body, header, Body, Request, Response, StatusCode};
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The name of the running `serve` or `daemon`.
+    name: String,
+
+    /// Pass the command how to obtain the credentials from the server, instead of the current
+    /// credentials.
+    #[arg(long, env = "ASSUME_ROLE_PROXY")]
+    proxy: bool,
+
+    #[command(flatten)]
+    options: exec::RunOptions,
+
+    /// A command and its arguments to run with the credentials. Runs current shell if not specified.
+    command: Vec<String>,
+}
+
+/// The credentials in the format of the container credentials provider.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Document {
+    access_key_id: String,
+    secret_access_key: String,
+    token: String,
+    expiration: String,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    let instance = registry::find(&args.name).await?;
+
+    match &instance.server {
+        Some(server) if !args.proxy => {
+            exec::execute(|| fetch(server), &args.command, &args.options).await
+        }
+        _ => {
+            let env = instance
+                .env
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone()))
+                .collect::<Vec<_>>();
+            let status = exec::execute_with_env(&env, &args.command, &args.options).await?;
+            exec::exit(status)
+        }
+    }
+}
+
+/// Obtains the current credentials from `server`.
+pub async fn fetch(server: &Server) -> Result<Credentials> {
+    let request = Request::get(ATTACH_PATH)
+        .header(header::HOST, "localhost")
+        .header(header::AUTHORIZATION, &server.token)
+        .body(Body::empty())?;
+    let response = match &server.address {
+        Address::Tcp(address) => send(TcpStream::connect(address).await?, request).await?,
+        #[cfg(unix)]
+        Address::Unix(path) => send(tokio::net::UnixStream::connect(path).await?, request).await?,
+        #[cfg(not(unix))]
+        Address::Unix(_) => bail!("unix domain sockets are not supported"),
+    };
+
+    if response.status() != StatusCode::OK {
+        bail!("the server responded with {}", response.status());
+    }
+    let document: Document = serde_json::from_slice(&body::to_bytes(response).await?)?;
+    Ok(Credentials::builder()
+        .access_key_id(document.access_key_id)
+        .secret_access_key(document.secret_access_key)
+        .session_token(document.token)
+        .expiration(DateTime::from_str(&document.expiration, Format::DateTime)?)
+        .build()?)
+}
+
+async fn send<S>(stream: S, request: Request<Body>) -> Result<Response<Body>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::handshake(stream).await?;
+    tokio::spawn(connection);
+    Ok(sender.send_request(request).await?)
+}
@@ -0,0 +1,80 @@
+use anyhow::{bail, Context as _, Result};
+use std::process::{Command, Stdio};
+
+/// The name to run Firefox with.
+pub const FIREFOX: &str = if cfg!(target_os = "macos") {
+    "Firefox"
+} else {
+    "firefox"
+};
+
+/// Opens `url` with `browser`, or the default browser if not specified, in the profile named
+/// `profile` if any.
+pub fn open(url: &str, browser: Option<&str>, profile: Option<&str>) -> Result<()> {
+    let mut cmd = match browser {
+        Some(browser) => {
+            let mut cmd = launcher(browser);
+            if let Some(profile) = profile {
+                cmd.args(profile_args(browser, profile));
+            }
+            cmd
+        }
+        None if profile.is_some() => bail!("the browser must be specified to use a profile"),
+        None => default_launcher(),
+    };
+    cmd.arg(url);
+
+    // The browser may keep running after the URL is opened, so it is not waited for.
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", cmd.get_program().to_string_lossy()))?;
+    Ok(())
+}
+
+/// Returns the URL that opens `url` in the container of Firefox named `name`, which is handled by
+/// the extension "Open external links in a container".
+pub fn container_url(url: &str, name: &str) -> String {
+    format!(
+        "ext+container:name={}&url={}",
+        urlencoding::encode(name),
+        urlencoding::encode(url)
+    )
+}
+
+/// Returns the command to run `browser` with the arguments that follow.
+fn launcher(browser: &str) -> Command {
+    // Applications on macOS are launched by their names, which are not in `PATH`.
+    if cfg!(target_os = "macos") && !browser.contains('/') {
+        let mut cmd = Command::new("open");
+        cmd.args(["-na", browser, "--args"]);
+        cmd
+    } else {
+        Command::new(browser)
+    }
+}
+
+/// Returns the command to open a URL with the default browser.
+fn default_launcher() -> Command {
+    if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // `start` of `cmd` would interpret the `&` in URLs.
+        let mut cmd = Command::new("rundll32");
+        cmd.arg("url.dll,FileProtocolHandler");
+        cmd
+    } else {
+        Command::new("xdg-open")
+    }
+}
+
+/// Returns the arguments to select the profile of `browser`, which are the ones of Firefox or of
+/// the browsers based on Chromium.
+fn profile_args(browser: &str, profile: &str) -> Vec<String> {
+    if browser.to_lowercase().contains("firefox") {
+        vec!["-P".to_string(), profile.to_string()]
+    } else {
+        vec![format!("--profile-directory={profile}")]
+    }
+}
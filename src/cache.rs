@@ -0,0 +1,734 @@
+use crate::assume::Session;
+use crate::config::{CacheConfig, ConfigFile};
+use crate::{crypt, dirs, duration, file, keyring};
+use anyhow::{bail, Context as _, Result};
+use aws_sdk_sts::types::{AssumedRoleUser, Credentials};
+use aws_smithy_types::date_time::{DateTime, Format};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::fs;
+
+/// Options of the cache shared by the commands that obtain credentials.
+#[derive(Clone, clap::Args)]
+pub struct Options {
+    /// Do not reuse cached credentials nor cache the credentials.
+    #[arg(long, env = "ASSUME_ROLE_NO_CACHE")]
+    pub no_cache: bool,
+
+    /// Where to store cached credentials. Defaults to `cache.backend` in the config file or `file`.
+    #[arg(long, value_enum, env = "ASSUME_ROLE_CACHE_BACKEND")]
+    cache_backend: Option<Backend>,
+
+    /// The directory of the file cache.
+    #[arg(long, value_name = "PATH", env = "ASSUME_ROLE_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Keep the file cache on a memory-backed file system so that credentials never reach the disk.
+    #[arg(
+        long,
+        conflicts_with = "cache_dir",
+        env = "ASSUME_ROLE_CACHE_MEMORY_ONLY"
+    )]
+    pub cache_memory_only: bool,
+
+    /// The minimum remaining lifetime of cached credentials to reuse them.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = duration::parse,
+        default_value = "5m",
+        env = "ASSUME_ROLE_MIN_TTL"
+    )]
+    pub min_ttl: Duration,
+
+    /// Use only cached credentials and never call AWS.
+    #[arg(long, conflicts_with = "no_cache", env = "ASSUME_ROLE_OFFLINE")]
+    pub offline: bool,
+}
+
+impl Options {
+    /// Returns the selected backend.
+    pub fn backend(&self, config_file: &ConfigFile) -> Backend {
+        self.cache_backend
+            .or(config_file.cache.backend)
+            .unwrap_or_default()
+    }
+
+    /// Returns the cache of this application.
+    pub fn local(&self, config_file: &ConfigFile) -> Result<Cache> {
+        let dir = if self.cache_memory_only {
+            Cache::memory_dir()?
+        } else {
+            self.cache_dir.clone().map_or_else(Cache::default_dir, Ok)?
+        };
+        Ok(Cache::new(
+            self.backend(config_file),
+            dir,
+            &config_file.cache,
+        ))
+    }
+
+    /// Returns the cached session for `key` in `cache` unless caching is disabled.
+    ///
+    /// The returned lock must be held until the fresh session is stored so that concurrent
+    /// invocations do not request the same credentials simultaneously. In offline mode, an error
+    /// is returned if no valid session is cached.
+    pub async fn lookup(
+        &self,
+        cache: &Cache,
+        key: &str,
+    ) -> Result<(Option<Session>, Option<file::Lock>)> {
+        if self.no_cache {
+            return Ok((None, None));
+        }
+
+        let lock = if self.offline {
+            None
+        } else {
+            Some(cache.lock(key).await?)
+        };
+
+        match cache.load(key, self.min_ttl).await? {
+            Some(session) => Ok((Some(session), lock)),
+            None if self.offline => bail!("no valid cached credentials (offline mode)"),
+            None => Ok((None, lock)),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+
+    /// The directory of the file cache.
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        env = "ASSUME_ROLE_CACHE_DIR"
+    )]
+    cache_dir: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// List the cached sessions.
+    List,
+
+    /// Delete cached sessions.
+    Clear(ClearArgs),
+}
+
+#[derive(clap::Args)]
+struct ClearArgs {
+    /// Delete only the sessions of the role, specified by its name or ARN.
+    #[arg(long, value_name = "ROLE")]
+    role: Option<String>,
+
+    /// Delete only the expired sessions.
+    #[arg(long)]
+    expired: bool,
+}
+
+pub async fn run(config_file: &ConfigFile, args: Args) -> Result<()> {
+    let config = &config_file.cache;
+    let mut caches = vec![Cache::new(
+        config.backend.unwrap_or_default(),
+        args.cache_dir.map_or_else(Cache::default_dir, Ok)?,
+        config,
+    )];
+    if let Ok(dir) = Cache::memory_dir() {
+        caches.push(Cache::new(Backend::File, dir, config));
+    }
+    caches.push(Cache::aws_cli()?);
+
+    match args.command {
+        Command::List => list(&caches).await,
+        Command::Clear(args) => clear(&caches, args).await,
+    }
+}
+
+async fn list(caches: &[Cache]) -> Result<()> {
+    let now = Utc::now().timestamp();
+
+    let mut rows = Vec::new();
+    for cache in caches {
+        for (key, entry) in cache.entries().await? {
+            let expiration = match entry.expiration() {
+                Ok(expiration) => expiration,
+                Err(e) => {
+                    eprintln!("Skipped `{key}` with the invalid expiration: {e}");
+                    continue;
+                }
+            };
+            let role_arn = entry.role_arn().unwrap_or_else(|| "-".to_string());
+            let session_name = entry
+                .assumed_role_user
+                .as_ref()
+                .and_then(|u| u.arn.rsplit_once('/'))
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let ttl = expiration - now;
+            let remaining = if ttl > 0 {
+                format!("{}h{:02}m{:02}s", ttl / 3600, ttl / 60 % 60, ttl % 60)
+            } else {
+                "expired".to_string()
+            };
+            rows.push((expiration, role_arn, session_name, remaining));
+        }
+    }
+    rows.sort();
+
+    let role_width = rows
+        .iter()
+        .map(|r| r.1.len())
+        .fold("ROLE ARN".len(), usize::max);
+    let session_width = rows
+        .iter()
+        .map(|r| r.2.len())
+        .fold("SESSION NAME".len(), usize::max);
+
+    println!(
+        "{:role_width$}  {:session_width$}  REMAINING",
+        "ROLE ARN", "SESSION NAME"
+    );
+    for (_, role_arn, session_name, remaining) in &rows {
+        println!("{role_arn:role_width$}  {session_name:session_width$}  {remaining}");
+    }
+
+    Ok(())
+}
+
+async fn clear(caches: &[Cache], args: ClearArgs) -> Result<()> {
+    let now = Utc::now().timestamp();
+
+    let mut count = 0;
+    for cache in caches {
+        for (key, entry) in cache.entries().await? {
+            if let Some(role) = &args.role {
+                let Some(role_arn) = entry.role_arn() else {
+                    continue;
+                };
+                if role_arn != *role && !role_arn.ends_with(&format!("/{role}")) {
+                    continue;
+                }
+            }
+
+            // Unreadable expirations are treated as expired.
+            if args.expired && entry.expiration().is_ok_and(|expiration| expiration > now) {
+                continue;
+            }
+
+            cache.remove(&key).await?;
+            count += 1;
+        }
+    }
+
+    eprintln!("Deleted {count} cached session(s)");
+    Ok(())
+}
+
+/// Finds the ARN of the role named `name` among the sessions in `caches`.
+pub async fn find_role_arn(caches: &[&Cache], name: &str) -> Result<Option<String>> {
+    for cache in caches {
+        for (_, entry) in cache.entries().await? {
+            if let Some(role_arn) = entry.role_arn() {
+                if role_arn.ends_with(&format!("/{name}")) {
+                    return Ok(Some(role_arn));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the ARNs of the roles cached in `caches`, the most recently assumed first.
+pub async fn recent_role_arns(caches: &[&Cache]) -> Result<Vec<String>> {
+    let mut roles = Vec::new();
+    for cache in caches {
+        for (_, entry) in cache.entries().await? {
+            if let Some(role_arn) = entry.role_arn() {
+                // Sessions expiring later were obtained more recently, assuming similar durations.
+                roles.push((entry.expiration().unwrap_or_default(), role_arn));
+            }
+        }
+    }
+
+    roles.sort_by_key(|(expiration, _)| std::cmp::Reverse(*expiration));
+    let mut arns = Vec::<String>::new();
+    for (_, role_arn) in roles {
+        if !arns.contains(&role_arn) {
+            arns.push(role_arn);
+        }
+    }
+    Ok(arns)
+}
+
+/// Derives the role ARN from the ARN of an assumed role user,
+/// e.g. `arn:aws:sts::123456789012:assumed-role/Role/Session`.
+pub fn role_arn_of(user_arn: &str) -> Option<String> {
+    let mut parts = user_arn.splitn(6, ':');
+    let (_, partition, _, _, account, resource) = (
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+        parts.next()?,
+    );
+    let (role, _) = resource.strip_prefix("assumed-role/")?.rsplit_once('/')?;
+    Some(format!("arn:{partition}:iam::{account}:role/{role}"))
+}
+
+/// Computes the cache key of a request from its parameters.
+pub fn key(parameters: &impl Serialize) -> Result<String> {
+    let json = serde_json::to_vec(parameters)?;
+    Ok(hex::encode(Sha256::digest(json)))
+}
+
+/// Computes the cache key that the AWS CLI (botocore) uses for an `AssumeRole` request.
+///
+/// The key is the SHA-1 digest of the request parameters serialized by Python's
+/// `json.dumps(..., sort_keys=True)`, excluding the role session name.
+pub fn aws_cli_key(
+    role_arn: &str,
+    external_id: Option<&str>,
+    serial_number: Option<&str>,
+    duration_seconds: Option<i32>,
+) -> String {
+    let mut fields = Vec::new();
+    if let Some(duration_seconds) = duration_seconds {
+        fields.push(format!("\"DurationSeconds\": {duration_seconds}"));
+    }
+    if let Some(external_id) = external_id {
+        fields.push(format!("\"ExternalId\": {}", py_json_string(external_id)));
+    }
+    fields.push(format!("\"RoleArn\": {}", py_json_string(role_arn)));
+    if let Some(serial_number) = serial_number {
+        fields.push(format!(
+            "\"SerialNumber\": {}",
+            py_json_string(serial_number)
+        ));
+    }
+
+    let json = format!("{{{}}}", fields.join(", "));
+    let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, json.as_bytes());
+    hex::encode(digest)
+}
+
+/// Serializes `s` as a JSON string in the same way as Python's `json.dumps`, which escapes the
+/// characters other than printable ASCII.
+fn py_json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            '\u{8}' => json.push_str("\\b"),
+            '\u{c}' => json.push_str("\\f"),
+            ' '..='~' => json.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    json.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Where cached sessions are stored.
+#[derive(Clone, Copy, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Plain files in the cache directory.
+    #[default]
+    File,
+
+    /// The secret store of the operating system (macOS Keychain, Windows Credential Manager or
+    /// Secret Service).
+    Keyring,
+
+    /// Files in the cache directory encrypted with `age`.
+    Age,
+
+    /// Files in the cache directory encrypted with GnuPG.
+    Gpg,
+}
+
+/// Cache of assumed role sessions.
+pub struct Cache {
+    backend: Backend,
+    dir: PathBuf,
+    config: CacheConfig,
+}
+
+impl Cache {
+    pub fn new(backend: Backend, dir: PathBuf, config: &CacheConfig) -> Self {
+        Self {
+            backend,
+            dir,
+            config: config.clone(),
+        }
+    }
+
+    /// Returns the default directory of the file backend.
+    pub fn default_dir() -> Result<PathBuf> {
+        Ok(dirs::cache_dir()?.join("sessions"))
+    }
+
+    /// Returns the directory of the file backend on a memory-backed file system.
+    pub fn memory_dir() -> Result<PathBuf> {
+        Ok(dirs::runtime_dir()?.join("sessions"))
+    }
+
+    /// Returns the cache shared with the AWS CLI.
+    pub fn aws_cli() -> Result<Self> {
+        Ok(Self {
+            backend: Backend::File,
+            dir: dirs::home_dir()?.join(".aws").join("cli").join("cache"),
+            config: CacheConfig::default(),
+        })
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.backend {
+            Backend::File | Backend::Keyring => ".json",
+            Backend::Age => ".json.age",
+            Backend::Gpg => ".json.gpg",
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}{}", self.extension()))
+    }
+
+    /// Returns the file listing the keys stored in the keyring, which cannot be enumerated.
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("keyring.index")
+    }
+
+    async fn index(&self) -> Result<Vec<String>> {
+        let path = self.index_path();
+        match fs::read_to_string(&path).await {
+            Ok(content) => Ok(content.lines().map(str::to_string).collect()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e).with_context(|| format!("failed to read `{}`", path.display())),
+        }
+    }
+
+    /// Adds `key` to the index of the keyring, or removes it if `present` is `false`.
+    async fn update_index(&self, key: &str, present: bool) -> Result<()> {
+        let mut keys = self.index().await?;
+        if keys.iter().any(|k| k == key) == present {
+            return Ok(());
+        }
+        if present {
+            keys.push(key.to_string());
+        } else {
+            keys.retain(|k| k != key);
+        }
+        let content = keys.iter().map(|k| format!("{k}\n")).collect::<String>();
+        file::write_private(&self.index_path(), content.as_bytes()).await
+    }
+
+    async fn read(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        if let Backend::Keyring = self.backend {
+            return keyring::get(key).await;
+        }
+
+        let path = self.path(key);
+        file::check_private(&path).await;
+        let content = match fs::read(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+
+        let content = match self.backend {
+            Backend::Age => {
+                crypt::age_decrypt(self.config.age_identity.as_deref(), &content).await?
+            }
+            Backend::Gpg => crypt::gpg_decrypt(&content).await?,
+            Backend::File | Backend::Keyring => content,
+        };
+        Ok(Some(content))
+    }
+
+    async fn write(&self, key: &str, content: &[u8]) -> Result<()> {
+        let content = match self.backend {
+            Backend::File => content.to_vec(),
+            Backend::Keyring => {
+                keyring::set(key, content).await?;
+                return self.update_index(key, true).await;
+            }
+            Backend::Age => crypt::age_encrypt(&self.config.age_recipients, content).await?,
+            Backend::Gpg => {
+                crypt::gpg_encrypt(self.config.gpg_recipient.as_deref(), content).await?
+            }
+        };
+        file::write_private(&self.path(key), &content).await
+    }
+
+    /// Deletes the entry of `key`.
+    async fn remove(&self, key: &str) -> Result<()> {
+        if let Backend::Keyring = self.backend {
+            keyring::delete(key).await?;
+            return self.update_index(key, false).await;
+        }
+
+        let path = self.path(key);
+        fs::remove_file(&path)
+            .await
+            .with_context(|| format!("failed to delete `{}`", path.display()))
+    }
+
+    /// Returns all entries of the cache with their keys.
+    async fn entries(&self) -> Result<Vec<(String, Entry)>> {
+        let mut entries = Vec::new();
+        if let Backend::Keyring = self.backend {
+            for key in self.index().await? {
+                let Some(content) = self.read(&key).await? else {
+                    continue;
+                };
+                match serde_json::from_slice(&content) {
+                    Ok(entry) => entries.push((key, entry)),
+                    Err(e) => tracing::debug!("ignoring `{key}` in the keyring: {e}"),
+                }
+            }
+            return Ok(entries);
+        }
+
+        let mut dir = match fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(entries),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read `{}`", self.dir.display()))
+            }
+        };
+        while let Some(item) = dir.next_entry().await? {
+            let path = item.path();
+            let Some(key) = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.strip_suffix(self.extension()))
+            else {
+                continue;
+            };
+            let Some(content) = self.read(key).await? else {
+                continue;
+            };
+
+            match serde_json::from_slice(&content) {
+                Ok(entry) => entries.push((key.to_string(), entry)),
+                Err(e) => tracing::debug!("ignoring `{}`: {e}", path.display()),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Acquires the exclusive lock of `key`, waiting for other processes to release it.
+    ///
+    /// The lock is held until the returned guard is dropped so that concurrent invocations do
+    /// not assume the same role simultaneously.
+    pub async fn lock(&self, key: &str) -> Result<file::Lock> {
+        let path = dirs::cache_dir()?.join("locks").join(format!("{key}.lock"));
+        file::Lock::acquire(&path).await
+    }
+
+    /// Returns the cached session for `key` if it is valid for at least `min_ttl`.
+    pub async fn load(&self, key: &str, min_ttl: Duration) -> Result<Option<Session>> {
+        let Some(content) = self.read(key).await? else {
+            return Ok(None);
+        };
+
+        let entry: Entry = match serde_json::from_slice(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("ignoring malformed cache `{key}`: {e}");
+                return Ok(None);
+            }
+        };
+
+        let session = entry.into_session()?;
+        let ttl = session.credentials.expiration().secs() - Utc::now().timestamp();
+        if ttl < min_ttl.as_secs() as i64 {
+            tracing::debug!(ttl, "cached credentials are expiring");
+            return Ok(None);
+        }
+
+        tracing::debug!(ttl, "using cached credentials");
+        Ok(Some(session))
+    }
+
+    /// Stores `session` of `role_arn` as the cache for `key`.
+    pub async fn store(&self, key: &str, role_arn: Option<&str>, session: &Session) -> Result<()> {
+        let entry = Entry::from_session(role_arn, session)?;
+        let json = serde_json::to_vec_pretty(&entry)?;
+        self.write(key, &json).await
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Entry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role_arn: Option<String>,
+    credentials: EntryCredentials,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    assumed_role_user: Option<EntryUser>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    packed_policy_size: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_identity: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EntryCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    expiration: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EntryUser {
+    assumed_role_id: String,
+    arn: String,
+}
+
+impl Entry {
+    /// Returns the ARN of the role, deriving it from the assumed role user if not recorded.
+    fn role_arn(&self) -> Option<String> {
+        self.role_arn.clone().or_else(|| {
+            self.assumed_role_user
+                .as_ref()
+                .and_then(|u| role_arn_of(&u.arn))
+        })
+    }
+
+    /// Returns the expiration of the credentials in seconds since the Unix epoch.
+    fn expiration(&self) -> Result<i64> {
+        // The AWS CLI writes the expiration with a UTC offset rather than `Z`.
+        let expiration = chrono::DateTime::parse_from_rfc3339(&self.credentials.expiration)?;
+        Ok(expiration.timestamp())
+    }
+
+    fn from_session(role_arn: Option<&str>, session: &Session) -> Result<Self> {
+        let credentials = &session.credentials;
+        Ok(Self {
+            role_arn: role_arn.map(str::to_string),
+            credentials: EntryCredentials {
+                access_key_id: credentials.access_key_id().to_string(),
+                secret_access_key: credentials.secret_access_key().to_string(),
+                session_token: credentials.session_token().to_string(),
+                expiration: credentials.expiration().fmt(Format::DateTime)?,
+            },
+            assumed_role_user: session.assumed_role_user.as_ref().map(|u| EntryUser {
+                assumed_role_id: u.assumed_role_id().to_string(),
+                arn: u.arn().to_string(),
+            }),
+            packed_policy_size: session.packed_policy_size,
+            source_identity: session.source_identity.clone(),
+        })
+    }
+
+    fn into_session(self) -> Result<Session> {
+        let expiration = DateTime::from_secs(self.expiration()?);
+        let credentials = Credentials::builder()
+            .access_key_id(self.credentials.access_key_id)
+            .secret_access_key(self.credentials.secret_access_key)
+            .session_token(self.credentials.session_token)
+            .expiration(expiration)
+            .build()?;
+
+        let assumed_role_user = self
+            .assumed_role_user
+            .map(|u| {
+                AssumedRoleUser::builder()
+                    .assumed_role_id(u.assumed_role_id)
+                    .arn(u.arn)
+                    .build()
+            })
+            .transpose()?;
+
+        Ok(Session {
+            credentials,
+            assumed_role_user,
+            packed_policy_size: self.packed_policy_size,
+            source_identity: self.source_identity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The expected keys are computed by
+    // `hashlib.sha1(json.dumps(args, sort_keys=True).encode()).hexdigest()` as botocore does.
+
+    #[test]
+    fn aws_cli_key_role_only() {
+        assert_eq!(
+            aws_cli_key("arn:aws:iam::111122223333:role/Admin", None, None, None),
+            "20d1c4c47209b5c6f6727fa999b7ec9c34fe8643"
+        );
+    }
+
+    #[test]
+    fn aws_cli_key_all_parameters() {
+        assert_eq!(
+            aws_cli_key(
+                "arn:aws:iam::111122223333:role/Admin",
+                Some("prod"),
+                Some("arn:aws:iam::111122223333:mfa/me"),
+                Some(3600)
+            ),
+            "6124cca92108d3e449cb3086a1188a1bd4c361b0"
+        );
+    }
+
+    #[test]
+    fn aws_cli_key_escapes() {
+        assert_eq!(
+            aws_cli_key(
+                "arn:aws:iam::111122223333:role/Admin",
+                Some("café \"q\" \\ \u{1f600}"),
+                None,
+                None
+            ),
+            "1a3562481cd7e035cff7fa6f88ad7778287b0110"
+        );
+        assert_eq!(
+            py_json_string("\u{7f}\u{1}\n"),
+            r#""\u007f\u0001\n""#.to_string()
+        );
+    }
+
+    #[test]
+    fn role_arn_of_assumed_role() {
+        assert_eq!(
+            role_arn_of("arn:aws:sts::111122223333:assumed-role/Admin/me@example.com"),
+            Some("arn:aws:iam::111122223333:role/Admin".to_string())
+        );
+        assert_eq!(role_arn_of("arn:aws:iam::111122223333:user/me"), None);
+    }
+}
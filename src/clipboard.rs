@@ -0,0 +1,155 @@
+use crate::duration;
+use anyhow::{anyhow, bail, Context as _, Result};
+use std::io::ErrorKind;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::Command;
+
+/// The options to copy the output to the clipboard instead of printing it.
+#[derive(clap::Args)]
+pub struct ClipboardArgs {
+    /// Copy the output to the clipboard instead of printing it, which keeps it out of the history
+    /// of the terminal.
+    #[arg(long, env = "ASSUME_ROLE_CLIPBOARD")]
+    clipboard: bool,
+
+    /// Clear the clipboard after this duration, such as `30s`, unless something else has been
+    /// copied. The command waits until then.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = duration::parse,
+        requires = "clipboard",
+        env = "ASSUME_ROLE_CLIPBOARD_CLEAR"
+    )]
+    clipboard_clear: Option<Duration>,
+}
+
+/// A command-line tool that accesses the clipboard.
+struct Tool {
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+}
+
+impl ClipboardArgs {
+    /// Copies `text` to the clipboard if requested, otherwise prints it.
+    pub async fn output(&self, text: &str) -> Result<()> {
+        if !self.clipboard {
+            println!("{text}");
+            return Ok(());
+        }
+
+        let tool = copy(text).await?;
+        eprintln!("Copied to the clipboard");
+
+        if let Some(after) = self.clipboard_clear {
+            eprintln!("Clearing the clipboard in {} seconds", after.as_secs());
+            tokio::time::sleep(after).await;
+            match paste(tool).await {
+                Ok(content) if content.trim_end() == text.trim_end() => {
+                    run(tool.copy, "").await?;
+                    eprintln!("Cleared the clipboard");
+                }
+                Ok(_) => eprintln!("The clipboard has been overwritten"),
+                Err(e) => tracing::warn!("failed to read the clipboard: {e:#}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+static PBCOPY: Tool = Tool {
+    copy: &["pbcopy"],
+    paste: &["pbpaste"],
+};
+
+static CLIP: Tool = Tool {
+    copy: &["clip"],
+    paste: &["powershell", "-NoProfile", "-Command", "Get-Clipboard"],
+};
+
+static WL_CLIPBOARD: Tool = Tool {
+    copy: &["wl-copy"],
+    paste: &["wl-paste", "--no-newline"],
+};
+
+static XCLIP: Tool = Tool {
+    copy: &["xclip", "-selection", "clipboard"],
+    paste: &["xclip", "-selection", "clipboard", "-out"],
+};
+
+static XSEL: Tool = Tool {
+    copy: &["xsel", "--clipboard", "--input"],
+    paste: &["xsel", "--clipboard", "--output"],
+};
+
+/// Returns the tools that may access the clipboard on this platform, preferred first.
+fn tools() -> Vec<&'static Tool> {
+    if cfg!(target_os = "macos") {
+        vec![&PBCOPY]
+    } else if cfg!(windows) {
+        vec![&CLIP]
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        vec![&WL_CLIPBOARD, &XCLIP, &XSEL]
+    } else {
+        vec![&XCLIP, &XSEL]
+    }
+}
+
+/// Copies `text` with the first tool available, and returns the tool.
+async fn copy(text: &str) -> Result<&'static Tool> {
+    for tool in tools() {
+        match run(tool.copy, text).await {
+            Ok(()) => return Ok(tool),
+            Err(e) if is_not_found(&e) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    bail!("no tool to access the clipboard is found")
+}
+
+async fn paste(tool: &Tool) -> Result<String> {
+    let output = Command::new(tool.paste[0])
+        .args(&tool.paste[1..])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("failed to run `{}`", tool.paste[0]))?;
+    if !output.status.success() {
+        bail!("`{}` failed with {}", tool.paste[0], output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs `command` with `input` on its standard input.
+async fn run(command: &[&str], input: &str) -> Result<()> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", command[0]))?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("no standard input of `{}`", command[0]))?;
+    stdin.write_all(input.as_bytes()).await?;
+    drop(stdin);
+
+    let status = child.wait().await?;
+    if !status.success() {
+        bail!("`{}` failed with {status}", command[0]);
+    }
+    Ok(())
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == ErrorKind::NotFound)
+}
@@ -0,0 +1,144 @@
+use crate::{exec, http};
+use anyhow::{anyhow, Context as _, Result};
+use aws_sdk_sts::types::Credentials;
+use aws_smithy_types::DateTime;
+use hyper::{header, Body, Request};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The ID of the identity pool, such as `us-east-1:00000000-0000-0000-0000-000000000000`.
+    #[arg(long, value_name = "ID", env = "ASSUME_ROLE_IDENTITY_POOL_ID")]
+    identity_pool_id: String,
+
+    /// The ID of the identity. A new or the existing identity of the logins is used if not specified.
+    #[arg(long, value_name = "ID", env = "ASSUME_ROLE_IDENTITY_ID")]
+    identity_id: Option<String>,
+
+    /// A token of an identity provider, such as `accounts.google.com=TOKEN`. Use
+    /// `cognito-identity.amazonaws.com=TOKEN` for developer authenticated identities.
+    #[arg(long, value_name = "PROVIDER=TOKEN", env = "ASSUME_ROLE_LOGIN")]
+    login: Vec<String>,
+
+    /// The ARN of the role to be assumed when multiple roles are received in the token.
+    #[arg(long, value_name = "ARN", env = "ASSUME_ROLE_CUSTOM_ROLE_ARN")]
+    custom_role_arn: Option<String>,
+
+    #[command(flatten)]
+    exec: exec::RunOptions,
+
+    /// A command and its arguments to run with the credentials. Runs current shell if not specified.
+    command: Vec<String>,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    let args = &args;
+    exec::execute(move || get_credentials(args), &args.command, &args.exec).await
+}
+
+async fn get_credentials(args: &Args) -> Result<Credentials> {
+    let (region, _) = args
+        .identity_pool_id
+        .split_once(':')
+        .ok_or_else(|| anyhow!("illegal identity pool ID: `{}`", args.identity_pool_id))?;
+
+    let mut logins = HashMap::with_capacity(args.login.len());
+    for login in &args.login {
+        let (provider, token) = login
+            .split_once('=')
+            .ok_or_else(|| anyhow!("illegal login: `{login}`"))?;
+        logins.insert(provider, token);
+    }
+
+    let identity_id = match &args.identity_id {
+        Some(identity_id) => identity_id.clone(),
+        None => {
+            #[derive(Serialize)]
+            #[serde(rename_all = "PascalCase")]
+            struct Input<'a> {
+                identity_pool_id: &'a str,
+                logins: &'a HashMap<&'a str, &'a str>,
+            }
+
+            #[derive(Deserialize)]
+            #[serde(rename_all = "PascalCase")]
+            struct Output {
+                identity_id: String,
+            }
+
+            let output: Output = call(
+                region,
+                "GetId",
+                &Input {
+                    identity_pool_id: &args.identity_pool_id,
+                    logins: &logins,
+                },
+            )
+            .await?;
+            output.identity_id
+        }
+    };
+    tracing::debug!(identity_id, "using identity");
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Input<'a> {
+        identity_id: &'a str,
+        logins: &'a HashMap<&'a str, &'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        custom_role_arn: Option<&'a str>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Output {
+        credentials: OutputCredentials,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct OutputCredentials {
+        access_key_id: String,
+        secret_key: String,
+        session_token: String,
+        expiration: f64,
+    }
+
+    let output: Output = call(
+        region,
+        "GetCredentialsForIdentity",
+        &Input {
+            identity_id: &identity_id,
+            logins: &logins,
+            custom_role_arn: args.custom_role_arn.as_deref(),
+        },
+    )
+    .await?;
+
+    Ok(Credentials::builder()
+        .access_key_id(output.credentials.access_key_id)
+        .secret_access_key(output.credentials.secret_key)
+        .session_token(output.credentials.session_token)
+        .expiration(DateTime::from_secs_f64(output.credentials.expiration))
+        .build()?)
+}
+
+/// Calls `operation` of Amazon Cognito Identity, which requires no signature.
+async fn call<T: DeserializeOwned>(
+    region: &str,
+    operation: &str,
+    input: &impl Serialize,
+) -> Result<T> {
+    let request = Request::post(format!("https://cognito-identity.{region}.amazonaws.com/"))
+        .header(header::CONTENT_TYPE, "application/x-amz-json-1.1")
+        .header(
+            "X-Amz-Target",
+            format!("AWSCognitoIdentityService.{operation}"),
+        )
+        .body(Body::from(serde_json::to_vec(input)?))?;
+
+    let body = http::send(request).await?;
+    serde_json::from_slice(&body).with_context(|| format!("malformed {operation} response"))
+}
@@ -0,0 +1,1074 @@
+use crate::cache;
+use crate::mfa::TokenSource;
+use crate::{assume, dirs, duration, file, http, oidc, okta, prompt, source, toml, validate};
+use anyhow::{anyhow, bail, Context as _, Result};
+use aws_config::SdkConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The configuration file of this application.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub cache: CacheConfig,
+    pub entra: EntraConfig,
+    pub mfa: MfaConfig,
+    pub oidc: OidcConfig,
+    pub okta: OktaConfig,
+    pub prompt: PromptConfig,
+    pub approval: ApprovalConfig,
+
+    /// The role, preset or chain to assume when `--role` is not specified.
+    pub role: Option<String>,
+
+    /// Named sets of parameters to assume a role with, used as `--role NAME` or `assume-role NAME`.
+    pub presets: BTreeMap<String, Preset>,
+
+    /// Named defaults shared by the presets that refer to them with `group`.
+    pub groups: BTreeMap<String, Preset>,
+
+    /// Named chains of roles, assumed in order by `--role NAME`.
+    pub chains: BTreeMap<String, Vec<ChainHop>>,
+
+    /// Aliases of account IDs, used as `--role ALIAS/ROLE` or `--account-id ALIAS`.
+    pub accounts: BTreeMap<String, String>,
+
+    /// Named IAM policies, used as inline session policies by `--policy-preset NAME`.
+    pub policies: BTreeMap<String, Value>,
+
+    /// Remote config files whose presets, groups, chains, accounts and policies are shared by a
+    /// team.
+    pub include: Vec<Include>,
+}
+
+/// A remote config file, fetched with the source credentials.
+#[derive(Clone, Deserialize)]
+pub struct Include {
+    /// The HTTPS URL or the `s3://BUCKET/KEY` URL of the file.
+    pub url: String,
+
+    /// How long the fetched file is reused, such as `1h`. Defaults to an hour.
+    #[serde(default)]
+    pub ttl: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// The default backend of the cache.
+    pub backend: Option<cache::Backend>,
+
+    /// The recipients to encrypt the cache with `age`.
+    pub age_recipients: Vec<String>,
+
+    /// The identity file to decrypt the cache with `age`.
+    pub age_identity: Option<PathBuf>,
+
+    /// The key to encrypt the cache with GnuPG.
+    pub gpg_recipient: Option<String>,
+}
+
+/// A role in a chain and the parameters to assume it with.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChainHop {
+    /// The name or the ARN of the role. The last role of a chain must be an ARN.
+    pub role: String,
+
+    /// The external ID required by the role.
+    pub external_id: Option<String>,
+
+    /// The duration, in seconds, of the role session.
+    pub duration_seconds: Option<i32>,
+
+    /// The session tags to pass.
+    pub tags: BTreeMap<String, String>,
+}
+
+/// The parameters to assume a role with, overridden by the command line.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Preset {
+    /// The name or the ARN of the role, or the name of a chain.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub role: String,
+
+    /// The group whose parameters are used unless specified by this preset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// An identifier for the assumed role session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role_session_name: Option<String>,
+
+    /// The duration, in seconds, of the role session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<i32>,
+
+    /// The external ID required by the role.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+
+    /// The identification number of the MFA device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+
+    /// The session tags to pass.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+
+    /// The keys of the session tags to set as transitive.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transitive_tag_keys: Vec<String>,
+
+    /// The ARNs of the IAM managed policies to use as managed session policies, or their names.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub policy_arns: Vec<String>,
+
+    /// An IAM policy in JSON or YAML to use as an inline session policy, or the path to it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+
+    /// The source identity to set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_identity: Option<String>,
+
+    /// The duration, in seconds, of the sessions of the console signed in by `console`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub console_duration_seconds: Option<i32>,
+
+    /// The issuer of the sign-in URLs of `console`, recorded in the sign-in events of CloudTrail.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub console_issuer: Option<String>,
+}
+
+impl Preset {
+    /// Fills the parameters not specified by this preset with those of `defaults`.
+    fn inherit(&mut self, defaults: &Preset) {
+        if self.role.is_empty() {
+            self.role = defaults.role.clone();
+        }
+        self.role_session_name = self
+            .role_session_name
+            .take()
+            .or_else(|| defaults.role_session_name.clone());
+        self.duration_seconds = self.duration_seconds.or(defaults.duration_seconds);
+        self.external_id = self
+            .external_id
+            .take()
+            .or_else(|| defaults.external_id.clone());
+        self.serial_number = self
+            .serial_number
+            .take()
+            .or_else(|| defaults.serial_number.clone());
+        for (key, value) in &defaults.tags {
+            self.tags
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        if self.transitive_tag_keys.is_empty() {
+            self.transitive_tag_keys = defaults.transitive_tag_keys.clone();
+        }
+        if self.policy_arns.is_empty() {
+            self.policy_arns = defaults.policy_arns.clone();
+        }
+        self.policy = self.policy.take().or_else(|| defaults.policy.clone());
+        self.source_identity = self
+            .source_identity
+            .take()
+            .or_else(|| defaults.source_identity.clone());
+        self.console_duration_seconds = self
+            .console_duration_seconds
+            .or(defaults.console_duration_seconds);
+        self.console_issuer = self
+            .console_issuer
+            .take()
+            .or_else(|| defaults.console_issuer.clone());
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EntraConfig {
+    /// The ID of the Microsoft Entra tenant.
+    pub tenant_id: Option<String>,
+
+    /// The identifier (entity ID) of the AWS enterprise application.
+    pub app_id_uri: Option<String>,
+
+    /// The user name to sign in to Microsoft Entra ID with.
+    pub username: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MfaConfig {
+    /// The identification number of the MFA device used unless specified on the command line.
+    pub serial_number: Option<String>,
+
+    /// A shell command whose output is used as the MFA code.
+    pub token_command: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OidcConfig {
+    /// Where to obtain the OIDC token from if `--oidc` is specified without a source.
+    pub token_source: Option<oidc::TokenSource>,
+
+    /// The audience of the OIDC token requested from the CI system.
+    pub audience: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OktaConfig {
+    /// The embed link of the AWS application in Okta.
+    pub app_url: Option<String>,
+
+    /// The user name to sign in to Okta with.
+    pub username: Option<String>,
+
+    /// The MFA factor to verify.
+    pub factor: Option<okta::Factor>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// How to interact with the user.
+    pub backend: prompt::Backend,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ApprovalConfig {
+    /// Patterns of role ARNs that require local approval, such as Touch ID, before assuming them.
+    pub roles: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Add or replace a preset.
+    Add(AddArgs),
+
+    /// Import the roles defined for another tool as presets.
+    Import(ImportArgs),
+
+    /// List the presets.
+    List,
+
+    /// Remove a preset.
+    Remove {
+        /// The name of the preset.
+        name: String,
+    },
+
+    /// Show the parameters of a preset.
+    Show {
+        /// The name of the preset.
+        name: String,
+    },
+}
+
+#[derive(clap::Args)]
+struct AddArgs {
+    /// The name of the preset.
+    name: String,
+
+    /// The name or the ARN of the role, or the name of a chain.
+    #[arg(short, long, value_name = "NAME")]
+    role: String,
+
+    /// The group whose parameters are used unless specified.
+    #[arg(short, long, value_name = "NAME")]
+    group: Option<String>,
+
+    /// An identifier for the assumed role session.
+    #[arg(long, value_name = "NAME")]
+    role_session_name: Option<String>,
+
+    /// The duration, in seconds, of the role session.
+    #[arg(long, value_name = "NUMBER", value_parser = clap::value_parser!(i32).range(900..=43200))]
+    duration_seconds: Option<i32>,
+
+    /// The external ID required by the role.
+    #[arg(long)]
+    external_id: Option<String>,
+
+    /// The identification number of the MFA device.
+    #[arg(long, visible_alias = "mfa")]
+    serial_number: Option<String>,
+
+    /// A session tag to pass.
+    #[arg(long, value_name = "KEY=VALUE")]
+    tag: Vec<String>,
+
+    /// The key of a session tag to set as transitive.
+    #[arg(long, value_name = "KEY")]
+    transitive_tag_key: Vec<String>,
+
+    /// The ARN of an IAM managed policy to use as a managed session policy, or the name of an AWS
+    /// managed policy or `customer:NAME`.
+    #[arg(long, value_name = "ARN")]
+    policy_arn: Vec<String>,
+
+    /// An IAM policy in JSON or YAML to use as an inline session policy, or the path to it.
+    #[arg(short, long, value_name = "POLICY")]
+    policy: Option<String>,
+
+    /// The source identity to set.
+    #[arg(long)]
+    source_identity: Option<String>,
+
+    /// The duration, in seconds, of the sessions of the console signed in by `console`.
+    #[arg(long, value_name = "NUMBER", value_parser = clap::value_parser!(i32).range(900..=43200))]
+    console_duration_seconds: Option<i32>,
+
+    /// The issuer of the sign-in URLs of `console`.
+    #[arg(long, value_name = "NAME")]
+    console_issuer: Option<String>,
+
+    /// Replace the preset if it exists.
+    #[arg(short, long)]
+    force: bool,
+}
+
+#[derive(clap::Args)]
+struct ImportArgs {
+    /// The tool to import the roles from.
+    #[arg(long, value_enum)]
+    from: Tool,
+
+    /// Replace the presets of the same names.
+    #[arg(short, long)]
+    force: bool,
+}
+
+/// Other tools that assume roles.
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum Tool {
+    /// Profiles in the shared config files used by aws-vault.
+    AwsVault,
+
+    /// Profiles in the shared config files used by awsume.
+    Awsume,
+
+    /// Profiles in the shared config files used by Granted.
+    Granted,
+}
+
+pub async fn run(args: Args) -> Result<()> {
+    // The presets are edited in the parsed document, and then only their tables are rewritten in
+    // the original text.
+    let path = ConfigFile::path()?;
+    let content = match tokio::fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read `{}`", path.display())),
+    };
+    let original =
+        toml::parse(&content).map_err(|e| anyhow!("failed to parse `{}`: {e}", path.display()))?;
+    let mut document = original.table.clone();
+    let presets = match document
+        .entry("presets")
+        .or_insert_with(|| Value::Object(Map::new()))
+    {
+        Value::Object(presets) => presets,
+        _ => bail!("`presets` of `{}` is not a table", path.display()),
+    };
+
+    let names = match args.command {
+        Command::Add(args) => {
+            if presets.contains_key(args.name.as_str()) && !args.force {
+                bail!(
+                    "preset `{}` already exists; specify `--force` to replace it",
+                    args.name
+                );
+            }
+            if args.role.starts_with("arn:") && !validate::is_arn_of(&args.role, "iam", "role/") {
+                bail!("`{}` is not an ARN of a role", args.role);
+            }
+            for arn in &args.policy_arn {
+                if arn.starts_with("arn:") && !validate::is_arn_of(arn, "iam", "policy/") {
+                    bail!("`{arn}` is not an ARN of a policy");
+                }
+            }
+
+            let preset = Preset {
+                role: args.role,
+                group: args.group,
+                role_session_name: args.role_session_name,
+                duration_seconds: args.duration_seconds,
+                external_id: args.external_id,
+                serial_number: args.serial_number,
+                tags: assume::parse_tags(&args.tag)?.into_iter().collect(),
+                transitive_tag_keys: args.transitive_tag_key,
+                policy_arns: args.policy_arn,
+                policy: args.policy,
+                source_identity: args.source_identity,
+                console_duration_seconds: args.console_duration_seconds,
+                console_issuer: args.console_issuer,
+            };
+            presets.insert(args.name.clone(), serde_json::to_value(preset)?);
+            vec![args.name]
+        }
+        Command::Import(args) => import(presets, args).await?,
+        Command::List => {
+            for (name, preset) in presets.iter() {
+                let role = preset["role"].as_str().unwrap_or_default();
+                println!("{name}\t{role}");
+            }
+            return Ok(());
+        }
+        Command::Remove { name } => {
+            if presets.remove(&name).is_none() {
+                bail!("preset `{name}` is not found");
+            }
+            vec![name]
+        }
+        Command::Show { name } => {
+            let preset = presets
+                .get(&name)
+                .and_then(Value::as_object)
+                .ok_or_else(|| anyhow!("preset `{name}` is not found"))?;
+            print!("{}", toml::to_string(preset));
+            return Ok(());
+        }
+    };
+
+    let content = rewrite(&content, &original, &document, &names)
+        .with_context(|| format!("failed to edit `{}`", path.display()))?;
+    // Refuse to write a file that cannot be loaded.
+    ConfigFile::parse(&path.display().to_string(), content.as_bytes())?;
+    file::write_private(&path, content.as_bytes()).await
+}
+
+/// Imports the roles defined for the tool into `presets`, and returns the names of the imported
+/// ones.
+async fn import(presets: &mut Map<String, Value>, args: ImportArgs) -> Result<Vec<String>> {
+    // All of the tools define roles as profiles of the shared config files.
+    let profiles = source::load_profiles().await?;
+    let mut names = Vec::new();
+    for name in profiles.profiles() {
+        let Some(profile) = profiles.get_profile(name) else {
+            continue;
+        };
+        let Some(mut preset) = source::preset_of(profile)? else {
+            if args.from == Tool::Granted && profile.get("granted_sso_start_url").is_some() {
+                eprintln!("skipped `{name}`: IAM Identity Center profiles are used with `--sso-start-url`");
+            }
+            continue;
+        };
+
+        // aws-vault and awsume use the MFA device of the source profile if the role has none.
+        if matches!(args.from, Tool::AwsVault | Tool::Awsume) {
+            preset.serial_number = preset.serial_number.or_else(|| {
+                profile
+                    .get("source_profile")
+                    .and_then(|source| profiles.get_profile(source))
+                    .and_then(|source| source.get("mfa_serial"))
+                    .map(str::to_string)
+            });
+        }
+
+        if presets.contains_key(name) && !args.force {
+            eprintln!("skipped `{name}`: the preset already exists");
+            continue;
+        }
+        presets.insert(name.to_string(), serde_json::to_value(preset)?);
+        names.push(name.to_string());
+        println!("imported `{name}`");
+    }
+
+    Ok(names)
+}
+
+/// Returns `content`, parsed as `original`, with the presets `names` changed to those in
+/// `document`.
+///
+/// Only the tables of the presets are replaced, added to the end or removed, so that the rest of
+/// the text, including the comments, is kept as is. The whole document is serialized instead if
+/// some of the presets are defined otherwise, such as by inline tables, unless it has comments.
+fn rewrite(
+    content: &str,
+    original: &toml::Document,
+    document: &Map<String, Value>,
+    names: &[String],
+) -> Result<String> {
+    let edited = edit_presets(content, original, document, names).filter(|edited| {
+        // The document lacks `presets` if the last one is removed.
+        let mut table = toml::parse(edited).map(|edited| edited.table);
+        if let Ok(table) = &mut table {
+            table
+                .entry("presets")
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        table.as_ref() == Ok(document)
+    });
+    match edited {
+        Some(edited) => Ok(edited),
+        None if !original.comments => Ok(toml::to_string(document)),
+        None => bail!(
+            "the presets are not defined by tables of their own, such as `[presets.NAME]`, and \
+             rewriting the whole file would drop its comments; edit the file by hand"
+        ),
+    }
+}
+
+/// Returns `content` with the tables of the presets `names` replaced with those in `document`,
+/// added to the end or removed, or `None` if some of them are not defined by their own tables.
+fn edit_presets(
+    content: &str,
+    original: &toml::Document,
+    document: &Map<String, Value>,
+    names: &[String],
+) -> Option<String> {
+    let presets = document.get("presets")?.as_object()?;
+    let mut lines = content
+        .split_inclusive('\n')
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let is_comment = |line: &str| line.trim_start().starts_with('#');
+    let is_blank = |line: &str| line.trim().is_empty();
+
+    let mut edits = Vec::new();
+    let mut added = Vec::new();
+    for name in names {
+        let table = presets.get(name).map(|preset| {
+            let mut table = Map::new();
+            table.insert(
+                "presets".to_string(),
+                Value::Object(Map::from_iter([(name.clone(), preset.clone())])),
+            );
+            toml::to_string(&table)
+        });
+
+        let header = format!("presets.{name}");
+        let Some(index) = original
+            .headers
+            .iter()
+            .position(|(path, _)| *path == header)
+        else {
+            let defined = original
+                .table
+                .get("presets")
+                .and_then(|presets| presets.get(name));
+            if defined.is_some() {
+                return None;
+            }
+            added.extend(table);
+            continue;
+        };
+
+        // The table lasts until the next header other than those of its sub-tables, without the
+        // blank lines and the comments above it.
+        let mut start = original.headers[index].1;
+        let mut end = original.headers[index + 1..]
+            .iter()
+            .find(|(path, _)| !path.starts_with(&format!("{header}.")))
+            .map_or(lines.len(), |(_, line)| *line);
+        while end > start + 1 && (is_blank(&lines[end - 1]) || is_comment(&lines[end - 1])) {
+            end -= 1;
+        }
+
+        if table.is_none() {
+            // The comments just above the header and the blank lines below the table go together.
+            while start > 0 && is_comment(&lines[start - 1]) {
+                start -= 1;
+            }
+            while end < lines.len() && is_blank(&lines[end]) {
+                end += 1;
+            }
+            if end == lines.len() {
+                while start > 0 && is_blank(&lines[start - 1]) {
+                    start -= 1;
+                }
+            }
+        }
+        edits.push((start..end, table.unwrap_or_default()));
+    }
+
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, table) in edits {
+        lines.splice(range, [table]);
+    }
+    let mut edited = lines.concat();
+    for table in added {
+        if !edited.is_empty() {
+            if !edited.ends_with('\n') {
+                edited.push('\n');
+            }
+            edited.push('\n');
+        }
+        edited.push_str(&table);
+    }
+    Some(edited)
+}
+
+/// Returns the content of the included file, reusing the local copy while it is fresh, or
+/// regardless of its age if `offline`.
+async fn fetch_include(config: &SdkConfig, include: &Include, offline: bool) -> Result<Vec<u8>> {
+    let ttl = duration::parse(include.ttl.as_deref().unwrap_or("1h")).map_err(|e| anyhow!(e))?;
+    let path = dirs::cache_dir()?.join("include").join(format!(
+        "{}.toml",
+        hex::encode(Sha256::digest(&include.url))
+    ));
+
+    let modified = tokio::fs::metadata(&path)
+        .await
+        .and_then(|m| m.modified())
+        .ok();
+    if let Some(modified) = modified {
+        if offline || modified.elapsed().is_ok_and(|elapsed| elapsed < ttl) {
+            return Ok(tokio::fs::read(&path).await?);
+        }
+    }
+    if offline {
+        bail!("no local copy (offline mode)");
+    }
+
+    let fetched = if let Some(location) = include.url.strip_prefix("s3://") {
+        let (bucket, key) = location
+            .split_once('/')
+            .ok_or_else(|| anyhow!("`{}` has no key", include.url))?;
+        http::s3_get(config, bucket, key).await
+    } else if include.url.starts_with("https://") {
+        http::send(hyper::Request::get(&include.url).body(hyper::Body::empty())?).await
+    } else {
+        bail!("`{}` is neither an HTTPS nor an S3 URL", include.url);
+    };
+
+    match fetched {
+        Ok(content) => {
+            file::write_private(&path, &content).await?;
+            Ok(content.to_vec())
+        }
+        // A stale copy is better than nothing when offline.
+        Err(e) if modified.is_some() => {
+            tracing::warn!("using the stale copy of `{}`: {e:#}", include.url);
+            Ok(tokio::fs::read(&path).await?)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+impl MfaConfig {
+    /// Returns where to obtain MFA codes from unless specified on the command line.
+    pub fn token_source(&self) -> TokenSource {
+        match &self.token_command {
+            Some(command) => TokenSource::Command(command.clone()),
+            None => TokenSource::Prompt,
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Returns the account ID that `name` is an alias of, or `name` itself.
+    pub fn account_id<'a>(&'a self, name: &'a str) -> &'a str {
+        self.accounts.get(name).map_or(name, String::as_str)
+    }
+
+    /// Returns the preset `name` with the parameters inherited from its groups.
+    pub fn preset(&self, name: &str) -> Result<Option<Preset>> {
+        let Some(mut preset) = self.presets.get(name).cloned() else {
+            return Ok(None);
+        };
+
+        let mut visited = Vec::new();
+        let mut next = preset.group.clone();
+        while let Some(group) = next {
+            if visited.contains(&group) {
+                bail!("the groups of preset `{name}` refer to each other");
+            }
+            let defaults = self
+                .groups
+                .get(&group)
+                .ok_or_else(|| anyhow!("group `{group}` of preset `{name}` is not found"))?;
+            preset.inherit(defaults);
+            next = defaults.group.clone();
+            visited.push(group);
+        }
+
+        if preset.role.is_empty() {
+            bail!("preset `{name}` has no `role`");
+        }
+        Ok(Some(preset))
+    }
+
+    /// Checks that the groups referred to by the presets and the groups exist and do not refer to
+    /// each other, which is known only once the included and the project configs are merged.
+    pub fn check_groups(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        for (kind, entries) in [("preset", &self.presets), ("group", &self.groups)] {
+            for (name, entry) in entries {
+                match &entry.group {
+                    Some(group) if !self.groups.contains_key(group) => {
+                        problems.push(format!("group `{group}` of {kind} `{name}` is not found"))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for name in self.groups.keys() {
+            let mut cycle = vec![name];
+            let mut next = self.groups[name].group.as_ref();
+            while let Some(group) = next {
+                if group == name {
+                    // A cycle is reported once, at the first of its groups by name.
+                    if cycle.iter().all(|g| name <= *g) {
+                        cycle.push(group);
+                        let cycle = cycle.iter().map(|g| format!("`{g}`")).collect::<Vec<_>>();
+                        problems.push(format!(
+                            "groups refer to each other: {}",
+                            cycle.join(" -> ")
+                        ));
+                    }
+                    break;
+                }
+                if cycle.contains(&group) {
+                    break;
+                }
+                cycle.push(group);
+                next = self.groups.get(group).and_then(|g| g.group.as_ref());
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!("the config is invalid:\n  {}", problems.join("\n  "));
+        }
+        Ok(())
+    }
+
+    /// Returns the path of the configuration file.
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = std::env::var_os("ASSUME_ROLE_CONFIG_FILE") {
+            return Ok(path.into());
+        }
+
+        Ok(dirs::config_dir()?.join("config.toml"))
+    }
+
+    /// Loads the configuration file, returning the default configuration if it does not exist.
+    pub async fn load() -> Result<Self> {
+        let mut config_file = Self::read(&Self::path()?).await?.unwrap_or_default();
+        if let Some(path) = Self::project_path() {
+            if let Some(project) = Self::read(&path).await? {
+                tracing::debug!("using the project config `{}`", path.display());
+                config_file.merge(project);
+            }
+        }
+
+        Ok(config_file)
+    }
+
+    /// Fetches the included config files and adds their presets, groups, chains, accounts and
+    /// policies unless defined locally.
+    ///
+    /// A file that cannot be fetched or parsed is skipped with a warning so that it does not
+    /// prevent using the local settings. Only the local copies are used if `offline`.
+    pub async fn fetch_includes(&mut self, config: &SdkConfig, offline: bool) {
+        for include in self.include.clone() {
+            let included = match fetch_include(config, &include, offline).await {
+                Ok(content) => Self::parse(&include.url, &content),
+                Err(e) => Err(e),
+            };
+            let included = match included {
+                Ok(included) => included,
+                Err(e) => {
+                    eprintln!("skipped the include `{}`: {e:#}", include.url);
+                    continue;
+                }
+            };
+
+            for (name, preset) in included.presets {
+                self.presets.entry(name).or_insert(preset);
+            }
+            for (name, group) in included.groups {
+                self.groups.entry(name).or_insert(group);
+            }
+            for (name, chain) in included.chains {
+                self.chains.entry(name).or_insert(chain);
+            }
+            for (name, account) in included.accounts {
+                self.accounts.entry(name).or_insert(account);
+            }
+            for (name, policy) in included.policies {
+                self.policies.entry(name).or_insert(policy);
+            }
+        }
+    }
+
+    /// Returns the path of the nearest `.assume-role.toml` in the current directory or its parents.
+    fn project_path() -> Option<PathBuf> {
+        let dir = std::env::current_dir().ok()?;
+        dir.ancestors()
+            .map(|dir| dir.join(".assume-role.toml"))
+            .find(|path| path.is_file())
+    }
+
+    async fn read(path: &Path) -> Result<Option<Self>> {
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+
+        Self::parse(&path.display().to_string(), &content).map(Some)
+    }
+
+    /// Parses the config file `name`, reporting all problems found at once.
+    fn parse(name: &str, content: &[u8]) -> Result<Self> {
+        let content =
+            std::str::from_utf8(content).with_context(|| format!("`{name}` is not UTF-8"))?;
+        let document =
+            toml::parse(content).map_err(|e| anyhow!("failed to parse `{name}`: {e}"))?;
+
+        let problems = validate::validate(&document);
+        if !problems.is_empty() {
+            bail!("`{name}` is invalid:\n  {}", problems.join("\n  "));
+        }
+
+        Self::deserialize(Value::Object(document.table))
+            .with_context(|| format!("failed to parse `{name}`"))
+    }
+
+    /// Applies the roles of a project config. Its `role` takes precedence over this file, but the
+    /// names already defined in this file are kept with a warning.
+    ///
+    /// Other settings of the project config are ignored so that a repository cannot change how
+    /// credentials are obtained or stored, such as by redefining a preset the user trusts.
+    fn merge(&mut self, project: Self) {
+        if project.role.is_some() {
+            self.role = project.role;
+        }
+        add_new("preset", &mut self.presets, project.presets);
+        add_new("group", &mut self.groups, project.groups);
+        add_new("chain", &mut self.chains, project.chains);
+        add_new("account", &mut self.accounts, project.accounts);
+        add_new("policy", &mut self.policies, project.policies);
+    }
+}
+
+/// Adds the entries of `project` to `entries` unless their names are already defined.
+fn add_new<T>(kind: &str, entries: &mut BTreeMap<String, T>, project: BTreeMap<String, T>) {
+    for (name, entry) in project {
+        if entries.contains_key(&name) {
+            eprintln!(
+                "skipped {kind} `{name}` of the project config: it is defined in the config file"
+            );
+            continue;
+        }
+        entries.insert(name, entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(content: &str) -> ConfigFile {
+        ConfigFile::parse("config.toml", content.as_bytes()).unwrap()
+    }
+
+    const COMMENTED: &str = r#"# Settings of assume-role.
+role = "prod" # the default
+
+# The production account.
+[presets.prod]
+role = "arn:aws:iam::123456789012:role/Admin"
+duration_seconds = 3600 # an hour
+
+[presets.prod.tags]
+Team = "infra"
+
+# Staging.
+[presets.stage]
+role = "Stage"
+
+# Groups come last.
+[groups.all]
+external_id = "x"
+"#;
+
+    /// Returns `content` rewritten after `edit` changes its presets.
+    fn edit(
+        content: &str,
+        edit: impl FnOnce(&mut Map<String, Value>) -> Vec<String>,
+    ) -> Result<String> {
+        let original = toml::parse(content).unwrap();
+        let mut document = original.table.clone();
+        let presets = document
+            .entry("presets")
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .unwrap();
+        let names = edit(presets);
+        rewrite(content, &original, &document, &names)
+    }
+
+    #[test]
+    fn replace_preset() {
+        let edited = edit(COMMENTED, |presets| {
+            presets.insert("prod".to_string(), json!({"role": "Admin"}));
+            vec!["prod".to_string()]
+        })
+        .unwrap();
+        assert_eq!(
+            edited,
+            COMMENTED.replace(
+                "[presets.prod]\nrole = \"arn:aws:iam::123456789012:role/Admin\"\n\
+                 duration_seconds = 3600 # an hour\n\n[presets.prod.tags]\nTeam = \"infra\"\n",
+                "[presets.prod]\nrole = \"Admin\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn remove_preset() {
+        let edited = edit(COMMENTED, |presets| {
+            presets.remove("stage");
+            vec!["stage".to_string()]
+        })
+        .unwrap();
+        assert_eq!(
+            edited,
+            COMMENTED.replace("# Staging.\n[presets.stage]\nrole = \"Stage\"\n\n", "")
+        );
+
+        // The last table takes the blank lines above it.
+        let edited = edit("a = 1\n\n[presets.prod]\nrole = \"Admin\"\n", |presets| {
+            presets.remove("prod");
+            vec!["prod".to_string()]
+        })
+        .unwrap();
+        assert_eq!(edited, "a = 1\n");
+    }
+
+    #[test]
+    fn add_presets() {
+        let edited = edit(COMMENTED, |presets| {
+            presets.insert("dev".to_string(), json!({"role": "Dev"}));
+            presets.insert("test".to_string(), json!({"role": "Test"}));
+            vec!["dev".to_string(), "test".to_string()]
+        })
+        .unwrap();
+        assert_eq!(
+            edited,
+            format!(
+                "{COMMENTED}\n[presets.dev]\nrole = \"Dev\"\n\n[presets.test]\nrole = \"Test\"\n"
+            )
+        );
+
+        let edited = edit("# no newline", |presets| {
+            presets.insert("dev".to_string(), json!({"role": "Dev"}));
+            vec!["dev".to_string()]
+        })
+        .unwrap();
+        assert_eq!(edited, "# no newline\n\n[presets.dev]\nrole = \"Dev\"\n");
+    }
+
+    #[test]
+    fn inline_presets() {
+        let add = |presets: &mut Map<String, Value>| {
+            presets.insert("prod".to_string(), json!({"role": "Admin"}));
+            vec!["prod".to_string()]
+        };
+
+        // The whole document is serialized if nothing is lost.
+        let edited = edit("[presets]\nprod = { role = \"Old\" }\n", add).unwrap();
+        assert_eq!(edited, "[presets.prod]\nrole = \"Admin\"\n");
+
+        assert!(edit("# comment\npresets.prod.role = \"Old\"\n", add).is_err());
+    }
+
+    #[test]
+    fn groups() {
+        let config_file = parse(
+            r#"
+[presets.prod]
+role = "Admin"
+group = "prod"
+
+[groups.prod]
+group = "all"
+external_id = "prod"
+
+[groups.all]
+duration_seconds = 3600
+"#,
+        );
+        config_file.check_groups().unwrap();
+        let preset = config_file.preset("prod").unwrap().unwrap();
+        assert_eq!(preset.external_id.as_deref(), Some("prod"));
+        assert_eq!(preset.duration_seconds, Some(3600));
+    }
+
+    #[test]
+    fn missing_groups() {
+        let config_file = parse(
+            r#"
+[presets.prod]
+role = "Admin"
+group = "prod"
+
+[groups.stage]
+group = "all"
+"#,
+        );
+        assert_eq!(
+            config_file.check_groups().unwrap_err().to_string(),
+            "the config is invalid:\n  \
+             group `prod` of preset `prod` is not found\n  \
+             group `all` of group `stage` is not found"
+        );
+    }
+
+    #[test]
+    fn cyclic_groups() {
+        let config_file = parse(
+            r#"
+[presets.prod]
+role = "Admin"
+group = "c"
+
+[groups.a]
+group = "b"
+
+[groups.b]
+group = "c"
+
+[groups.c]
+group = "a"
+
+[groups.self]
+group = "self"
+
+[groups.outside]
+group = "a"
+"#,
+        );
+        assert_eq!(
+            config_file.check_groups().unwrap_err().to_string(),
+            "the config is invalid:\n  \
+             groups refer to each other: `a` -> `b` -> `c` -> `a`\n  \
+             groups refer to each other: `self` -> `self`"
+        );
+        assert!(config_file.preset("prod").is_err());
+    }
+}
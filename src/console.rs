@@ -0,0 +1,284 @@
+use crate::assume::{assume_role, partition, AssumeArgs};
+use crate::clipboard::ClipboardArgs;
+use crate::config::{ConfigFile, Preset};
+use crate::qr::QrCode;
+use crate::{browser, http};
+use anyhow::{bail, Context as _, Result};
+use aws_config::SdkConfig;
+use aws_sdk_sts::types::Credentials;
+use aws_smithy_types::date_time::Format;
+use hyper::{header, Body, Request};
+use serde::Deserialize;
+
+/// The URLs of the AWS Management Console, which differ among partitions.
+struct Endpoints {
+    /// The endpoint of the federation.
+    federation: &'static str,
+
+    /// The home page of the console.
+    console: &'static str,
+
+    /// The page that lists the buckets of Amazon S3.
+    s3_buckets: &'static str,
+}
+
+impl Endpoints {
+    fn of(partition: &str) -> Self {
+        match partition {
+            "aws-cn" => Self {
+                federation: "https://signin.amazonaws.cn/federation",
+                console: "https://console.amazonaws.cn/",
+                s3_buckets: "https://console.amazonaws.cn/s3/buckets/",
+            },
+            "aws-us-gov" => Self {
+                federation: "https://signin.amazonaws-us-gov.com/federation",
+                console: "https://console.amazonaws-us-gov.com/",
+                s3_buckets: "https://console.amazonaws-us-gov.com/s3/buckets/",
+            },
+            _ => Self {
+                federation: "https://signin.aws.amazon.com/federation",
+                console: "https://console.aws.amazon.com/",
+                s3_buckets: "https://s3.console.aws.amazon.com/s3/buckets/",
+            },
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    assume: AssumeArgs,
+
+    /// The page to open after signing in: the name of a service such as `cloudwatch`, a bucket or a
+    /// prefix of Amazon S3 such as `s3://my-bucket/logs/`, or the URL of a page of the console.
+    #[arg(long, value_name = "PAGE", env = "ASSUME_ROLE_DESTINATION")]
+    destination: Option<String>,
+
+    /// Open the URL in the browser instead of printing it.
+    #[arg(long, conflicts_with = "clipboard", env = "ASSUME_ROLE_OPEN")]
+    open: bool,
+
+    /// The browser to open the URL in, such as `firefox` or `Google Chrome` on macOS, instead of
+    /// the default browser. Implies `--open`.
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "clipboard",
+        env = "ASSUME_ROLE_BROWSER"
+    )]
+    browser: Option<String>,
+
+    /// The profile of the browser to open the URL in, which is the name of a profile of Firefox or
+    /// the directory of one of Chromium-based browsers such as `Profile 1`.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_BROWSER_PROFILE")]
+    browser_profile: Option<String>,
+
+    /// Open the URL in the container of Firefox with this name, so that the consoles of multiple
+    /// roles can be used at the same time. Requires the extension "Open external links in a
+    /// container". Implies `--open` with Firefox unless `--browser` is specified.
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "clipboard",
+        env = "ASSUME_ROLE_CONTAINER"
+    )]
+    container: Option<String>,
+
+    /// The duration, in seconds, of the session of the console. Defaults to
+    /// `console_duration_seconds` of the preset, otherwise an hour.
+    #[arg(
+        long,
+        value_name = "NUMBER",
+        value_parser = clap::value_parser!(i32).range(900..=43200),
+        env = "ASSUME_ROLE_SESSION_DURATION"
+    )]
+    session_duration: Option<i32>,
+
+    /// The issuer of the sign-in URL, recorded in the sign-in event of CloudTrail. Defaults to
+    /// `console_issuer` of the preset.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_ISSUER")]
+    issuer: Option<String>,
+
+    /// Print the URL as a QR code to open it on another device.
+    #[arg(
+        long,
+        conflicts_with_all = ["open", "browser", "container", "clipboard"],
+        env = "ASSUME_ROLE_QR"
+    )]
+    qr: bool,
+
+    #[command(flatten)]
+    clipboard: ClipboardArgs,
+
+    /// Print only the URL, or a JSON object of the URL and the expiration of the session, for other
+    /// tools to consume. Takes precedence over the options to open the URL.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "url",
+        conflicts_with_all = ["qr", "clipboard"],
+        env = "ASSUME_ROLE_PRINT"
+    )]
+    print: Option<Print>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Print {
+    /// The URL alone.
+    Url,
+
+    /// A JSON object with `SigninUrl` and `Expiration`.
+    Json,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SigninToken {
+    signin_token: String,
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, args: Args) -> Result<()> {
+    let preset = match args.assume.role().or(config_file.role.as_deref()) {
+        Some(name) => config_file.preset(name)?.unwrap_or_default(),
+        None => Preset::default(),
+    };
+    let session_duration = args.session_duration.or(preset.console_duration_seconds);
+    let issuer = args.issuer.as_deref().or(preset.console_issuer.as_deref());
+    let session = assume_role(config, config_file, args.assume).await?;
+    let credentials = session.credentials;
+
+    // The partition of the assumed role is the one of the console, even if the region is not set.
+    let partition = session
+        .assumed_role_user
+        .as_ref()
+        .and_then(|user| user.arn().split(':').nth(1))
+        .unwrap_or_else(|| partition(config));
+    let endpoints = Endpoints::of(partition);
+    let region = config.region().map(|region| region.as_ref());
+    let destination = match &args.destination {
+        Some(page) => destination(&endpoints, page, region)?,
+        None => endpoints.console.to_string(),
+    };
+
+    let url = signin_url(
+        &endpoints,
+        &credentials,
+        session_duration,
+        issuer,
+        &destination,
+    )
+    .await?;
+    let expiration = credentials.expiration.fmt(Format::DateTime)?;
+
+    match args.print {
+        Some(Print::Url) => {
+            println!("{url}");
+            return Ok(());
+        }
+        Some(Print::Json) => {
+            let output = serde_json::json!({
+                "SigninUrl": url,
+                "Expiration": expiration,
+            });
+            println!("{output}");
+            return Ok(());
+        }
+        None => {}
+    }
+
+    eprintln!("The session will expire at {expiration} at the latest");
+    if args.open || args.browser.is_some() {
+        browser::open(
+            &url,
+            args.browser.as_deref(),
+            args.browser_profile.as_deref(),
+        )?;
+    } else if let Some(container) = &args.container {
+        browser::open(
+            &browser::container_url(&url, container),
+            Some(args.browser.as_deref().unwrap_or(browser::FIREFOX)),
+            args.browser_profile.as_deref(),
+        )?;
+    } else if args.qr {
+        print!("{}", QrCode::encode(url.as_bytes())?.render());
+    } else {
+        args.clipboard.output(&url).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the URL of the page of the console specified by `--destination`.
+fn destination(endpoints: &Endpoints, page: &str, region: Option<&str>) -> Result<String> {
+    if page.starts_with("https://") {
+        return Ok(page.to_string());
+    }
+
+    if let Some(path) = page.strip_prefix("s3://") {
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if bucket.is_empty() {
+            bail!("no bucket in `{page}`");
+        }
+        let mut url = format!("{}{}", endpoints.s3_buckets, urlencoding::encode(bucket));
+        if !prefix.is_empty() {
+            url = format!("{url}?prefix={}", urlencoding::encode(prefix));
+        }
+        return Ok(url);
+    }
+
+    let is_service = !page.is_empty()
+        && page
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !is_service {
+        bail!("`{page}` is neither the name of a service, a location of Amazon S3 nor a URL");
+    }
+    Ok(match region {
+        Some(region) => format!("{}{page}/home?region={region}", endpoints.console),
+        None => format!("{}{page}/home", endpoints.console),
+    })
+}
+
+/// Exchanges `credentials` for a sign-in token, and returns the URL that signs in to the console
+/// with it and opens `destination`.
+async fn signin_url(
+    endpoints: &Endpoints,
+    credentials: &Credentials,
+    session_duration: Option<i32>,
+    issuer: Option<&str>,
+    destination: &str,
+) -> Result<String> {
+    // https://docs.aws.amazon.com/IAM/latest/UserGuide/id_roles_providers_enable-console-custom-url.html
+    let session = serde_json::json!({
+        "sessionId": credentials.access_key_id(),
+        "sessionKey": credentials.secret_access_key(),
+        "sessionToken": credentials.session_token(),
+    });
+    // The credentials are posted rather than put in the URI, which may appear in errors.
+    let mut form = format!(
+        "Action=getSigninToken&SessionType=json&Session={}",
+        urlencoding::encode(&session.to_string())
+    );
+    if let Some(session_duration) = session_duration {
+        form.push_str(&format!("&SessionDuration={session_duration}"));
+    }
+    let request = Request::post(endpoints.federation)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form))?;
+    let body = http::send(request).await?;
+    let token: SigninToken =
+        serde_json::from_slice(&body).context("malformed response from the federation endpoint")?;
+
+    let mut url = format!(
+        "{}?Action=login&Destination={}&SigninToken={}",
+        endpoints.federation,
+        urlencoding::encode(destination),
+        urlencoding::encode(&token.signin_token)
+    );
+    if let Some(issuer) = issuer {
+        url.push_str(&format!("&Issuer={}", urlencoding::encode(issuer)));
+    }
+    Ok(url)
+}
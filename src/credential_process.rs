@@ -0,0 +1,37 @@
+use crate::assume::{assume_role, AssumeArgs};
+use crate::config::ConfigFile;
+use anyhow::Result;
+use aws_config::SdkConfig;
+use aws_sdk_sts::types::Credentials;
+use aws_smithy_types::date_time::Format;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    assume: AssumeArgs,
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, args: Args) -> Result<()> {
+    let credentials = assume_role(config, config_file, args.assume)
+        .await?
+        .credentials;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&document(&credentials)?)?
+    );
+
+    Ok(())
+}
+
+/// Returns `credentials` in the format expected by `credential_process`.
+pub fn document(credentials: &Credentials) -> Result<serde_json::Value> {
+    // https://docs.aws.amazon.com/cli/latest/userguide/cli-configure-sourcing-external.html
+    Ok(serde_json::json!({
+        "Version": 1,
+        "AccessKeyId": credentials.access_key_id(),
+        "SecretAccessKey": credentials.secret_access_key(),
+        "SessionToken": credentials.session_token(),
+        "Expiration": credentials.expiration().fmt(Format::DateTime)?,
+    }))
+}
@@ -0,0 +1,81 @@
+//! Encryption of cached sessions with external tools.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::Command;
+
+/// Encrypts `plaintext` with `age` for `recipients`.
+pub async fn age_encrypt(recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>> {
+    if recipients.is_empty() {
+        bail!("no age recipient is configured; set `cache.age_recipients` in the config file");
+    }
+
+    let mut cmd = Command::new("age");
+    cmd.arg("--encrypt");
+    for recipient in recipients {
+        cmd.args(["--recipient", recipient]);
+    }
+    filter(cmd, plaintext).await
+}
+
+/// Decrypts `ciphertext` with `age` using the identity file at `identity`.
+pub async fn age_decrypt(identity: Option<&std::path::Path>, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let Some(identity) = identity else {
+        bail!("no age identity is configured; set `cache.age_identity` in the config file");
+    };
+
+    let mut cmd = Command::new("age");
+    cmd.arg("--decrypt").arg("--identity").arg(identity);
+    filter(cmd, ciphertext).await
+}
+
+/// Encrypts `plaintext` with GnuPG for `recipient`.
+pub async fn gpg_encrypt(recipient: Option<&str>, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let Some(recipient) = recipient else {
+        bail!("no GnuPG key is configured; set `cache.gpg_recipient` in the config file");
+    };
+
+    let mut cmd = Command::new("gpg");
+    cmd.args([
+        "--batch",
+        "--yes",
+        "--quiet",
+        "--encrypt",
+        "--recipient",
+        recipient,
+    ]);
+    filter(cmd, plaintext).await
+}
+
+/// Decrypts `ciphertext` with GnuPG.
+pub async fn gpg_decrypt(ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let mut cmd = Command::new("gpg");
+    cmd.args(["--batch", "--quiet", "--decrypt"]);
+    filter(cmd, ciphertext).await
+}
+
+/// Runs `cmd` with `input` as its standard input and returns its standard output.
+async fn filter(mut cmd: Command, input: &[u8]) -> Result<Vec<u8>> {
+    let program = cmd.as_std().get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run `{program}`"))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let writer = tokio::spawn(async move {
+        stdin.write_all(&input).await?;
+        stdin.shutdown().await
+    });
+
+    let output = child.wait_with_output().await?;
+    writer.await??;
+    if !output.status.success() {
+        return Err(anyhow!("`{program}` failed: {}", output.status));
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,73 @@
+use crate::assume::{assume_role, AssumeArgs, Session};
+use crate::cache::role_arn_of;
+use crate::config::ConfigFile;
+use crate::profile;
+use crate::registry::{self, Instance};
+use crate::serve::{renewal_time, RENEW_BEFORE, RETRY_INTERVAL};
+use anyhow::Result;
+use aws_config::SdkConfig;
+use aws_smithy_types::date_time::Format;
+use std::time::SystemTime;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    assume: AssumeArgs,
+
+    /// The profile in the shared credentials file to keep the credentials in.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_WRITE_PROFILE")]
+    write_profile: String,
+
+    /// The name to attach to the daemon with. Defaults to the name of the profile.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_INSTANCE_NAME")]
+    name: Option<String>,
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, mut args: Args) -> Result<()> {
+    let name = args.name.as_ref().unwrap_or(&args.write_profile).clone();
+    // The credentials must be assumed again, not taken from the cache, when they are renewed.
+    args.assume.require_min_ttl(RENEW_BEFORE);
+
+    // The first credentials are required so that mistakes in the arguments are reported at once.
+    let session = write(config, config_file, &args).await?;
+    let mut renew_at = renewal_time(&session.credentials);
+
+    let instance = Instance {
+        pid: std::process::id(),
+        role: session
+            .assumed_role_user
+            .and_then(|user| role_arn_of(&user.arn)),
+        server: None,
+        env: vec![("AWS_PROFILE".to_string(), args.write_profile.clone())],
+    };
+    let _registration = registry::register(&name, &instance).await?;
+
+    loop {
+        let wait = renew_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        renew_at = match write(config, config_file, &args).await {
+            Ok(session) => renewal_time(&session.credentials),
+            Err(e) => {
+                tracing::warn!("failed to renew the credentials: {e:#}");
+                SystemTime::now() + RETRY_INTERVAL
+            }
+        };
+    }
+}
+
+/// Writes the credentials of a new session to the profile.
+async fn write(config: &SdkConfig, config_file: &ConfigFile, args: &Args) -> Result<Session> {
+    let session = assume_role(config, config_file, args.assume.clone()).await?;
+    let credentials = &session.credentials;
+    profile::write_profile(&args.write_profile, credentials).await?;
+    eprintln!(
+        "Wrote the credentials to the profile `{}`, which will expire at {}",
+        args.write_profile,
+        credentials.expiration.fmt(Format::DateTime)?
+    );
+
+    Ok(session)
+}
@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+/// Returns the home directory of the current user.
+pub fn home_dir() -> Result<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("failed to determine the home directory"))
+}
+
+/// Returns the directory to store the cache of this application.
+pub fn cache_dir() -> Result<PathBuf> {
+    let base = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".cache"),
+    };
+    Ok(base.join("assume-role"))
+}
+
+/// Returns the directory to store the configuration of this application.
+pub fn config_dir() -> Result<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".config"),
+    };
+    Ok(base.join("assume-role"))
+}
+
+/// Returns a directory on a memory-backed file system private to the current user.
+pub fn runtime_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|s| !s.is_empty()) {
+        return Ok(PathBuf::from(dir).join("assume-role"));
+    }
+
+    #[cfg(target_os = "linux")]
+    if std::path::Path::new("/dev/shm").is_dir() {
+        let uid = unsafe { libc::getuid() };
+        return Ok(PathBuf::from(format!("/dev/shm/assume-role-{uid}")));
+    }
+
+    Err(anyhow!(
+        "no memory-backed directory is available; set `XDG_RUNTIME_DIR`"
+    ))
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+/// Parses a duration such as `90`, `15m` or `1h30m`. A number without a unit is in seconds.
+pub fn parse(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    if let Ok(secs) = s.parse() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut secs = 0u64;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("missing unit in `{s}`"))?;
+        let value: u64 = rest[..end]
+            .parse()
+            .map_err(|_| format!("invalid duration: `{s}`"))?;
+        let unit = match rest[end..].chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 24 * 60 * 60,
+            _ => return Err(format!("invalid duration: `{s}`")),
+        };
+        secs = value
+            .checked_mul(unit)
+            .and_then(|value| secs.checked_add(value))
+            .ok_or_else(|| format!("duration is too long: `{s}`"))?;
+        rest = &rest[end + 1..];
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_seconds() {
+        assert_eq!(parse("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse(" 0 "), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse("45s"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse("1d2h3m4s"), Ok(Duration::from_secs(93_784)));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("  ").is_err());
+        assert!(parse("1h30").is_err());
+        assert!(parse("h").is_err());
+        assert!(parse("1w").is_err());
+        assert!(parse("-1m").is_err());
+    }
+
+    #[test]
+    fn parse_overflow() {
+        assert!(parse("18446744073709551615s").is_ok());
+        assert!(parse("18446744073709551615m").is_err());
+        assert!(parse("18446744073709551615s1s").is_err());
+        assert!(parse("99999999999999999999s").is_err());
+    }
+}
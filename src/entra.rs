@@ -0,0 +1,293 @@
+use crate::config::EntraConfig;
+use crate::http::{self, Agent};
+use crate::{prompt, saml};
+use anyhow::{anyhow, bail, Context as _, Result};
+use base64::Engine as _;
+use chrono::{SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+
+const LOGIN_URL: &str = "https://login.microsoftonline.com";
+
+/// The default identifier (entity ID) of the AWS enterprise application.
+const DEFAULT_APP_ID_URI: &str = "https://signin.aws.amazon.com/saml";
+
+#[derive(Clone, clap::Args)]
+pub struct EntraArgs {
+    /// The ID of the Microsoft Entra tenant to obtain the SAML assertion from.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with_all = saml::CONFLICTS,
+        conflicts_with_all = ["okta", "okta_app_url"],
+        env = "ASSUME_ROLE_ENTRA_TENANT_ID"
+    )]
+    entra_tenant_id: Option<String>,
+
+    /// The identifier (entity ID) of the AWS enterprise application. Defaults to
+    /// `entra.app_id_uri` in the config file or `https://signin.aws.amazon.com/saml`.
+    #[arg(long, value_name = "URI", env = "ASSUME_ROLE_ENTRA_APP_ID_URI")]
+    entra_app_id_uri: Option<String>,
+
+    /// The user name to sign in to Microsoft Entra ID with. Defaults to `entra.username` in the config file.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_ENTRA_USERNAME")]
+    entra_username: Option<String>,
+
+    /// Obtain the SAML assertion from the tenant of `entra.tenant_id` in the config file.
+    #[arg(
+        long,
+        conflicts_with_all = saml::CONFLICTS,
+        conflicts_with_all = ["okta", "okta_app_url"],
+        env = "ASSUME_ROLE_ENTRA"
+    )]
+    entra: bool,
+}
+
+impl EntraArgs {
+    /// Returns whether the SAML assertion is obtained from Microsoft Entra ID.
+    pub fn enabled(&self) -> bool {
+        self.entra || self.entra_tenant_id.is_some()
+    }
+
+    /// Signs in to Microsoft Entra ID and returns the base64-encoded SAML assertion for the AWS
+    /// enterprise application.
+    pub async fn saml_assertion(&self, config: &EntraConfig) -> Result<String> {
+        let tenant_id = self
+            .entra_tenant_id
+            .as_ref()
+            .or(config.tenant_id.as_ref())
+            .ok_or_else(|| anyhow!("the tenant ID of Microsoft Entra ID is not specified"))?;
+        let app_id_uri = self
+            .entra_app_id_uri
+            .as_deref()
+            .or(config.app_id_uri.as_deref())
+            .unwrap_or(DEFAULT_APP_ID_URI);
+        let username = self
+            .entra_username
+            .as_ref()
+            .or(config.username.as_ref())
+            .ok_or_else(|| anyhow!("the user name of Microsoft Entra ID is not specified"))?;
+
+        let mut agent = Agent::default();
+        let mut page = agent
+            .get(&format!(
+                "{LOGIN_URL}/{tenant_id}/saml2?SAMLRequest={}",
+                urlencoding::encode(&authn_request(app_id_uri)?)
+            ))
+            .await?;
+
+        let mut signed_in = false;
+        loop {
+            if let Ok(assertion) = saml::form_value(&page) {
+                return Ok(assertion);
+            }
+
+            let config = Config::parse(&page)?;
+            if let Some(message) = config.error() {
+                bail!("Microsoft Entra ID: {message}");
+            }
+
+            page = if !signed_in {
+                signed_in = true;
+                let password =
+                    prompt::secret(&format!("Microsoft Entra ID password for {username}: "))
+                        .await?;
+                agent
+                    .post_form(
+                        &config.url("urlPost")?,
+                        &[
+                            ("login", username),
+                            ("loginfmt", username),
+                            ("passwd", &password),
+                            ("type", "11"),
+                            ("LoginOptions", "3"),
+                            ("ctx", config.str("sCtx")?),
+                            ("flowToken", config.str("sFT")?),
+                            ("canary", config.str("canary")?),
+                        ],
+                    )
+                    .await?
+            } else if config.value.get("arrUserProofs").is_some() {
+                verify(&mut agent, &config, username).await?
+            } else if config.value.get("pgid").and_then(Value::as_str) == Some("KmsiInterrupt") {
+                agent
+                    .post_form(
+                        &format!("{LOGIN_URL}/kmsi"),
+                        &[
+                            ("LoginOptions", "1"),
+                            ("type", "28"),
+                            ("ctx", config.str("sCtx")?),
+                            ("flowToken", config.str("sFT")?),
+                            ("canary", config.str("canary")?),
+                        ],
+                    )
+                    .await?
+            } else {
+                bail!("unexpected page from Microsoft Entra ID");
+            };
+        }
+    }
+}
+
+/// Returns a deflated and base64-encoded SAML authentication request for `app_id_uri`.
+fn authn_request(app_id_uri: &str) -> Result<String> {
+    let mut id = [0; 16];
+    ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut id)
+        .map_err(|_| anyhow!("failed to generate a request ID"))?;
+
+    let xml = format!(
+        r#"<samlp:AuthnRequest xmlns="urn:oasis:names:tc:SAML:2.0:metadata" ID="id{}" Version="2.0" IssueInstant="{}" IsPassive="false" AssertionConsumerServiceURL="https://signin.aws.amazon.com/saml" xmlns:samlp="urn:oasis:names:tc:SAML:2.0:protocol"><Issuer xmlns="urn:oasis:names:tc:SAML:2.0:assertion">{}</Issuer><samlp:NameIDPolicy Format="urn:oasis:names:tc:SAML:1.1:nameid-format:unspecified"/></samlp:AuthnRequest>"#,
+        hex::encode(id),
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        app_id_uri
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('"', "&quot;"),
+    );
+
+    let deflated = miniz_oxide::deflate::compress_to_vec(xml.as_bytes(), 6);
+    Ok(base64::engine::general_purpose::STANDARD.encode(deflated))
+}
+
+/// The `$Config` object embedded in the pages of the sign-in flow.
+struct Config {
+    value: Value,
+}
+
+impl Config {
+    fn parse(page: &str) -> Result<Self> {
+        let start = page
+            .find("$Config=")
+            .ok_or_else(|| anyhow!("unexpected page from Microsoft Entra ID"))?;
+        let value = serde_json::Deserializer::from_str(&page[start + "$Config=".len()..])
+            .into_iter::<Value>()
+            .next()
+            .ok_or_else(|| anyhow!("unexpected page from Microsoft Entra ID"))?
+            .context("malformed page from Microsoft Entra ID")?;
+        Ok(Self { value })
+    }
+
+    fn str(&self, key: &str) -> Result<&str> {
+        self.value
+            .get(key)
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("`{key}` is missing in the page from Microsoft Entra ID"))
+    }
+
+    fn url(&self, key: &str) -> Result<String> {
+        let base = LOGIN_URL.parse()?;
+        Ok(http::resolve(&base, self.str(key)?)?.to_string())
+    }
+
+    fn error(&self) -> Option<&str> {
+        ["strServiceExceptionMessage", "sErrTxt"]
+            .iter()
+            .find_map(|key| self.value.get(*key).and_then(Value::as_str))
+            .filter(|message| !message.is_empty())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AuthResponse {
+    success: bool,
+    result_value: Option<String>,
+    message: Option<String>,
+    session_id: Option<String>,
+    flow_token: Option<String>,
+    ctx: Option<String>,
+    entropy: Option<u32>,
+}
+
+/// Verifies the default MFA method of the user and returns the next page.
+async fn verify(agent: &mut Agent, config: &Config, username: &str) -> Result<String> {
+    let proofs = config
+        .value
+        .get("arrUserProofs")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("no MFA method is available"))?;
+    let proof = proofs
+        .iter()
+        .find(|p| p.get("isDefault").and_then(Value::as_bool) == Some(true))
+        .or_else(|| proofs.first())
+        .ok_or_else(|| anyhow!("no MFA method is available"))?;
+    let method = proof
+        .get("authMethodId")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("malformed MFA method"))?;
+
+    let begin: AuthResponse = agent
+        .post_json(
+            &config.url("urlBeginAuth")?,
+            &serde_json::json!({
+                "AuthMethodId": method,
+                "Method": "BeginAuth",
+                "ctx": config.str("sCtx")?,
+                "flowToken": config.str("sFT")?,
+            }),
+        )
+        .await?;
+    if !begin.success {
+        bail!("failed to begin MFA: {}", begin.message.unwrap_or_default());
+    }
+
+    let code = match method {
+        "PhoneAppNotification" | "CompanionAppsNotification" => {
+            match begin.entropy.filter(|n| *n != 0) {
+                Some(number) => {
+                    eprintln!("Enter {number} in Microsoft Authenticator to approve...")
+                }
+                None => eprintln!("Waiting for approval in Microsoft Authenticator..."),
+            }
+            None
+        }
+        _ => Some(prompt::secret("Microsoft Entra ID verification code: ").await?),
+    };
+
+    let mut state = begin;
+    for poll_count in 1.. {
+        let end: AuthResponse = agent
+            .post_json(
+                &config.url("urlEndAuth")?,
+                &serde_json::json!({
+                    "AuthMethodId": method,
+                    "Method": "EndAuth",
+                    "SessionId": state.session_id,
+                    "FlowToken": state.flow_token,
+                    "Ctx": state.ctx,
+                    "AdditionalAuthData": code,
+                    "PollCount": poll_count,
+                }),
+            )
+            .await?;
+        if end.success {
+            state = end;
+            break;
+        }
+
+        match end.result_value.as_deref() {
+            Some("AuthenticationPending") if code.is_none() => {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+            result => bail!(
+                "MFA failed: {}",
+                end.message.as_deref().or(result).unwrap_or("unknown error")
+            ),
+        }
+    }
+
+    agent
+        .post_form(
+            &config.url("urlPost")?,
+            &[
+                ("type", "22"),
+                ("request", state.ctx.as_deref().unwrap_or_default()),
+                ("mfaAuthMethod", method),
+                ("login", username),
+                ("flowToken", state.flow_token.as_deref().unwrap_or_default()),
+                ("canary", config.str("canary")?),
+            ],
+        )
+        .await
+}
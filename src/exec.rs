@@ -0,0 +1,626 @@
+use crate::assume::{assume_role, environment, AssumeArgs};
+use crate::config::ConfigFile;
+use crate::duration;
+use anyhow::{anyhow, bail, Context as _, Result};
+use aws_config::SdkConfig;
+use aws_sdk_sts::types::Credentials;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::time::{Duration, SystemTime};
+use tokio::process::Child;
+use tokio::time::Instant;
+
+/// The exit code when the command times out, which is the same as `timeout` of GNU coreutils.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// The environment variables of the credentials.
+const CREDENTIAL_VARIABLES: [&str; 3] = [
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+];
+
+/// How long the command is given to exit after being asked to terminate on timeout.
+const KILL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    assume: AssumeArgs,
+
+    #[command(flatten)]
+    options: RunOptions,
+
+    /// A command and its arguments to run as the assumed role. Runs current shell if not specified.
+    command: Vec<String>,
+}
+
+/// Options of how the command is run, shared by the commands that run one.
+#[derive(clap::Args)]
+pub struct RunOptions {
+    /// The shell to run if no command is specified, or to run `--command` with. Defaults to `SHELL` or the login shell of the
+    /// user.
+    #[arg(long, value_name = "PATH", env = "ASSUME_ROLE_SHELL")]
+    shell: Option<String>,
+
+    /// A command line to run with the shell, which may contain pipelines and lists of commands.
+    #[arg(
+        short = 'c',
+        long = "command",
+        value_name = "STRING",
+        conflicts_with = "command",
+        env = "ASSUME_ROLE_COMMAND"
+    )]
+    command_string: Option<String>,
+
+    /// Run the shell as a login shell.
+    #[cfg(unix)]
+    #[arg(short = 'l', long, env = "ASSUME_ROLE_LOGIN_SHELL")]
+    login_shell: bool,
+
+    /// Remove the credentials and the profile inherited from the environment, so that they cannot
+    /// take precedence over the assumed role.
+    #[arg(long, env = "ASSUME_ROLE_CLEAN_ENV")]
+    clean_env: bool,
+
+    /// An environment variable to set for the command, in addition to the credentials.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env, env = "ASSUME_ROLE_ENV")]
+    env: Vec<(String, String)>,
+
+    /// The working directory of the command.
+    #[arg(long, value_name = "PATH", env = "ASSUME_ROLE_CWD")]
+    cwd: Option<PathBuf>,
+
+    /// Run the command on a pseudo-terminal, so that interactive commands work even if the standard
+    /// I/O of assume-role is not a terminal.
+    #[cfg(unix)]
+    #[arg(long, env = "ASSUME_ROLE_PTY")]
+    pty: bool,
+
+    /// The time after which the command is terminated, such as `30m`.
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = duration::parse,
+        env = "ASSUME_ROLE_COMMAND_TIMEOUT"
+    )]
+    command_timeout: Option<Duration>,
+
+    /// What to do with the command when the credentials expire.
+    #[arg(long, value_enum, value_name = "ACTION", env = "ASSUME_ROLE_ON_EXPIRY")]
+    on_expiry: Option<OnExpiry>,
+
+    /// Replace assume-role with the command instead of running it as a child process.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with_all = ["pty", "on_expiry", "command_timeout"], env = "ASSUME_ROLE_EXEC")]
+    exec: bool,
+
+    /// Pass the credentials to the command as the JSON of `credential_process` on this file
+    /// descriptor instead of the environment variables, which are visible to every descendant.
+    #[cfg(unix)]
+    #[arg(
+        long,
+        value_name = "FD",
+        value_parser = clap::value_parser!(i32).range(3..),
+        env = "ASSUME_ROLE_CREDS_FD"
+    )]
+    creds_fd: Option<i32>,
+
+    /// Pass the credentials to the command as the JSON of `credential_process` through a named pipe
+    /// created at this path instead of the environment variables. The pipe can be read as many
+    /// times as needed, and is removed when the command exits.
+    #[cfg(unix)]
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["creds_fd", "exec"],
+        env = "ASSUME_ROLE_CREDS_FIFO"
+    )]
+    creds_fifo: Option<PathBuf>,
+}
+
+/// What to do with the command when the credentials expire.
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OnExpiry {
+    /// Terminate the command.
+    Kill,
+    /// Print a warning and let the command run.
+    Warn,
+    /// Terminate the command and run it again with new credentials.
+    Restart,
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, args: Args) -> Result<()> {
+    let assume = &args.assume;
+    execute(
+        move || async move {
+            Ok(assume_role(config, config_file, assume.clone())
+                .await?
+                .credentials)
+        },
+        &args.command,
+        &args.options,
+    )
+    .await
+}
+
+/// Runs `command`, or the current shell if empty, with the credentials returned by `obtain` in its
+/// environment.
+///
+/// `obtain` is called again to restart the command when the credentials expire, if requested.
+pub async fn execute<F, Fut>(mut obtain: F, command: &[String], options: &RunOptions) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Credentials>>,
+{
+    let deadline = deadline(options);
+    #[cfg(unix)]
+    let fifo = options
+        .creds_fifo
+        .as_deref()
+        .map(crate::pipe::Fifo::create)
+        .transpose()?;
+    let status = loop {
+        let credentials = obtain().await?;
+        println!(
+            "Credentials will expire at {}",
+            credentials
+                .expiration
+                .fmt(aws_smithy_types::date_time::Format::DateTime)?
+        );
+        let expires_in = SystemTime::try_from(credentials.expiration)?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+
+        let mut cmd = build(command, options)?;
+        #[cfg(unix)]
+        let mut _reader = None;
+        #[cfg(unix)]
+        if options.creds_fd.is_some() || fifo.is_some() {
+            // The inherited credentials would be used instead of the passed ones.
+            for name in CREDENTIAL_VARIABLES {
+                cmd.env_remove(name);
+            }
+
+            let document = serde_json::to_vec(&crate::credential_process::document(&credentials)?)?;
+            if let Some(fd) = options.creds_fd {
+                _reader = Some(crate::pipe::pass(&mut cmd, fd, &document)?);
+            }
+            if let Some(fifo) = &fifo {
+                fifo.set(document);
+            }
+        } else {
+            cmd.envs(environment(&credentials));
+        }
+        #[cfg(not(unix))]
+        cmd.envs(environment(&credentials));
+
+        #[cfg(unix)]
+        if options.exec {
+            let error = std::os::unix::process::CommandExt::exec(&mut cmd);
+            return Err(error).context("failed to execute the command");
+        }
+
+        let (status, restart) = spawn(cmd, options, deadline, Some(expires_in)).await?;
+        if !restart || status.is_none() {
+            break status;
+        }
+    };
+
+    #[cfg(unix)]
+    drop(fifo);
+    exit(status)
+}
+
+/// Runs `command`, or the current shell if empty, with `env` in its environment instead of the
+/// credentials, which are provided by a server that renews them.
+///
+/// Returns the exit status of the command, or `None` if it timed out, to be passed to [`exit`] once
+/// the server is shut down.
+pub async fn execute_with_env(
+    env: &[(&str, String)],
+    command: &[String],
+    options: &RunOptions,
+) -> Result<Option<ExitStatus>> {
+    #[cfg(unix)]
+    if options.exec {
+        bail!("`--exec` cannot be used while serving the credentials");
+    }
+    if options.on_expiry.is_some() {
+        bail!("`--on-expiry` cannot be used while serving the credentials, which are renewed");
+    }
+    #[cfg(unix)]
+    if options.creds_fd.is_some() || options.creds_fifo.is_some() {
+        bail!("`--creds-fd` and `--creds-fifo` cannot be used while serving the credentials");
+    }
+
+    let mut cmd = build(command, options)?;
+    // The inherited credentials and container endpoint would take precedence over the served ones.
+    for (name, _) in std::env::vars_os() {
+        let inherited = name.to_str().is_some_and(|name| {
+            CREDENTIAL_VARIABLES.contains(&name) || name.starts_with("AWS_CONTAINER_")
+        });
+        if inherited {
+            cmd.env_remove(name);
+        }
+    }
+    cmd.envs(env.iter().map(|(key, value)| (key, value)));
+
+    let (status, _) = spawn(cmd, options, deadline(options), None).await?;
+    Ok(status)
+}
+
+/// Returns the time after which the command is terminated.
+fn deadline(options: &RunOptions) -> Option<Instant> {
+    options
+        .command_timeout
+        .map(|timeout| Instant::now() + timeout)
+}
+
+/// Runs `cmd` until it exits or `deadline`, and returns its exit status, or `None` if it timed out,
+/// and whether it should be restarted with new credentials as they expired in `expires_in`.
+async fn spawn(
+    mut cmd: Command,
+    options: &RunOptions,
+    deadline: Option<Instant>,
+    expires_in: Option<Duration>,
+) -> Result<(Option<ExitStatus>, bool)> {
+    // The command leads a process group to be terminated with its descendants on timeout, unless
+    // it leads a session on a pseudo-terminal. The group is given the terminal if this process has
+    // it, since reading from or configuring the terminal in a background group stops the command.
+    #[cfg(unix)]
+    let foreground = if options.command_timeout.is_some() && !options.pty {
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let foreground = unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() };
+        if foreground {
+            unsafe {
+                std::os::unix::process::CommandExt::pre_exec(&mut cmd, || {
+                    set_foreground(libc::getpgrp());
+                    Ok(())
+                });
+            }
+        }
+        foreground
+    } else {
+        false
+    };
+
+    #[cfg(unix)]
+    let pty = if options.pty {
+        let pty = crate::pty::Pty::open()?;
+        pty.attach(&mut cmd)?;
+        Some(pty)
+    } else {
+        None
+    };
+
+    let mut child = tokio::process::Command::from(cmd).spawn()?;
+    #[cfg(unix)]
+    let relay = pty.map(crate::pty::Pty::relay).transpose()?;
+
+    let on_expiry = expires_in.and(options.on_expiry);
+    let result = tokio::select! {
+        status = wait_until(&mut child, deadline) => status.map(|status| (status, false)),
+        _ = tokio::time::sleep(expires_in.unwrap_or_default()), if on_expiry.is_some() => {
+            match on_expiry {
+                Some(OnExpiry::Warn) => {
+                    eprintln!("WARNING: the credentials of the command have expired");
+                    wait_until(&mut child, deadline).await.map(|status| (status, false))
+                }
+                Some(OnExpiry::Restart) => {
+                    eprintln!("The credentials have expired; restarting the command");
+                    terminate(&mut child)?;
+                    wait_until(&mut child, deadline).await.map(|status| (status, true))
+                }
+                Some(OnExpiry::Kill) | None => {
+                    eprintln!("The credentials have expired; terminating the command");
+                    terminate(&mut child)?;
+                    wait_until(&mut child, deadline).await.map(|status| (status, false))
+                }
+            }
+        }
+    };
+
+    #[cfg(unix)]
+    if let Some(relay) = relay {
+        relay.finish();
+    }
+    #[cfg(unix)]
+    if foreground {
+        set_foreground(unsafe { libc::getpgrp() });
+    }
+    result
+}
+
+/// Makes the process group `pgrp` the foreground one of the terminal on the standard input.
+///
+/// SIGTTOU, which a background process gets for this, is ignored meanwhile. Only async-signal-safe
+/// functions are called so that this can also be called between `fork` and `exec`.
+#[cfg(unix)]
+fn set_foreground(pgrp: libc::pid_t) {
+    unsafe {
+        let handler = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgrp);
+        libc::signal(libc::SIGTTOU, handler);
+    }
+}
+
+/// Exits with the exit code of the command if it failed or timed out.
+pub fn exit(status: Option<ExitStatus>) -> Result<()> {
+    let Some(status) = status else {
+        std::process::exit(TIMEOUT_EXIT_CODE);
+    };
+    if !status.success() {
+        std::process::exit(exit_code(status));
+    }
+
+    Ok(())
+}
+
+/// Builds the command to run.
+fn build(command: &[String], options: &RunOptions) -> Result<Command> {
+    let mut cmd = if command.is_empty() {
+        let shell = shell(options)?;
+        let mut cmd = Command::new(&shell);
+        // A shell started with the name prefixed by `-` runs as a login shell.
+        #[cfg(unix)]
+        if options.login_shell {
+            let name = shell.rsplit('/').next().unwrap_or(&shell);
+            std::os::unix::process::CommandExt::arg0(&mut cmd, format!("-{name}"));
+        }
+        if let Some(command) = &options.command_string {
+            cmd.arg("-c").arg(command);
+        }
+        cmd
+    } else {
+        let mut iter = command.iter();
+        let mut cmd = Command::new(iter.next().unwrap());
+        cmd.args(iter);
+        cmd
+    };
+
+    if options.clean_env {
+        for (name, _) in std::env::vars_os() {
+            if name.to_str().is_some_and(is_inherited_credential) {
+                cmd.env_remove(name);
+            }
+        }
+    }
+    cmd.envs(options.env.iter().map(|(key, value)| (key, value)));
+    if let Some(cwd) = &options.cwd {
+        cmd.current_dir(cwd);
+    }
+
+    Ok(cmd)
+}
+
+/// Returns the shell to run, which is the one specified by `--shell`, `SHELL`, or the passwd database
+/// in this order.
+fn shell(options: &RunOptions) -> Result<String> {
+    if let Some(shell) = &options.shell {
+        return Ok(shell.clone());
+    }
+    if let Some(shell) = std::env::var("SHELL").ok().filter(|s| !s.is_empty()) {
+        return Ok(shell);
+    }
+
+    #[cfg(unix)]
+    {
+        let passwd = unsafe { libc::getpwuid(libc::getuid()) };
+        if !passwd.is_null() && !unsafe { (*passwd).pw_shell }.is_null() {
+            let shell = unsafe { std::ffi::CStr::from_ptr((*passwd).pw_shell) };
+            let shell = shell.to_string_lossy();
+            if !shell.is_empty() {
+                return Ok(shell.into_owned());
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "failed to determine the shell; set `SHELL` or specify `--shell`"
+    ))
+}
+
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("`{s}` is not in the form of KEY=VALUE")),
+    }
+}
+
+/// Returns whether the environment variable `name` specifies credentials or how to obtain them.
+fn is_inherited_credential(name: &str) -> bool {
+    matches!(
+        name,
+        "AWS_ACCESS_KEY_ID"
+            | "AWS_SECRET_ACCESS_KEY"
+            | "AWS_SESSION_TOKEN"
+            | "AWS_SECURITY_TOKEN"
+            | "AWS_CREDENTIAL_EXPIRATION"
+            | "AWS_SESSION_EXPIRATION"
+            | "AWS_PROFILE"
+            | "AWS_DEFAULT_PROFILE"
+            | "AWS_ROLE_ARN"
+            | "AWS_ROLE_SESSION_NAME"
+            | "AWS_WEB_IDENTITY_TOKEN_FILE"
+            | "AWS_VAULT"
+    ) || name.starts_with("AWS_CONTAINER_")
+}
+
+/// Waits for `child` to exit, forwarding SIGINT, SIGTERM and SIGHUP received in the meantime so
+/// that the command is terminated instead of being left behind, unless the terminal has already
+/// sent them to it.
+#[cfg(unix)]
+async fn wait(child: &mut Child) -> Result<ExitStatus> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    loop {
+        let number = tokio::select! {
+            status = child.wait() => return Ok(status?),
+            _ = interrupt.recv() => libc::SIGINT,
+            _ = terminate.recv() => libc::SIGTERM,
+            _ = hangup.recv() => libc::SIGHUP,
+        };
+        if should_forward(child, number) {
+            send_signal(child, number);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait(child: &mut Child) -> Result<ExitStatus> {
+    // Console control events are delivered to every process attached to the console.
+    Ok(child.wait().await?)
+}
+
+/// Waits for `child` to exit, or terminates it at `deadline` and returns `None`.
+async fn wait_until(child: &mut Child, deadline: Option<Instant>) -> Result<Option<ExitStatus>> {
+    let Some(deadline) = deadline else {
+        return Ok(Some(wait(child).await?));
+    };
+    if let Ok(status) = tokio::time::timeout_at(deadline, wait(child)).await {
+        return Ok(Some(status?));
+    }
+
+    eprintln!("The command timed out; terminating it");
+    terminate(child)?;
+    if tokio::time::timeout(KILL_TIMEOUT, wait(child))
+        .await
+        .is_err()
+    {
+        #[cfg(unix)]
+        send_signal(child, libc::SIGKILL);
+        #[cfg(not(unix))]
+        child.start_kill()?;
+        wait(child).await?;
+    }
+    Ok(None)
+}
+
+/// Asks `child` to terminate.
+fn terminate(child: &mut Child) -> Result<()> {
+    #[cfg(unix)]
+    {
+        send_signal(child, libc::SIGTERM);
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    Ok(child.start_kill()?)
+}
+
+/// Returns whether the signal `number` received by this process should be sent to `child`.
+///
+/// The terminal sends SIGINT of Ctrl-C and SIGHUP of a hangup to its whole foreground process
+/// group, so they reach `child` directly if it is in the process group of this process and the
+/// group is in the foreground; forwarding them would deliver them twice. Such signals sent by
+/// `kill` to this process alone cannot be told apart, and are not forwarded either.
+#[cfg(unix)]
+fn should_forward(child: &Child, number: libc::c_int) -> bool {
+    let Some(pid) = child.id() else {
+        return false;
+    };
+    if number == libc::SIGTERM || unsafe { libc::getpgid(pid as libc::pid_t) } == pid as libc::pid_t
+    {
+        return true;
+    }
+
+    let group = unsafe { libc::getpgrp() };
+    let foreground = [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO]
+        .into_iter()
+        .any(|fd| unsafe { libc::tcgetpgrp(fd) } == group);
+    !foreground
+}
+
+/// Sends the signal `number` to `child`, and to its process group if it leads one.
+#[cfg(unix)]
+fn send_signal(child: &Child, number: libc::c_int) {
+    if let Some(pid) = child.id() {
+        let pid = pid as libc::pid_t;
+        let target = if unsafe { libc::getpgid(pid) } == pid {
+            -pid
+        } else {
+            pid
+        };
+        unsafe { libc::kill(target, number) };
+    }
+}
+
+/// Returns the exit code that reflects `status`, following the convention of shells for the
+/// commands terminated by signals.
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+
+    status.code().unwrap_or(1)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use clap::Parser as _;
+    use std::io::Write as _;
+
+    /// The environment variable that makes [`interactive_command_on_terminal`] run the command.
+    const TERMINAL_TEST: &str = "ASSUME_ROLE_TEST_TERMINAL";
+
+    #[derive(clap::Parser)]
+    struct Cli {
+        #[command(flatten)]
+        options: RunOptions,
+
+        command: Vec<String>,
+    }
+
+    #[test]
+    fn interactive_command_with_timeout() {
+        // This test runs itself again on a pseudo-terminal, which is the controlling terminal of
+        // the process.
+        let pty = crate::pty::Pty::open().unwrap();
+        let mut cmd = Command::new(std::env::current_exe().unwrap());
+        cmd.args([
+            "--exact",
+            "exec::tests::interactive_command_on_terminal",
+            "--test-threads=1",
+        ])
+        .env(TERMINAL_TEST, "1");
+        pty.attach(&mut cmd).unwrap();
+        let mut child = cmd.spawn().unwrap();
+
+        pty.master().write_all(b"hello\n").unwrap();
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn interactive_command_on_terminal() {
+        if std::env::var_os(TERMINAL_TEST).is_none() {
+            return;
+        }
+
+        let cli = Cli::parse_from([
+            "assume-role",
+            "--command-timeout",
+            "5s",
+            "--",
+            "sh",
+            "-c",
+            r#"read x && [ "$x" = hello ]"#,
+        ]);
+        let status = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(execute_with_env(&[], &cli.command, &cli.options))
+            .unwrap();
+        // The command would be stopped by SIGTTIN until it timed out in a background group.
+        assert!(status.is_some_and(|status| status.success()));
+        // The terminal is given back.
+        assert_eq!(unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) }, unsafe {
+            libc::getpgrp()
+        });
+    }
+}
@@ -0,0 +1,120 @@
+use crate::assume::{parse_tags, partition, resolve_policy_arns, Session, SessionOptions};
+use crate::config::ConfigFile;
+use crate::{cache, exec, source};
+use anyhow::Result;
+use aws_config::SdkConfig;
+use aws_sdk_sts::types::{PolicyDescriptorType, Tag};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The name of the federated user.
+    #[arg(short, long, value_name = "NAME", env = "ASSUME_ROLE_NAME")]
+    name: String,
+
+    #[command(flatten)]
+    session: SessionOptions,
+
+    /// The duration, in seconds, that the session should last.
+    #[arg(long, value_name = "NUMBER", env = "ASSUME_ROLE_DURATION_SECONDS")]
+    duration_seconds: Option<i32>,
+
+    #[command(flatten)]
+    cache: cache::Options,
+
+    #[command(flatten)]
+    exec: exec::RunOptions,
+
+    /// A command and its arguments to run as the federated user. Runs current shell if not specified.
+    command: Vec<String>,
+}
+
+/// The parameters that identify a `GetFederationToken` request in the cache.
+#[derive(serde::Serialize)]
+struct Parameters<'a> {
+    access_key_id: Option<&'a str>,
+    name: &'a str,
+    policy_arns: &'a [String],
+    policy: Option<&'a str>,
+    duration_seconds: Option<i32>,
+    tags: &'a [(String, String)],
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, args: Args) -> Result<()> {
+    let args = &args;
+    exec::execute(
+        move || async move {
+            let session = get_federation_token(config, config_file, args).await?;
+            Ok(session.credentials)
+        },
+        &args.command,
+        &args.exec,
+    )
+    .await
+}
+
+async fn get_federation_token(
+    config: &SdkConfig,
+    config_file: &ConfigFile,
+    args: &Args,
+) -> Result<Session> {
+    let tags = parse_tags(&args.session.tags().await?)?;
+    let policy = args.session.policy(config_file).await?;
+
+    let access_key_id = source::access_key_id(config).await?;
+
+    let cache = args.cache.local(config_file)?;
+    let key = cache::key(&Parameters {
+        access_key_id: access_key_id.as_deref(),
+        name: &args.name,
+        policy_arns: &args.session.policy_arn,
+        policy: policy.as_deref(),
+        duration_seconds: args.duration_seconds,
+        tags: &tags,
+    })?;
+
+    let (session, _lock) = args.cache.lookup(&cache, &key).await?;
+    if let Some(session) = session {
+        return Ok(session);
+    }
+
+    // The managed policies must be in the account of the caller.
+    let policy_arns =
+        resolve_policy_arns(config, partition(config), None, &args.session.policy_arn)
+            .await?
+            .into_iter()
+            .map(|arn| PolicyDescriptorType::builder().arn(arn).build())
+            .collect();
+
+    let sts = aws_sdk_sts::Client::new(config);
+    let mut request = sts
+        .get_federation_token()
+        .name(&args.name)
+        .set_policy_arns(Some(policy_arns))
+        .set_policy(policy)
+        .set_duration_seconds(args.duration_seconds);
+
+    for (key, value) in tags {
+        request = request.tags(Tag::builder().key(key).value(value).build()?);
+    }
+
+    let response = request.send().await?;
+    if let Some(user) = response.federated_user() {
+        tracing::debug!(arn = user.arn(), "federated");
+    }
+
+    let session = Session::new(
+        response.credentials,
+        None,
+        response.packed_policy_size,
+        None,
+    )?;
+    session.check_packed_size(args.session.packed_size_warning);
+
+    if !args.cache.no_cache {
+        if let Err(e) = cache.store(&key, None, &session).await {
+            tracing::warn!("failed to cache credentials: {e:#}");
+        }
+    }
+
+    Ok(session)
+}
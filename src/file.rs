@@ -0,0 +1,110 @@
+use anyhow::{Context as _, Result};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt as _;
+
+/// Creates `path` and its parents, making new directories only accessible by the current user.
+pub async fn create_private_dir(path: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder
+        .create(path)
+        .await
+        .with_context(|| format!("failed to create `{}`", path.display()))
+}
+
+/// Warns if `path`, which contains secrets, is accessible by other users.
+pub async fn check_private(path: &Path) {
+    #[cfg(unix)]
+    if let Ok(metadata) = fs::metadata(path).await {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let mode = metadata.permissions().mode();
+        if mode & 0o077 != 0 {
+            tracing::warn!(
+                "`{}` is accessible by other users (mode {:o}); consider `chmod 600`",
+                path.display(),
+                mode & 0o777
+            );
+        }
+    }
+}
+
+/// Atomically replaces the content of `path` with a file only readable by the current user.
+///
+/// The parent directory is created if it does not exist.
+pub async fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_private_dir(parent).await?;
+    }
+
+    let mut temp = path.to_path_buf().into_os_string();
+    temp.push(".assume-role.tmp");
+    let temp = PathBuf::from(temp);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options
+        .open(&temp)
+        .await
+        .with_context(|| format!("failed to open `{}`", temp.display()))?;
+
+    file.write_all(content).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&temp, path)
+        .await
+        .with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+/// An advisory lock on a file, released when dropped.
+pub struct Lock {
+    _file: std::fs::File,
+}
+
+impl Lock {
+    /// Acquires the exclusive lock on `path`, creating the file if it does not exist.
+    pub async fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent).await?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options
+            .open(path)
+            .await
+            .with_context(|| format!("failed to open `{}`", path.display()))?
+            .into_std()
+            .await;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd as _;
+
+            let fd = file.as_raw_fd();
+            if unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+                tracing::info!(
+                    "waiting for another process to release `{}`",
+                    path.display()
+                );
+                let ret =
+                    tokio::task::spawn_blocking(move || unsafe { libc::flock(fd, libc::LOCK_EX) })
+                        .await?;
+                if ret != 0 {
+                    return Err(std::io::Error::last_os_error())
+                        .with_context(|| format!("failed to lock `{}`", path.display()));
+                }
+            }
+        }
+
+        Ok(Self { _file: file })
+    }
+}
@@ -0,0 +1,128 @@
+//! Metadata of the git repository in the current directory, passed as session tags.
+
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// The maximum length of the value of a session tag.
+const MAX_TAG_VALUE_LENGTH: usize = 256;
+
+/// Returns the session tags of the name of the repository, the branch and the short commit SHA, or
+/// nothing if the current directory is not in a repository.
+pub async fn tags() -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+    let Some(toplevel) = git(&["rev-parse", "--show-toplevel"]).await else {
+        tracing::debug!("not in a git repository");
+        return tags;
+    };
+
+    // The name of the remote identifies the repository better than the directory of the clone.
+    let repository = git(&["remote", "get-url", "origin"])
+        .await
+        .and_then(|url| name_of(&url))
+        .or_else(|| name_of(&toplevel));
+    if let Some(repository) = repository {
+        tags.insert("GitRepository".to_string(), tag_value(&repository));
+    }
+
+    match git(&["rev-parse", "--abbrev-ref", "HEAD"]).await {
+        // `HEAD` means detached.
+        Some(branch) if branch != "HEAD" => {
+            tags.insert("GitBranch".to_string(), tag_value(&branch));
+        }
+        _ => {}
+    }
+
+    if let Some(commit) = git(&["rev-parse", "--short", "HEAD"]).await {
+        tags.insert("GitCommit".to_string(), commit);
+    }
+
+    tags
+}
+
+/// Runs `git` with `args` and returns its output, or `None` if it fails.
+async fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// Returns `value` with the characters that STS does not allow in the value of a session tag, such
+/// as `#` or `!` of a branch name, replaced with `_`, and truncated to the maximum length.
+fn tag_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            let allowed = c.is_alphanumeric()
+                || (c.is_whitespace() && !c.is_control())
+                || matches!(c, '_' | '.' | ':' | '/' | '=' | '+' | '-' | '@');
+            if allowed {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_TAG_VALUE_LENGTH)
+        .collect()
+}
+
+/// Returns the last component of the URL or the path of a repository without `.git`.
+fn name_of(location: &str) -> Option<String> {
+    let name = location
+        .trim_end_matches('/')
+        .rsplit(['/', '\\', ':'])
+        .next()?;
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    Some(name.to_string()).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            name_of("https://github.com/dacci/assume-role.git").as_deref(),
+            Some("assume-role")
+        );
+        assert_eq!(
+            name_of("git@github.com:dacci/assume-role.git").as_deref(),
+            Some("assume-role")
+        );
+        assert_eq!(name_of("git@host:repo").as_deref(), Some("repo"));
+        assert_eq!(
+            name_of("ssh://git@host:2222/team/repo/").as_deref(),
+            Some("repo")
+        );
+        assert_eq!(name_of("/home/me/src/repo").as_deref(), Some("repo"));
+        assert_eq!(name_of(r"C:\src\repo").as_deref(), Some("repo"));
+        assert_eq!(name_of("repo.git.git").as_deref(), Some("repo.git"));
+        assert_eq!(name_of("/"), None);
+        assert_eq!(name_of(""), None);
+        assert_eq!(name_of("https://host/.git"), None);
+    }
+
+    #[test]
+    fn tag_values() {
+        assert_eq!(tag_value("feature/ABC-123_x.y"), "feature/ABC-123_x.y");
+        assert_eq!(tag_value("user@host:a=b+c"), "user@host:a=b+c");
+        assert_eq!(tag_value("fix#42!"), "fix_42_");
+        assert_eq!(tag_value("日本語 ブランチ"), "日本語 ブランチ");
+        assert_eq!(tag_value("a\tb,c;d'e\"f"), "a_b_c_d_e_f");
+        assert_eq!(tag_value(&"x".repeat(300)).len(), MAX_TAG_VALUE_LENGTH);
+        assert_eq!(
+            tag_value(&"é".repeat(300)).chars().count(),
+            MAX_TAG_VALUE_LENGTH
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use aws_config::SdkConfig;
+use aws_sdk_sts::config::ProvideCredentials as _;
+use aws_sigv4::http_request::{
+    PayloadChecksumKind, PercentEncodingMode, SignableBody, SignableRequest, SigningSettings,
+};
+use aws_sigv4::sign::v4;
+use hyper::body::Bytes;
+use hyper::client::HttpConnector;
+use hyper::{header, Body, Client, Method, Request, StatusCode, Uri};
+use hyper_rustls::HttpsConnector;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+fn client() -> Client<HttpsConnector<HttpConnector>> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Client::builder().build(connector)
+}
+
+/// Sends `request` and returns the body of the successful response.
+pub async fn send(request: Request<Body>) -> Result<Bytes> {
+    let uri = request.uri().clone();
+    let response = client()
+        .request(request)
+        .await
+        .with_context(|| format!("failed to request `{uri}`"))?;
+
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .with_context(|| format!("failed to read the response from `{uri}`"))?;
+    if !status.is_success() {
+        bail!(
+            "`{uri}` responded with {status}: {}",
+            String::from_utf8_lossy(&body).trim()
+        );
+    }
+
+    Ok(body)
+}
+
+/// Posts `body` as JSON to `uri` and parses the JSON response.
+pub async fn post_json<T: DeserializeOwned>(uri: &str, body: &impl Serialize) -> Result<T> {
+    let request = Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::ACCEPT, "application/json")
+        .body(Body::from(serde_json::to_vec(body)?))?;
+
+    let body = send(request).await?;
+    serde_json::from_slice(&body).with_context(|| format!("malformed response from `{uri}`"))
+}
+
+/// Calls `target` of an AWS service that speaks the JSON 1.1 protocol, signing the request with
+/// the credentials of `config`.
+pub async fn aws_json<T: DeserializeOwned>(
+    config: &SdkConfig,
+    endpoint: &str,
+    signing_region: &str,
+    service: &str,
+    target: &str,
+    input: &impl Serialize,
+) -> Result<T> {
+    let body = serde_json::to_vec(input)?;
+    let mut request = Request::post(endpoint)
+        .header(header::CONTENT_TYPE, "application/x-amz-json-1.1")
+        .header("X-Amz-Target", target)
+        .body(Body::empty())?;
+    sign(
+        config,
+        &mut request,
+        &body,
+        signing_region,
+        service,
+        SigningSettings::default(),
+    )
+    .await?;
+    *request.body_mut() = body.into();
+
+    let body = send(request).await?;
+    serde_json::from_slice(&body).with_context(|| format!("malformed {target} response"))
+}
+
+/// Gets the object at `key` of the S3 `bucket` with the credentials of `config`.
+pub async fn s3_get(config: &SdkConfig, bucket: &str, key: &str) -> Result<Bytes> {
+    let region = config
+        .region()
+        .map(|r| r.to_string())
+        .unwrap_or_else(|| "us-east-1".to_string());
+    let uri = format!(
+        "https://{bucket}.s3.{region}.amazonaws.com/{}",
+        urlencoding::encode(key).replace("%2F", "/")
+    );
+    let mut request = Request::get(uri).body(Body::empty())?;
+
+    // S3 requires the hash of the payload in a header.
+    let mut settings = SigningSettings::default();
+    settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+    settings.percent_encoding_mode = PercentEncodingMode::Single;
+    sign(config, &mut request, &[], &region, "s3", settings).await?;
+
+    send(request).await
+}
+
+/// Signs `request` with `body` by Signature Version 4 with the credentials of `config`.
+async fn sign(
+    config: &SdkConfig,
+    request: &mut Request<Body>,
+    body: &[u8],
+    region: &str,
+    service: &str,
+    settings: SigningSettings,
+) -> Result<()> {
+    let credentials = config
+        .credentials_provider()
+        .ok_or_else(|| anyhow!("no credentials are available"))?
+        .provide_credentials()
+        .await?;
+    let identity = credentials.into();
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region)
+        .name(service)
+        .time(SystemTime::now())
+        .settings(settings)
+        .build()?
+        .into();
+
+    let uri = request.uri().to_string();
+    let headers = request
+        .headers()
+        .iter()
+        .map(|(k, v)| Ok((k.as_str(), v.to_str()?)))
+        .collect::<Result<Vec<_>>>()?;
+    let signable = SignableRequest::new(
+        request.method().as_str(),
+        &uri,
+        headers.into_iter(),
+        SignableBody::Bytes(body),
+    )?;
+    let (instructions, _) = aws_sigv4::http_request::sign(signable, &params)?.into_parts();
+    instructions.apply_to_request_http0x(request);
+
+    Ok(())
+}
+
+/// A client that keeps cookies and follows redirects like a browser, for sign-in flows of IdPs
+/// that have no API.
+#[derive(Default)]
+pub struct Agent {
+    cookies: HashMap<(String, String), String>,
+}
+
+impl Agent {
+    /// Requests `uri` and returns the body of the final response after redirects.
+    pub async fn get(&mut self, uri: &str) -> Result<String> {
+        self.request(Method::GET, uri, None, Body::empty()).await
+    }
+
+    /// Posts `form` URL-encoded to `uri` and returns the body of the final response after redirects.
+    pub async fn post_form(&mut self, uri: &str, form: &[(&str, &str)]) -> Result<String> {
+        let body = form
+            .iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        self.request(
+            Method::POST,
+            uri,
+            Some("application/x-www-form-urlencoded"),
+            body.into(),
+        )
+        .await
+    }
+
+    /// Posts `body` as JSON to `uri` and parses the JSON response.
+    pub async fn post_json<T: DeserializeOwned>(
+        &mut self,
+        uri: &str,
+        body: &impl Serialize,
+    ) -> Result<T> {
+        let body = self
+            .request(
+                Method::POST,
+                uri,
+                Some("application/json"),
+                serde_json::to_vec(body)?.into(),
+            )
+            .await?;
+        serde_json::from_str(&body).with_context(|| format!("malformed response from `{uri}`"))
+    }
+
+    async fn request(
+        &mut self,
+        mut method: Method,
+        uri: &str,
+        mut content_type: Option<&str>,
+        mut body: Body,
+    ) -> Result<String> {
+        let client = client();
+        let mut uri: Uri = uri
+            .parse()
+            .with_context(|| format!("malformed URL `{uri}`"))?;
+
+        for _ in 0..MAX_REDIRECTS {
+            let host = uri.host().unwrap_or_default().to_string();
+            let mut builder = Request::builder().method(&method).uri(&uri);
+            if let Some(content_type) = content_type {
+                builder = builder.header(header::CONTENT_TYPE, content_type);
+            }
+            let cookie = self
+                .cookies
+                .iter()
+                .filter(|((h, _), _)| *h == host)
+                .map(|((_, name), value)| format!("{name}={value}"))
+                .collect::<Vec<_>>()
+                .join("; ");
+            if !cookie.is_empty() {
+                builder = builder.header(header::COOKIE, cookie);
+            }
+
+            let response = client
+                .request(builder.body(std::mem::take(&mut body))?)
+                .await
+                .with_context(|| format!("failed to request `{uri}`"))?;
+
+            for value in response.headers().get_all(header::SET_COOKIE) {
+                let Some((name, value)) = value
+                    .to_str()
+                    .ok()
+                    .and_then(|v| v.split(';').next())
+                    .and_then(|v| v.split_once('='))
+                else {
+                    continue;
+                };
+                self.cookies
+                    .insert((host.clone(), name.trim().to_string()), value.to_string());
+            }
+
+            let status = response.status();
+            if status.is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .with_context(|| format!("`{uri}` redirected without location"))?;
+                uri = resolve(&uri, location)?;
+                if status != StatusCode::TEMPORARY_REDIRECT
+                    && status != StatusCode::PERMANENT_REDIRECT
+                {
+                    method = Method::GET;
+                    content_type = None;
+                }
+                continue;
+            }
+
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .with_context(|| format!("failed to read the response from `{uri}`"))?;
+            let body = String::from_utf8_lossy(&body).into_owned();
+            if !status.is_success() {
+                bail!("`{uri}` responded with {status}: {}", body.trim());
+            }
+
+            return Ok(body);
+        }
+
+        bail!("too many redirects from `{uri}`")
+    }
+}
+
+const MAX_REDIRECTS: usize = 10;
+
+/// Resolves `location` relative to `base`.
+pub fn resolve(base: &Uri, location: &str) -> Result<Uri> {
+    let uri = if location.starts_with("//") {
+        format!("{}:{location}", base.scheme_str().unwrap_or("https"))
+    } else if location.starts_with('/') {
+        format!(
+            "{}://{}{location}",
+            base.scheme_str().unwrap_or("https"),
+            base.authority().map(|a| a.as_str()).unwrap_or_default()
+        )
+    } else {
+        location.to_string()
+    };
+    uri.parse()
+        .with_context(|| format!("malformed URL `{location}`"))
+}
@@ -0,0 +1,236 @@
+//! Access to the secret store of the operating system through its command line tools, or the
+//! Credential Manager on Windows.
+
+use anyhow::Result;
+#[cfg(not(windows))]
+use {
+    anyhow::{anyhow, bail, Context as _},
+    std::process::Stdio,
+    tokio::io::AsyncWriteExt as _,
+    tokio::process::Command,
+};
+
+const SERVICE: &str = "assume-role";
+
+/// Returns the secret stored for `account`.
+pub async fn get(account: &str) -> Result<Option<Vec<u8>>> {
+    #[cfg(windows)]
+    return credential_manager::read(account);
+
+    #[cfg(not(windows))]
+    {
+        let mut cmd = if cfg!(target_os = "macos") {
+            let mut cmd = Command::new("security");
+            cmd.args(["find-generic-password", "-s", SERVICE, "-a", account, "-w"]);
+            cmd
+        } else if cfg!(unix) {
+            let mut cmd = Command::new("secret-tool");
+            cmd.args(["lookup", "service", SERVICE, "account", account]);
+            cmd
+        } else {
+            bail!("keyring is not supported on this platform");
+        };
+
+        let output = cmd
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .await
+            .with_context(|| format!("failed to run `{:?}`", cmd.as_std().get_program()))?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+
+        let mut secret = output.stdout;
+        if secret.last() == Some(&b'\n') {
+            secret.pop();
+        }
+        Ok(Some(secret))
+    }
+}
+
+/// Stores `secret` for `account`, replacing the existing one.
+pub async fn set(account: &str, secret: &[u8]) -> Result<()> {
+    #[cfg(windows)]
+    return credential_manager::write(account, secret);
+
+    // Secrets are passed through the standard input so that they never appear in the
+    // arguments of a process.
+    #[cfg(not(windows))]
+    {
+        let (mut cmd, input) = if cfg!(target_os = "macos") {
+            let mut cmd = Command::new("security");
+            cmd.arg("-i");
+            let input = format!(
+                "add-generic-password -U -s {SERVICE} -a {account} -X {}\n",
+                hex::encode(secret)
+            );
+            (cmd, input.into_bytes())
+        } else if cfg!(unix) {
+            let mut cmd = Command::new("secret-tool");
+            cmd.args([
+                "store",
+                &format!("--label={SERVICE} {account}"),
+                "service",
+                SERVICE,
+                "account",
+                account,
+            ]);
+            (cmd, secret.to_vec())
+        } else {
+            bail!("keyring is not supported on this platform");
+        };
+
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run `{:?}`", cmd.as_std().get_program()))?;
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(&input).await?;
+        drop(stdin);
+
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(anyhow!(
+                "failed to store the secret to the keyring: {status}"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Deletes the secret stored for `account`. A missing secret is not an error.
+pub async fn delete(account: &str) -> Result<()> {
+    #[cfg(windows)]
+    return credential_manager::delete(account);
+
+    #[cfg(not(windows))]
+    {
+        let mut cmd = if cfg!(target_os = "macos") {
+            let mut cmd = Command::new("security");
+            cmd.args(["delete-generic-password", "-s", SERVICE, "-a", account]);
+            cmd
+        } else if cfg!(unix) {
+            let mut cmd = Command::new("secret-tool");
+            cmd.args(["clear", "service", SERVICE, "account", account]);
+            cmd
+        } else {
+            bail!("keyring is not supported on this platform");
+        };
+
+        // Both tools fail only if the secret does not exist once they run.
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .with_context(|| format!("failed to run `{:?}`", cmd.as_std().get_program()))?;
+        Ok(())
+    }
+}
+
+/// Generic credentials of the Windows Credential Manager, named `assume-role/<account>`.
+#[cfg(windows)]
+mod credential_manager {
+    use anyhow::{bail, Result};
+    use std::ffi::c_void;
+    use std::io;
+
+    const CRED_TYPE_GENERIC: u32 = 1;
+    const CRED_PERSIST_LOCAL_MACHINE: u32 = 2;
+    const ERROR_NOT_FOUND: i32 = 1168;
+
+    #[repr(C)]
+    struct Credential {
+        flags: u32,
+        kind: u32,
+        target_name: *mut u16,
+        comment: *mut u16,
+        last_written: [u32; 2],
+        blob_size: u32,
+        blob: *mut u8,
+        persist: u32,
+        attribute_count: u32,
+        attributes: *mut c_void,
+        target_alias: *mut u16,
+        user_name: *mut u16,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn CredReadW(
+            target_name: *const u16,
+            kind: u32,
+            flags: u32,
+            credential: *mut *mut Credential,
+        ) -> i32;
+        fn CredWriteW(credential: *const Credential, flags: u32) -> i32;
+        fn CredDeleteW(target_name: *const u16, kind: u32, flags: u32) -> i32;
+        fn CredFree(buffer: *mut c_void);
+    }
+
+    fn target_name(account: &str) -> Vec<u16> {
+        format!("{}/{account}", super::SERVICE)
+            .encode_utf16()
+            .chain([0])
+            .collect()
+    }
+
+    pub fn read(account: &str) -> Result<Option<Vec<u8>>> {
+        let target_name = target_name(account);
+        let mut credential = std::ptr::null_mut();
+        unsafe {
+            if CredReadW(target_name.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() == Some(ERROR_NOT_FOUND) {
+                    return Ok(None);
+                }
+                bail!("failed to read the secret from the Credential Manager: {e}");
+            }
+            let secret =
+                std::slice::from_raw_parts((*credential).blob, (*credential).blob_size as usize)
+                    .to_vec();
+            CredFree(credential.cast());
+            Ok(Some(secret))
+        }
+    }
+
+    pub fn write(account: &str, secret: &[u8]) -> Result<()> {
+        let mut target_name = target_name(account);
+        let mut secret = secret.to_vec();
+        let credential = Credential {
+            flags: 0,
+            kind: CRED_TYPE_GENERIC,
+            target_name: target_name.as_mut_ptr(),
+            comment: std::ptr::null_mut(),
+            last_written: [0; 2],
+            blob_size: secret.len() as u32,
+            blob: secret.as_mut_ptr(),
+            persist: CRED_PERSIST_LOCAL_MACHINE,
+            attribute_count: 0,
+            attributes: std::ptr::null_mut(),
+            target_alias: std::ptr::null_mut(),
+            user_name: std::ptr::null_mut(),
+        };
+        if unsafe { CredWriteW(&credential, 0) } == 0 {
+            bail!(
+                "failed to store the secret to the Credential Manager: {}",
+                io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn delete(account: &str) -> Result<()> {
+        let target_name = target_name(account);
+        if unsafe { CredDeleteW(target_name.as_ptr(), CRED_TYPE_GENERIC, 0) } == 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(ERROR_NOT_FOUND) {
+                bail!("failed to delete the secret from the Credential Manager: {e}");
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,64 +1,128 @@
-use anyhow::{anyhow, Context as _, Result};
-use aws_sdk_sts::types::{PolicyDescriptorType, Tag};
-use chrono::Utc;
+mod approval;
+mod assume;
+mod attach;
+mod browser;
+mod cache;
+mod clipboard;
+mod cognito;
+mod config;
+mod console;
+mod credential_process;
+mod crypt;
+mod daemon;
+mod dirs;
+mod duration;
+mod entra;
+mod exec;
+mod export;
+mod federate;
+mod file;
+mod git;
+mod http;
+mod keyring;
+mod mfa;
+mod oidc;
+mod okta;
+mod organizations;
+#[cfg(unix)]
+mod pipe;
+mod preflight;
+mod profile;
+mod prompt;
+#[cfg(unix)]
+mod pty;
+mod qr;
+mod registry;
+mod revoke;
+mod roles;
+mod saml;
+mod serve;
+mod service;
+mod session;
+mod source;
+mod toml;
+mod validate;
+mod whoami;
+
+use anyhow::Result;
 use clap::Parser;
-use tokio::fs::File;
-use tokio::process::Command;
+use config::ConfigFile;
 
 #[derive(clap::Parser)]
-struct Args {
-    /// The name or the Amazon Resource Name (ARN) of the role to assume.
-    #[arg(short, long, value_name = "NAME")]
-    role: String,
+struct Cli {
+    #[command(flatten)]
+    source: source::SourceArgs,
 
-    /// An identifier for the assumed role session.
-    #[arg(long, value_name = "NAME")]
-    role_session_name: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Run a command with the credentials of a running `serve` or `daemon`.
+    Attach(attach::Args),
+
+    /// Manage the cached sessions.
+    Cache(cache::Args),
+
+    /// Run a command with the credentials of an identity in an Amazon Cognito identity pool.
+    Cognito(cognito::Args),
+
+    /// Manage the presets in the config file.
+    Config(config::Args),
+
+    /// Print a URL that signs in to the AWS Management Console as the assumed role.
+    Console(console::Args),
 
-    /// The Amazon Resource Names (ARNs) of the IAM managed policy that you want to use as managed session policies.
-    #[arg(long, value_name = "ARN")]
-    policy_arn: Vec<String>,
+    /// Print the credentials of the assumed role in the format expected by `credential_process`.
+    CredentialProcess(credential_process::Args),
 
-    /// An IAM policy in JSON or YAML that you want to use as an inline session policy.
-    #[arg(short, long, value_name = "PATH")]
-    policy: Option<String>,
+    /// Keep the credentials of the assumed role fresh in a profile of the shared credentials file.
+    Daemon(daemon::Args),
 
-    /// The duration, in seconds, of the role session.
-    #[arg(long, value_name = "NUMBER")]
-    duration_seconds: Option<i32>,
+    /// Run a command as the assumed role.
+    Exec(exec::Args),
 
-    /// A session tag that you want to pass.
-    #[arg(long, value_name = "KEY=VALUE")]
-    tag: Vec<String>,
+    /// Print shell commands that export the credentials of the assumed role.
+    Export(export::Args),
 
-    /// A key for session tags that you want to set as transitive.
-    #[arg(long, value_name = "KEY")]
-    transitive_tag_key: Vec<String>,
+    /// Run a command as a federated user with scoped-down permissions.
+    Federate(federate::Args),
 
-    /// A unique identifier that might be required when you assume a role in another account.
-    #[arg(long)]
-    external_id: Option<String>,
+    /// List the roles that the current credentials can assume.
+    ListRoles(roles::Args),
 
-    /// The identification number of the MFA device that is associated with the user who is making the `AssumeRole` call.
-    #[arg(long)]
-    serial_number: Option<String>,
+    /// Deny the sessions of a role issued before now, such as leaked credentials.
+    Revoke(revoke::Args),
 
-    /// The value provided by the MFA device, if the trust policy of the role being assumed requires MFA.
-    #[arg(long)]
-    token_code: Option<String>,
+    /// Run a command with a local instance metadata service that renews the credentials of the
+    /// assumed role.
+    Serve(serve::Args),
 
-    /// The source identity specified by the principal that is calling the `AssumeRole` operation.
-    #[arg(long)]
-    source_identity: Option<String>,
+    /// Run a command with MFA-authenticated credentials of the current user.
+    Session(session::Args),
 
-    /// A command and its arguments to run as the assumed role. Runs current shell if not specified.
-    command: Vec<String>,
+    /// Show the identity of the current credentials.
+    Whoami(whoami::Args),
+
+    /// `assume-role PRESET [OPTIONS] [-- COMMAND]...` is the same as `exec -r PRESET`, and
+    /// `assume-role -- COMMAND...` is the same as `exec -- COMMAND...`.
+    #[command(external_subcommand)]
+    Preset(Vec<String>),
+}
+
+/// The arguments following the name of a preset.
+#[derive(clap::Parser)]
+struct PresetCli {
+    #[command(flatten)]
+    exec: exec::Args,
 }
 
 fn main() -> Result<()> {
     use tracing_subscriber::prelude::*;
 
-    let args: Args = Args::parse();
+    let cli = Cli::parse();
 
     tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer())
@@ -69,93 +133,86 @@ fn main() -> Result<()> {
         .enable_all()
         .build()
         .unwrap()
-        .block_on(async_main(args))
+        .block_on(async_main(cli))
 }
 
-async fn async_main(args: Args) -> Result<()> {
-    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-    let sts = aws_sdk_sts::Client::new(&config);
-
-    let role_arn = if args.role.starts_with("arn:") {
-        args.role
-    } else {
-        let iam = aws_sdk_iam::Client::new(&config);
-        let response = iam.get_role().role_name(args.role).send().await?;
-        response
-            .role()
-            .ok_or_else(|| anyhow!("role is not provided"))
-            .map(|r| r.arn())?
-            .to_string()
-    };
-
-    let mut request = sts
-        .assume_role()
-        .role_arn(role_arn)
-        .role_session_name(
-            args.role_session_name
-                .unwrap_or_else(|| format!("assume-role@{}", Utc::now().timestamp())),
-        )
-        .set_policy_arns(Some(
-            args.policy_arn
-                .iter()
-                .map(|s| PolicyDescriptorType::builder().arn(s).build())
-                .collect(),
-        ))
-        .set_duration_seconds(args.duration_seconds)
-        .set_transitive_tag_keys(Some(args.transitive_tag_key))
-        .set_external_id(args.external_id)
-        .set_serial_number(args.serial_number)
-        .set_token_code(args.token_code)
-        .set_source_identity(args.source_identity);
-
-    for tag in &args.tag {
-        if let Some((key, value)) = tag.split_once('=') {
-            request = request.tags(Tag::builder().key(key).value(value).build()?);
-        } else {
-            return Err(anyhow!("illegal tag: `{tag}`"));
-        }
-    }
-
-    if let Some(path) = &args.policy {
-        let f = File::open(path)
-            .await
-            .with_context(|| format!("failed to open `{path}`"))?
-            .into_std()
-            .await;
-        let value: serde_yaml::Value =
-            serde_yaml::from_reader(f).with_context(|| format!("failed to read `{path}`"))?;
-
-        let policy = serde_json::to_string(&value).context("malformed policy")?;
-        request = request.policy(policy);
-    }
-
-    let response = request.send().await?;
-
-    let Some(credentials) = response.credentials() else {
-        return Err(anyhow!("no credentials provided"));
+async fn async_main(cli: Cli) -> Result<()> {
+    // These subcommands do not need the config file, so that `config` can fix a broken one.
+    let command = match cli.command {
+        Command::Attach(args) => return attach::run(args).await,
+        Command::Config(args) => return config::run(args).await,
+        Command::Serve(args) if args.is_control() => return serve::control(args).await,
+        command => command,
     };
 
-    println!(
-        "Credentials will expire at {}",
-        credentials
-            .expiration
-            .fmt(aws_smithy_types::date_time::Format::DateTime)?
-    );
-
-    let mut cmd = if args.command.is_empty() {
-        Command::new(std::env::var("SHELL").context("failed to get environment variable `SHELL`")?)
-    } else {
-        let mut iter = args.command.iter();
-        let mut cmd = Command::new(iter.next().unwrap());
-        cmd.args(iter);
-        cmd
+    let mut config_file = ConfigFile::load().await?;
+    prompt::set_backend(config_file.prompt.backend);
+    // The cache is managed without the source credentials.
+    let command = match command {
+        Command::Cache(args) => return cache::run(&config_file, args).await,
+        command => command,
     };
 
-    cmd.env("AWS_ACCESS_KEY_ID", credentials.access_key_id())
-        .env("AWS_SECRET_ACCESS_KEY", credentials.secret_access_key())
-        .env("AWS_SESSION_TOKEN", credentials.session_token());
+    let config = cli.source.load().await?;
+    config_file.fetch_includes(&config, offline()).await;
+    config_file.check_groups()?;
+    if let Some(serial_number) = cli.source.mfa_serial().await? {
+        config_file.mfa.serial_number = Some(serial_number);
+    }
+    if let Some((name, preset)) = cli.source.preset().await? {
+        config_file.presets.insert(name.clone(), preset);
+        config_file.role = Some(name);
+    }
 
-    cmd.spawn()?.wait().await?;
+    match command {
+        Command::Attach(_) | Command::Cache(_) | Command::Config(_) => unreachable!(),
+        Command::Cognito(args) => cognito::run(args).await,
+        Command::Console(args) => console::run(&config, &config_file, args).await,
+        Command::CredentialProcess(args) => {
+            credential_process::run(&config, &config_file, args).await
+        }
+        Command::Daemon(args) => daemon::run(&config, &config_file, args).await,
+        Command::Exec(args) => exec::run(&config, &config_file, args).await,
+        Command::Export(args) => export::run(&config, &config_file, args).await,
+        Command::Federate(args) => federate::run(&config, &config_file, args).await,
+        Command::ListRoles(args) => roles::run(&config, &config_file, args).await,
+        Command::Revoke(args) => revoke::run(&config, args).await,
+        Command::Serve(args) => serve::run(&config, &config_file, args).await,
+        Command::Session(args) => session::run(&config, &config_file, args).await,
+        Command::Whoami(args) => whoami::run(&config, args).await,
+        Command::Preset(args) => {
+            let name = &args[0];
+            let argv = if std::env::args().skip_while(|a| a != "--").nth(1).as_ref() == Some(name) {
+                // `assume-role -- COMMAND`, whose `--` is dropped by clap, runs the command as
+                // the default role.
+                ["assume-role".to_string(), "--".to_string()]
+                    .into_iter()
+                    .chain(args.iter().cloned())
+                    .collect::<Vec<_>>()
+            } else if config_file.presets.contains_key(name) {
+                [format!("assume-role {name}"), "--role".into(), name.clone()]
+                    .into_iter()
+                    .chain(args[1..].iter().cloned())
+                    .collect()
+            } else {
+                anyhow::bail!("no subcommand or preset named `{name}`");
+            };
+
+            let args = PresetCli::parse_from(argv).exec;
+            exec::run(&config, &config_file, args).await
+        }
+    }
+}
 
-    Ok(())
+/// Returns whether `--offline` is specified, before the subcommand that has it is known.
+fn offline() -> bool {
+    std::env::args()
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == "--offline")
+        || std::env::var("ASSUME_ROLE_OFFLINE").is_ok_and(|value| {
+            !matches!(
+                value.to_lowercase().as_str(),
+                "" | "0" | "n" | "no" | "f" | "false" | "off"
+            )
+        })
 }
@@ -0,0 +1,244 @@
+use crate::config::{ConfigFile, MfaConfig};
+use crate::prompt;
+use anyhow::{anyhow, Context as _, Result};
+use aws_config::SdkConfig;
+use aws_sdk_sts::error::ProvideErrorMetadata;
+use std::process::Stdio;
+use std::str::FromStr;
+use tokio::process::Command;
+
+/// The value of `--serial-number` that requests detection of the MFA device.
+pub const AUTO: &str = "auto";
+
+#[derive(Clone, clap::Args)]
+pub struct MfaArgs {
+    /// The identification number of the MFA device that is associated with the user who is making the call.
+    /// Specify `auto` to detect the device of the caller.
+    #[arg(long, visible_alias = "mfa", env = "ASSUME_ROLE_SERIAL_NUMBER")]
+    pub serial_number: Option<String>,
+
+    /// The value provided by the MFA device, if MFA is required.
+    /// Specify `-` to read it from the standard input.
+    #[arg(long, env = "ASSUME_ROLE_TOKEN_CODE", hide_env_values = true)]
+    token_code: Option<String>,
+
+    /// Where to obtain the MFA code from if `--token-code` is not specified: `prompt` or `yubikey[:ACCOUNT]`.
+    #[arg(long, value_name = "SOURCE", env = "ASSUME_ROLE_TOKEN_SOURCE")]
+    token_source: Option<TokenSource>,
+
+    /// A shell command whose output is used as the MFA code if `--token-code` is not specified.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        conflicts_with = "token_source",
+        env = "ASSUME_ROLE_TOKEN_COMMAND"
+    )]
+    token_command: Option<String>,
+
+    /// The number of times to obtain the MFA code when it is rejected.
+    #[arg(
+        long,
+        value_name = "NUMBER",
+        default_value_t = 3,
+        env = "ASSUME_ROLE_MFA_ATTEMPTS"
+    )]
+    mfa_attempts: u32,
+}
+
+impl MfaArgs {
+    /// Returns whether the MFA code is read from the standard input.
+    pub fn reads_stdin(&self) -> bool {
+        self.token_code.as_deref() == Some("-")
+    }
+
+    /// Applies the defaults of `config` to the options not specified on the command line.
+    pub fn apply_defaults(&mut self, config: &MfaConfig) {
+        if self.serial_number.is_none() {
+            self.serial_number = config.serial_number.clone();
+        }
+    }
+
+    /// Determines the MFA device and obtains the first code.
+    pub async fn resolve(self, config: &SdkConfig, config_file: &ConfigFile) -> Result<Mfa> {
+        let serial_number = match self.serial_number {
+            Some(s) if s == AUTO => Some(detect_serial_number(config).await?),
+            serial_number => serial_number,
+        };
+
+        // The source is kept only when a fresh code can be obtained again on failure.
+        let (token_code, source) = match (&serial_number, self.token_code) {
+            (_, Some(code)) if code == "-" => {
+                let line = tokio::task::spawn_blocking(|| {
+                    let mut line = String::new();
+                    std::io::stdin().read_line(&mut line).map(|_| line)
+                })
+                .await?
+                .context("failed to read the MFA code")?;
+                (Some(line.trim().to_string()), None)
+            }
+            (Some(serial_number), None) => {
+                let source = match (self.token_source, self.token_command) {
+                    (Some(source), _) => source,
+                    (None, Some(command)) => TokenSource::Command(command),
+                    (None, None) => config_file.mfa.token_source(),
+                };
+                (Some(source.token_code(serial_number).await?), Some(source))
+            }
+            (_, token_code) => (token_code, None),
+        };
+
+        Ok(Mfa {
+            serial_number,
+            token_code,
+            source,
+            attempts: 1,
+            max_attempts: self.mfa_attempts,
+        })
+    }
+}
+
+/// The MFA device and code to authenticate a request with.
+#[derive(Default)]
+pub struct Mfa {
+    pub serial_number: Option<String>,
+    pub token_code: Option<String>,
+    source: Option<TokenSource>,
+    attempts: u32,
+    max_attempts: u32,
+}
+
+impl Mfa {
+    /// Obtains a fresh code if `error` reports that the code is rejected and another attempt is
+    /// allowed. Returns whether the request should be retried.
+    pub async fn retry(&mut self, error: &impl ProvideErrorMetadata) -> Result<bool> {
+        let (Some(source), Some(serial_number)) = (&self.source, &self.serial_number) else {
+            return Ok(false);
+        };
+        if self.attempts >= self.max_attempts || !is_invalid_code(error) {
+            return Ok(false);
+        }
+
+        eprintln!("The MFA code was rejected, please try again");
+        self.attempts += 1;
+        self.token_code = Some(source.token_code(serial_number).await?);
+        Ok(true)
+    }
+}
+
+/// Detects the serial number of the MFA device of the caller.
+///
+/// The devices are listed with `iam:ListMFADevices`. If it is not allowed, the ARN of the
+/// virtual MFA device named after the user is derived from `sts:GetCallerIdentity`.
+pub async fn detect_serial_number(config: &SdkConfig) -> Result<String> {
+    let iam = aws_sdk_iam::Client::new(config);
+    match iam.list_mfa_devices().send().await {
+        Ok(response) => {
+            if let Some(device) = response.mfa_devices().first() {
+                tracing::debug!(
+                    serial_number = device.serial_number(),
+                    "detected MFA device"
+                );
+                return Ok(device.serial_number().to_string());
+            }
+            return Err(anyhow!("no MFA device is associated with the caller"));
+        }
+        Err(e) => tracing::debug!("failed to list MFA devices: {e}"),
+    }
+
+    let sts = aws_sdk_sts::Client::new(config);
+    let response = sts.get_caller_identity().send().await?;
+    let arn = response
+        .arn()
+        .ok_or_else(|| anyhow!("caller ARN is not provided"))?;
+
+    // arn:aws:iam::123456789012:user/path/name -> arn:aws:iam::123456789012:mfa/name
+    let (prefix, resource) = arn
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("malformed caller ARN: `{arn}`"))?;
+    let name = resource
+        .strip_prefix("user/")
+        .and_then(|s| s.rsplit('/').next())
+        .ok_or_else(|| anyhow!("cannot derive the MFA device of `{arn}`"))?;
+    let serial_number = format!("{prefix}:mfa/{name}");
+    tracing::debug!(serial_number, "derived MFA device");
+
+    Ok(serial_number)
+}
+
+/// Returns whether `error` reports that the MFA code is invalid or expired.
+fn is_invalid_code(error: &impl ProvideErrorMetadata) -> bool {
+    error
+        .message()
+        .is_some_and(|m| m.contains("MultiFactorAuthentication failed"))
+}
+
+/// Where to obtain MFA codes from.
+#[derive(Clone, Debug, Default)]
+pub enum TokenSource {
+    /// Ask the user on the terminal.
+    #[default]
+    Prompt,
+
+    /// Generate with the OATH application of a YubiKey through `ykman`. The account defaults to
+    /// the serial number of the MFA device.
+    Yubikey(Option<String>),
+
+    /// Use the output of a shell command.
+    Command(String),
+}
+
+impl FromStr for TokenSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "prompt" => Ok(Self::Prompt),
+            None if s == "yubikey" => Ok(Self::Yubikey(None)),
+            Some(("yubikey", account)) => Ok(Self::Yubikey(Some(account.to_string()))),
+            _ => Err(format!("unknown token source: `{s}`")),
+        }
+    }
+}
+
+impl TokenSource {
+    /// Obtains a code of the MFA device `serial_number`.
+    pub async fn token_code(&self, serial_number: &str) -> Result<String> {
+        match self {
+            Self::Prompt => prompt::secret(&format!("Enter MFA code for {serial_number}: ")).await,
+            Self::Yubikey(account) => {
+                let account = account.as_deref().unwrap_or(serial_number);
+                // ykman asks the user to touch the key on its own if the account requires it.
+                let mut cmd = Command::new("ykman");
+                cmd.args(["oath", "accounts", "code", "--single", account]);
+                run(cmd, "ykman").await
+            }
+            Self::Command(command) => {
+                let cmd = if cfg!(windows) {
+                    let mut cmd = Command::new("cmd");
+                    cmd.arg("/C").arg(command);
+                    cmd
+                } else {
+                    let mut cmd = Command::new("sh");
+                    cmd.arg("-c").arg(command);
+                    cmd
+                };
+                run(cmd, command).await
+            }
+        }
+    }
+}
+
+/// Runs `cmd`, which may interact with the user, and returns its trimmed output.
+async fn run(mut cmd: Command, name: &str) -> Result<String> {
+    let output = cmd
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .with_context(|| format!("failed to run `{name}`"))?;
+    if !output.status.success() {
+        return Err(anyhow!("`{name}` failed: {}", output.status));
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
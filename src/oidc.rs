@@ -0,0 +1,90 @@
+use crate::http;
+use anyhow::{Context as _, Result};
+use hyper::{header, Body, Request};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The audience that AWS STS expects by default.
+pub const DEFAULT_AUDIENCE: &str = "sts.amazonaws.com";
+
+/// Where to obtain the OIDC token of the workload from.
+#[derive(Clone, Debug)]
+pub enum TokenSource {
+    /// Request from GitHub Actions.
+    Github,
+
+    /// Read from an environment variable, such as `CIRCLE_OIDC_TOKEN_V2` of CircleCI or the one
+    /// declared in `id_tokens` of GitLab CI/CD.
+    Env(String),
+
+    /// Read from a file.
+    File(PathBuf),
+}
+
+impl TokenSource {
+    /// Obtains the token, requesting it for `audience` if the source issues tokens on demand.
+    pub async fn token(&self, audience: &str) -> Result<String> {
+        let token = match self {
+            Self::Github => return github_token(audience).await,
+            Self::Env(name) => std::env::var(name)
+                .with_context(|| format!("failed to get environment variable `{name}`"))?,
+            Self::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("failed to read `{}`", path.display()))?,
+        };
+
+        Ok(token.trim().to_string())
+    }
+}
+
+impl FromStr for TokenSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "github" => Ok(Self::Github),
+            Some(("env", name)) if !name.is_empty() => Ok(Self::Env(name.to_string())),
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(path.into())),
+            _ => Err(format!(
+                "unknown token source `{s}`; expected `github`, `env:NAME` or `file:PATH`"
+            )),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TokenSource {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Requests the OIDC token of the running GitHub Actions job for `audience`.
+///
+/// The job must have the `id-token: write` permission.
+async fn github_token(audience: &str) -> Result<String> {
+    let url = std::env::var("ACTIONS_ID_TOKEN_REQUEST_URL")
+        .context("`ACTIONS_ID_TOKEN_REQUEST_URL` is not set; is `id-token: write` permitted?")?;
+    let token = std::env::var("ACTIONS_ID_TOKEN_REQUEST_TOKEN")
+        .context("`ACTIONS_ID_TOKEN_REQUEST_TOKEN` is not set; is `id-token: write` permitted?")?;
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let request = Request::get(format!(
+        "{url}{separator}audience={}",
+        urlencoding::encode(audience)
+    ))
+    .header(header::AUTHORIZATION, format!("Bearer {token}"))
+    .header(header::ACCEPT, "application/json")
+    .body(Body::empty())?;
+
+    #[derive(serde::Deserialize)]
+    struct Response {
+        value: String,
+    }
+
+    let body = http::send(request).await?;
+    let response: Response =
+        serde_json::from_slice(&body).context("malformed OIDC token response")?;
+    Ok(response.value)
+}
@@ -0,0 +1,199 @@
+use crate::config::OktaConfig;
+use crate::{http, prompt, saml};
+use anyhow::{anyhow, bail, Context as _, Result};
+use hyper::{header, Body, Request};
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Clone, clap::Args)]
+pub struct OktaArgs {
+    /// The embed link of the AWS application in Okta to obtain the SAML assertion from.
+    #[arg(
+        long,
+        value_name = "URL",
+        conflicts_with_all = saml::CONFLICTS,
+        env = "ASSUME_ROLE_OKTA_APP_URL"
+    )]
+    okta_app_url: Option<String>,
+
+    /// The user name to sign in to Okta with. Defaults to `okta.username` in the config file.
+    #[arg(long, value_name = "NAME", env = "ASSUME_ROLE_OKTA_USERNAME")]
+    okta_username: Option<String>,
+
+    /// The MFA factor to verify if Okta requires one. Defaults to `okta.factor` in the config file or `push`.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FACTOR",
+        env = "ASSUME_ROLE_OKTA_FACTOR"
+    )]
+    okta_factor: Option<Factor>,
+
+    /// Obtain the SAML assertion from the app of `okta.app_url` in the config file.
+    #[arg(long, conflicts_with_all = saml::CONFLICTS, env = "ASSUME_ROLE_OKTA")]
+    okta: bool,
+}
+
+/// An MFA factor of Okta.
+#[derive(Clone, Copy, Debug, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Factor {
+    /// Okta Verify push notification.
+    #[default]
+    Push,
+
+    /// A time-based one-time password of an authenticator app.
+    Totp,
+}
+
+impl OktaArgs {
+    /// Returns whether the SAML assertion is obtained from Okta.
+    pub fn enabled(&self) -> bool {
+        self.okta || self.okta_app_url.is_some()
+    }
+
+    /// Signs in to Okta and returns the base64-encoded SAML assertion for the AWS application.
+    pub async fn saml_assertion(&self, config: &OktaConfig) -> Result<String> {
+        let app_url = self
+            .okta_app_url
+            .as_ref()
+            .or(config.app_url.as_ref())
+            .ok_or_else(|| anyhow!("the app URL of Okta is not specified"))?;
+        let username = self
+            .okta_username
+            .as_ref()
+            .or(config.username.as_ref())
+            .ok_or_else(|| anyhow!("the user name of Okta is not specified"))?;
+        let factor = self.okta_factor.or(config.factor).unwrap_or_default();
+
+        let uri: hyper::Uri = app_url.parse().context("malformed app URL of Okta")?;
+        let origin = format!(
+            "{}://{}",
+            uri.scheme_str().unwrap_or("https"),
+            uri.authority()
+                .ok_or_else(|| anyhow!("malformed app URL of Okta"))?
+        );
+
+        let password = prompt::secret(&format!("Okta password for {username}: ")).await?;
+        let session_token = authenticate(&origin, username, &password, factor).await?;
+
+        let separator = if app_url.contains('?') { '&' } else { '?' };
+        let request = Request::get(format!(
+            "{app_url}{separator}sessionToken={}",
+            urlencoding::encode(&session_token)
+        ))
+        .header(header::ACCEPT, "text/html")
+        .body(Body::empty())?;
+        let html = http::send(request).await?;
+
+        saml::form_value(&String::from_utf8_lossy(&html))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transaction {
+    status: String,
+    session_token: Option<String>,
+    state_token: Option<String>,
+    factor_result: Option<String>,
+    #[serde(rename = "_embedded", default)]
+    embedded: Embedded,
+    #[serde(rename = "_links", default)]
+    links: Links,
+}
+
+#[derive(Default, Deserialize)]
+struct Embedded {
+    #[serde(default)]
+    factors: Vec<FactorObject>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FactorObject {
+    factor_type: String,
+    #[serde(rename = "_links")]
+    links: Links,
+}
+
+#[derive(Default, Deserialize)]
+struct Links {
+    verify: Option<Link>,
+    next: Option<Link>,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+/// Authenticates with the Okta Authentication API and returns the session token.
+async fn authenticate(
+    origin: &str,
+    username: &str,
+    password: &str,
+    factor: Factor,
+) -> Result<String> {
+    let mut transaction: Transaction = http::post_json(
+        &format!("{origin}/api/v1/authn"),
+        &serde_json::json!({ "username": username, "password": password }),
+    )
+    .await?;
+
+    if transaction.status == "MFA_REQUIRED" {
+        let state_token = transaction
+            .state_token
+            .clone()
+            .ok_or_else(|| anyhow!("no state token provided by Okta"))?;
+        let factor_type = match factor {
+            Factor::Push => "push",
+            Factor::Totp => "token:software:totp",
+        };
+        let verify = transaction
+            .embedded
+            .factors
+            .iter()
+            .find(|f| f.factor_type == factor_type)
+            .and_then(|f| f.links.verify.as_ref())
+            .ok_or_else(|| anyhow!("the `{factor_type}` factor is not enrolled in Okta"))?;
+
+        transaction = match factor {
+            Factor::Push => {
+                eprintln!("Waiting for approval of the push notification...");
+                let mut href = verify.href.clone();
+                loop {
+                    let t: Transaction =
+                        http::post_json(&href, &serde_json::json!({ "stateToken": state_token }))
+                            .await?;
+                    match t.factor_result.as_deref() {
+                        Some("WAITING") => {
+                            href = t.links.next.as_ref().map_or(href, |next| next.href.clone());
+                            tokio::time::sleep(Duration::from_secs(2)).await;
+                        }
+                        Some(result @ ("REJECTED" | "TIMEOUT")) => {
+                            bail!("the push notification is {}", result.to_lowercase())
+                        }
+                        _ => break t,
+                    }
+                }
+            }
+            Factor::Totp => {
+                let code = prompt::secret("Okta verification code: ").await?;
+                http::post_json(
+                    &verify.href,
+                    &serde_json::json!({ "stateToken": state_token, "passCode": code }),
+                )
+                .await?
+            }
+        };
+    }
+
+    if transaction.status != "SUCCESS" {
+        bail!("Okta authentication ended with `{}`", transaction.status);
+    }
+
+    transaction
+        .session_token
+        .ok_or_else(|| anyhow!("no session token provided by Okta"))
+}
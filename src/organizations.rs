@@ -0,0 +1,122 @@
+use crate::assume::partition;
+use crate::{dirs, file, http};
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// How long the list of accounts is reused before it is fetched again.
+const MAX_AGE: i64 = 24 * 60 * 60;
+
+/// The accounts of the organization, cached locally.
+#[derive(Deserialize, Serialize)]
+struct Accounts {
+    /// The time the accounts were listed, in seconds since the epoch.
+    fetched_at: i64,
+    accounts: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListAccountsInput<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListAccountsOutput {
+    accounts: Vec<Account>,
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Account {
+    id: String,
+    name: String,
+}
+
+/// Resolves the ID of the account named `name` in the organization of the caller.
+///
+/// The accounts are listed with `organizations:ListAccounts`, which requires the credentials of the
+/// management account or a delegated administrator, and cached for a day.
+pub async fn account_id(config: &SdkConfig, name: &str) -> Result<String> {
+    let path = path()?;
+
+    if let Some(cached) = load(&path).await {
+        if Utc::now().timestamp() - cached.fetched_at < MAX_AGE {
+            if let Some(id) = cached.accounts.get(name) {
+                return Ok(id.clone());
+            }
+        }
+    }
+
+    let accounts = Accounts {
+        fetched_at: Utc::now().timestamp(),
+        accounts: list_accounts(config).await?,
+    };
+    if let Err(e) = file::write_private(&path, &serde_json::to_vec(&accounts)?).await {
+        tracing::warn!("failed to cache the accounts: {e:#}");
+    }
+
+    accounts
+        .accounts
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("no account named `{name}` in the organization"))
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()?.join("accounts.json"))
+}
+
+async fn load(path: &PathBuf) -> Option<Accounts> {
+    let content = tokio::fs::read(path).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+async fn list_accounts(config: &SdkConfig) -> Result<BTreeMap<String, String>> {
+    // Organizations is a global service served from a single region of each partition.
+    let (endpoint, region) = match partition(config) {
+        "aws-cn" => (
+            "https://organizations.cn-northwest-1.amazonaws.com.cn/",
+            "cn-northwest-1",
+        ),
+        "aws-us-gov" => (
+            "https://organizations.us-gov-west-1.amazonaws.com/",
+            "us-gov-west-1",
+        ),
+        _ => (
+            "https://organizations.us-east-1.amazonaws.com/",
+            "us-east-1",
+        ),
+    };
+
+    let mut accounts = BTreeMap::new();
+    let mut next_token = None;
+    loop {
+        let output: ListAccountsOutput = http::aws_json(
+            config,
+            endpoint,
+            region,
+            "organizations",
+            "AWSOrganizationsV20161128.ListAccounts",
+            &ListAccountsInput {
+                next_token: next_token.as_deref(),
+            },
+        )
+        .await?;
+
+        accounts.extend(output.accounts.into_iter().map(|a| (a.name, a.id)));
+
+        match output.next_token {
+            Some(token) => next_token = Some(token),
+            None => break,
+        }
+    }
+
+    Ok(accounts)
+}
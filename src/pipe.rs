@@ -0,0 +1,195 @@
+use anyhow::{Context as _, Result};
+use std::ffi::CString;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Makes `content` readable by `cmd` on the file descriptor `fd` through a pipe.
+///
+/// The returned end of the pipe must be kept open until `cmd` is spawned.
+pub fn pass(cmd: &mut Command, fd: i32, content: &[u8]) -> Result<OwnedFd> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("failed to create a pipe");
+    }
+    let reader = unsafe { OwnedFd::from_raw_fd(fds[0]) };
+    let mut writer = unsafe { File::from_raw_fd(fds[1]) };
+    for fd in fds {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+
+    // The content fits in the buffer of the pipe, so writing it does not wait for the command.
+    writer.write_all(content)?;
+    drop(writer);
+
+    let source = reader.as_raw_fd();
+    unsafe {
+        cmd.pre_exec(move || {
+            // `dup2` leaves the flags alone if the descriptors are the same.
+            let result = if source == fd {
+                libc::fcntl(fd, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(source, fd)
+            };
+            if result < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(reader)
+}
+
+/// A named pipe that gives its current content to every reader, and is removed when dropped.
+///
+/// Each reader opens a pipe of its own: once a reader opens the pipe, a new one is put in its place
+/// before the content is written, so that the reader sees the end of the content however slowly it
+/// reads. Only readers that open the pipe at the same moment may share it.
+pub struct Fifo {
+    path: PathBuf,
+    content: Arc<Mutex<Vec<u8>>>,
+    /// Whether the writer should stop, which also guards replacing the pipe at `path`.
+    stop: Arc<Mutex<bool>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Fifo {
+    /// Creates a named pipe only accessible by the current user at `path`, which must not exist.
+    pub fn create(path: &Path) -> Result<Self> {
+        mkfifo(path)?;
+
+        let content = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(Mutex::new(false));
+        let writer = {
+            let (path, content, stop) = (path.to_path_buf(), content.clone(), stop.clone());
+            std::thread::spawn(move || write(&path, &content, &stop))
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            content,
+            stop,
+            writer: Some(writer),
+        })
+    }
+
+    /// Replaces the content given to the following readers.
+    pub fn set(&self, content: Vec<u8>) {
+        *self.content.lock().unwrap() = content;
+    }
+}
+
+impl Drop for Fifo {
+    fn drop(&mut self) {
+        // Opening the pipe as a reader lets the writer waiting for one go on and see that it should
+        // stop. The pipe is kept open until then since the writer may open it only afterwards.
+        let reader = {
+            let mut stop = self.stop.lock().unwrap();
+            *stop = true;
+            std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(&self.path)
+        };
+        // The writer would wait for a reader forever if the pipe is removed from under it.
+        if let Some(writer) = self.writer.take() {
+            if reader.is_ok() || writer.is_finished() {
+                let _ = writer.join();
+            }
+        }
+        drop(reader);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Writes `content` to each reader that opens the pipe at `path` until `stop` is set.
+fn write(path: &Path, content: &Mutex<Vec<u8>>, stop: &Mutex<bool>) {
+    let mut next = path.as_os_str().to_owned();
+    next.push(format!(".{}.next", std::process::id()));
+    let next = PathBuf::from(next);
+    loop {
+        // Opening the pipe blocks until a reader opens it.
+        let Ok(mut file) = std::fs::OpenOptions::new().write(true).open(path) else {
+            return;
+        };
+        {
+            let stop = stop.lock().unwrap();
+            if *stop {
+                return;
+            }
+            if let Err(e) = mkfifo(&next).and_then(|()| Ok(std::fs::rename(&next, path)?)) {
+                tracing::warn!("failed to replace `{}`: {e:#}", path.display());
+                return;
+            }
+        }
+
+        let content = content.lock().unwrap().clone();
+        // The reader may close the pipe without reading it all.
+        let _ = file.write_all(&content);
+    }
+}
+
+/// Creates a named pipe only accessible by the current user at `path`.
+fn mkfifo(path: &Path) -> Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("failed to create `{}`", path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::os::unix::fs::MetadataExt as _;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("assume-role-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn fifo_per_reader() {
+        let path = temp_path("fifo");
+        let fifo = Fifo::create(&path).unwrap();
+        fifo.set(b"first".to_vec());
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+
+        // A reader that has not read yet does not hold up the following ones.
+        let mut slow = File::open(&path).unwrap();
+        // The pipe is replaced before the content is written to the slow reader.
+        let inode = slow.metadata().unwrap().ino();
+        while std::fs::metadata(&path).unwrap().ino() == inode {
+            std::thread::yield_now();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"first");
+        fifo.set(b"second".to_vec());
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        let mut content = Vec::new();
+        slow.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"first");
+
+        drop(fifo);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn fifo_without_readers() {
+        let path = temp_path("unread");
+        let fifo = Fifo::create(&path).unwrap();
+        assert!(Fifo::create(&path).is_err());
+        drop(fifo);
+        assert!(!path.exists());
+    }
+}
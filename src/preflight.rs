@@ -0,0 +1,128 @@
+//! Pre-flight check of the permission to assume a role with the policy simulator of IAM.
+
+use crate::cache;
+use anyhow::{anyhow, bail, Result};
+use aws_config::SdkConfig;
+use aws_sdk_iam::types::{ContextEntry, ContextKeyTypeEnum, PolicyEvaluationDecisionType};
+
+/// Checks that the identity-based policies of the caller allow `sts:AssumeRole` on `role_arn`,
+/// with the MFA present if `mfa` is `true`.
+///
+/// The simulator does not evaluate trust policies, which alone can allow the principals in the
+/// same account. Thus only an explicit denial or a role in another account fails the check, and the
+/// check is skipped with a message if the role of the caller cannot be resolved or the simulation
+/// itself is not permitted.
+pub async fn check(config: &SdkConfig, role_arn: &str, mfa: bool) -> Result<()> {
+    let identity = aws_sdk_sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await?;
+    let caller = identity
+        .arn()
+        .ok_or_else(|| anyhow!("caller ARN is not provided"))?;
+    let iam = aws_sdk_iam::Client::new(config);
+    // The policies of an assumed role are those of the role, whose ARN may have a path, such as
+    // `/aws-reserved/sso.amazonaws.com/`, that the ARN of the session lacks.
+    let principal = match cache::role_arn_of(caller) {
+        Some(arn) => match get_role_arn(&iam, &arn).await {
+            Ok(arn) => arn,
+            Err(e) => {
+                eprintln!(
+                    "Could not check the permission to assume `{role_arn}`: failed to get the \
+                     role of the caller: {e:#}"
+                );
+                return Ok(());
+            }
+        },
+        None => caller.to_string(),
+    };
+    // The role to assume in the same account may also have a path that its ARN built from the name
+    // lacks, which the resources in the policies may match.
+    let role_arn = &if account_of(&principal) == account_of(role_arn) {
+        get_role_arn(&iam, role_arn)
+            .await
+            .unwrap_or_else(|_| role_arn.to_string())
+    } else {
+        role_arn.to_string()
+    };
+
+    let mut request = iam
+        .simulate_principal_policy()
+        .policy_source_arn(&principal)
+        .action_names("sts:AssumeRole")
+        .resource_arns(role_arn);
+    if mfa {
+        request = request.context_entries(
+            ContextEntry::builder()
+                .context_key_name("aws:MultiFactorAuthPresent")
+                .context_key_type(ContextKeyTypeEnum::Boolean)
+                .context_key_values("true")
+                .build(),
+        );
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!(
+                "Could not check the permission to assume `{role_arn}`: {:#}",
+                anyhow::Error::from(e)
+            );
+            return Ok(());
+        }
+    };
+
+    for result in response.evaluation_results() {
+        match result.eval_decision() {
+            PolicyEvaluationDecisionType::Allowed => {}
+            PolicyEvaluationDecisionType::ExplicitDeny => {
+                let policies = result
+                    .matched_statements()
+                    .iter()
+                    .filter_map(|statement| statement.source_policy_id())
+                    .map(|id| format!("`{id}`"))
+                    .collect::<Vec<_>>();
+                bail!(
+                    "`{principal}` is not allowed to assume `{role_arn}` because it is explicitly \
+                     denied by {}",
+                    if policies.is_empty() {
+                        "a policy".to_string()
+                    } else {
+                        policies.join(", ")
+                    }
+                );
+            }
+            _ => {
+                let reason = if result.missing_context_values().is_empty() {
+                    "no identity-based policy allows `sts:AssumeRole` on it".to_string()
+                } else {
+                    format!(
+                        "no identity-based policy allows it without the condition keys {}",
+                        result.missing_context_values().join(", ")
+                    )
+                };
+                if account_of(&principal) == account_of(role_arn) {
+                    eprintln!(
+                        "`{principal}` may be allowed only by the trust policy, since {reason}"
+                    );
+                } else {
+                    bail!("`{principal}` is not allowed to assume `{role_arn}` because {reason}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the ARN of the role of `arn` with its path.
+async fn get_role_arn(iam: &aws_sdk_iam::Client, arn: &str) -> Result<String> {
+    let name = arn.rsplit('/').next().unwrap_or_default();
+    let output = iam.get_role().role_name(name).send().await?;
+    Ok(output
+        .role()
+        .map_or_else(|| arn.to_string(), |role| role.arn().to_string()))
+}
+
+fn account_of(arn: &str) -> Option<&str> {
+    arn.split(':').nth(4)
+}
@@ -0,0 +1,184 @@
+use crate::{dirs, file};
+use anyhow::{Context as _, Result};
+use aws_sdk_sts::types::Credentials;
+use aws_smithy_types::date_time::Format;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tokio::fs;
+
+const KEYS: [&str; 3] = [
+    "aws_access_key_id",
+    "aws_secret_access_key",
+    "aws_session_token",
+];
+
+const EXPIRATION_COMMENT: &str = "# assume-role: expires at ";
+
+/// Returns the path of the shared credentials file.
+pub fn credentials_file() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os("AWS_SHARED_CREDENTIALS_FILE") {
+        return Ok(path.into());
+    }
+
+    Ok(dirs::home_dir()?.join(".aws").join("credentials"))
+}
+
+/// Writes `credentials` to the profile `name` in the shared credentials file.
+///
+/// Other profiles and unrelated keys of the profile are preserved. The file is replaced
+/// atomically so that concurrent readers never observe a partially written file.
+pub async fn write_profile(name: &str, credentials: &Credentials) -> Result<()> {
+    let path = credentials_file()?;
+    let content = match fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read `{}`", path.display())),
+    };
+
+    let content = update_profile(&content, name, credentials)?;
+    file::write_private(&path, content.as_bytes()).await
+}
+
+/// Returns `content` of a shared credentials file with `credentials` written to the profile `name`.
+fn update_profile(content: &str, name: &str, credentials: &Credentials) -> Result<String> {
+    let section = [
+        format!(
+            "{EXPIRATION_COMMENT}{}",
+            credentials.expiration().fmt(Format::DateTime)?
+        ),
+        format!("{} = {}", KEYS[0], credentials.access_key_id()),
+        format!("{} = {}", KEYS[1], credentials.secret_access_key()),
+        format!("{} = {}", KEYS[2], credentials.session_token()),
+    ];
+
+    let mut output = Vec::new();
+    let mut current = None;
+    let mut found = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(header) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            current = Some(header.trim().to_string());
+            output.push(line.to_string());
+            if header.trim() == name {
+                found = true;
+                output.extend(section.iter().cloned());
+            }
+            continue;
+        }
+
+        if current.as_deref() == Some(name) {
+            if trimmed.starts_with(EXPIRATION_COMMENT) {
+                continue;
+            }
+            if let Some((key, _)) = trimmed.split_once('=') {
+                if KEYS.contains(&key.trim().to_ascii_lowercase().as_str()) {
+                    continue;
+                }
+            }
+        }
+
+        output.push(line.to_string());
+    }
+
+    if !found {
+        if output.last().is_some_and(|l| !l.trim().is_empty()) {
+            output.push(String::new());
+        }
+        output.push(format!("[{name}]"));
+        output.extend(section);
+    }
+
+    let mut content = output.join("\n");
+    content.push('\n');
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_smithy_types::DateTime;
+
+    fn credentials() -> Credentials {
+        Credentials::builder()
+            .access_key_id("AKIANEW")
+            .secret_access_key("new-secret")
+            .session_token("new-token")
+            .expiration(DateTime::from_secs(1_700_000_000))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn new_file() {
+        assert_eq!(
+            update_profile("", "dev", &credentials()).unwrap(),
+            "[dev]\n\
+             # assume-role: expires at 2023-11-14T22:13:20Z\n\
+             aws_access_key_id = AKIANEW\n\
+             aws_secret_access_key = new-secret\n\
+             aws_session_token = new-token\n"
+        );
+    }
+
+    #[test]
+    fn new_profile() {
+        assert_eq!(
+            update_profile(
+                "[default]\naws_access_key_id = AKIADEFAULT",
+                "dev",
+                &credentials()
+            )
+            .unwrap(),
+            "[default]\n\
+             aws_access_key_id = AKIADEFAULT\n\
+             \n\
+             [dev]\n\
+             # assume-role: expires at 2023-11-14T22:13:20Z\n\
+             aws_access_key_id = AKIANEW\n\
+             aws_secret_access_key = new-secret\n\
+             aws_session_token = new-token\n"
+        );
+    }
+
+    #[test]
+    fn existing_profile() {
+        let content = "\
+# comment
+[dev]
+# assume-role: expires at 2023-01-01T00:00:00Z
+aws_access_key_id = AKIAOLD
+AWS_Secret_Access_Key=old-secret
+aws_session_token = old-token
+region = us-east-1
+
+[ other ]
+aws_access_key_id = AKIAOTHER
+";
+        assert_eq!(
+            update_profile(content, "dev", &credentials()).unwrap(),
+            "\
+# comment
+[dev]
+# assume-role: expires at 2023-11-14T22:13:20Z
+aws_access_key_id = AKIANEW
+aws_secret_access_key = new-secret
+aws_session_token = new-token
+region = us-east-1
+
+[ other ]
+aws_access_key_id = AKIAOTHER
+"
+        );
+        assert_eq!(
+            update_profile(content, "other", &credentials()).unwrap(),
+            content.replace(
+                "[ other ]\naws_access_key_id = AKIAOTHER",
+                "[ other ]\n\
+                 # assume-role: expires at 2023-11-14T22:13:20Z\n\
+                 aws_access_key_id = AKIANEW\n\
+                 aws_secret_access_key = new-secret\n\
+                 aws_session_token = new-token"
+            )
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use serde::Deserialize;
+use std::io::{BufRead as _, IsTerminal as _, Write as _};
+use std::process::Stdio;
+use std::sync::OnceLock;
+use tokio::io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader};
+use tokio::process::Command;
+
+/// How to interact with the user.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// The terminal connected to the standard input.
+    #[default]
+    Tty,
+
+    /// A dialog of the desktop, shown by `osascript` on macOS or `zenity` elsewhere.
+    Gui,
+
+    /// A `pinentry` program of GnuPG.
+    Pinentry,
+}
+
+static BACKEND: OnceLock<Backend> = OnceLock::new();
+
+/// Selects the backend used by the prompts of this process.
+pub fn set_backend(backend: Backend) {
+    let _ = BACKEND.set(backend);
+}
+
+fn backend() -> Backend {
+    BACKEND.get().copied().unwrap_or_default()
+}
+
+/// Prompts the user for a secret without echoing the input.
+pub async fn secret(prompt: &str) -> Result<String> {
+    match backend() {
+        Backend::Tty => tty_read(prompt, false).await,
+        Backend::Gui => gui_secret(prompt).await,
+        Backend::Pinentry => {
+            let mut pinentry = Pinentry::spawn().await?;
+            pinentry
+                .command(&format!("SETDESC {}", escape(prompt)))
+                .await?;
+            pinentry.command("SETPROMPT Code:").await?;
+            pinentry
+                .command("GETPIN")
+                .await?
+                .ok_or_else(|| anyhow!("no input is provided"))
+        }
+    }
+}
+
+/// Asks the user to confirm `message`.
+pub async fn confirm(message: &str) -> Result<bool> {
+    match backend() {
+        Backend::Tty => {
+            let answer = tty_read(&format!("{message} [y/N]: "), true).await?;
+            Ok(matches!(answer.to_ascii_lowercase().as_str(), "y" | "yes"))
+        }
+        Backend::Gui => {
+            let mut cmd = if cfg!(target_os = "macos") {
+                let mut cmd = Command::new("osascript");
+                cmd.args([
+                    "-e",
+                    &format!("display dialog {}", applescript_string(message)),
+                ]);
+                cmd
+            } else {
+                let mut cmd = Command::new("zenity");
+                cmd.args(["--question", "--text", message]);
+                cmd
+            };
+            let status = cmd
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await
+                .context("failed to show a dialog")?;
+            Ok(status.success())
+        }
+        Backend::Pinentry => {
+            let mut pinentry = Pinentry::spawn().await?;
+            pinentry
+                .command(&format!("SETDESC {}", escape(message)))
+                .await?;
+            Ok(pinentry.command("CONFIRM").await.is_ok())
+        }
+    }
+}
+
+/// Asks the user to choose one of `items`, the most relevant first.
+pub async fn select(prompt: &str, items: Vec<String>) -> Result<String> {
+    if items.is_empty() {
+        bail!("nothing to choose from");
+    }
+
+    match backend() {
+        Backend::Gui => gui_select(prompt, &items).await,
+        // `pinentry` cannot show a list, so the terminal is used instead.
+        Backend::Tty | Backend::Pinentry => {
+            if !std::io::stdin().is_terminal() {
+                bail!("cannot prompt for input because standard input is not a terminal");
+            }
+
+            let prompt = prompt.to_string();
+            tokio::task::spawn_blocking(move || tty_select(&prompt, &items)).await?
+        }
+    }
+}
+
+async fn tty_read(prompt: &str, echo: bool) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        bail!("cannot prompt for input because standard input is not a terminal");
+    }
+
+    let prompt = prompt.to_string();
+    tokio::task::spawn_blocking(move || {
+        let mut stderr = std::io::stderr();
+        write!(stderr, "{prompt}")?;
+        stderr.flush()?;
+
+        let guard = (!echo).then(TermGuard::disable_echo);
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        if guard.is_some() {
+            drop(guard);
+            writeln!(stderr)?;
+        }
+
+        Ok(line.trim().to_string())
+    })
+    .await?
+}
+
+async fn gui_secret(prompt: &str) -> Result<String> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("osascript");
+        cmd.args([
+            "-e",
+            &format!(
+                "text returned of (display dialog {} default answer \"\" with hidden answer)",
+                applescript_string(prompt)
+            ),
+        ]);
+        cmd
+    } else {
+        let mut cmd = Command::new("zenity");
+        cmd.args(["--entry", "--hide-text", "--text", prompt]);
+        cmd
+    };
+
+    let output = cmd
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .context("failed to show a dialog")?;
+    if !output.status.success() {
+        bail!("the dialog was cancelled");
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+async fn gui_select(prompt: &str, items: &[String]) -> Result<String> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let list = items
+            .iter()
+            .map(|i| applescript_string(i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut cmd = Command::new("osascript");
+        cmd.args([
+            "-e",
+            &format!(
+                "choose from list {{{list}}} with prompt {}",
+                applescript_string(prompt)
+            ),
+        ]);
+        cmd
+    } else {
+        let mut cmd = Command::new("zenity");
+        cmd.args(["--list", "--text", prompt, "--column", prompt])
+            .args(items);
+        cmd
+    };
+
+    let output = cmd
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .context("failed to show a dialog")?;
+    let selected = String::from_utf8(output.stdout)?.trim().to_string();
+    // `osascript` prints `false` when the dialog is cancelled.
+    if !output.status.success() || selected.is_empty() || selected == "false" {
+        bail!("the dialog was cancelled");
+    }
+
+    Ok(selected)
+}
+
+/// The number of candidates shown by the picker at once.
+const PICKER_HEIGHT: usize = 10;
+
+/// Lets the user narrow down `items` by typing and choose one with the arrow keys.
+#[cfg(unix)]
+fn tty_select(prompt: &str, items: &[String]) -> Result<String> {
+    use std::io::Read as _;
+
+    let _guard = TermGuard::raw();
+    let mut stderr = std::io::stderr();
+    let mut stdin = std::io::stdin().lock();
+
+    let mut query = String::new();
+    let mut cursor = 0;
+    let result = loop {
+        let matches = items
+            .iter()
+            .filter(|item| fuzzy_match(&query, item))
+            .collect::<Vec<_>>();
+        cursor = cursor.min(matches.len().saturating_sub(1));
+
+        // Draw the query line and the candidates below it, then move back to the query line.
+        let shown = matches.len().min(PICKER_HEIGHT);
+        write!(stderr, "\r\x1b[J{prompt}: {query}")?;
+        for (i, item) in matches.iter().take(shown).enumerate() {
+            let marker = if i == cursor { '>' } else { ' ' };
+            write!(stderr, "\r\n{marker} {item}")?;
+        }
+        if shown > 0 {
+            write!(stderr, "\x1b[{shown}A")?;
+        }
+        write!(
+            stderr,
+            "\r\x1b[{}C",
+            prompt.chars().count() + 2 + query.chars().count()
+        )?;
+        stderr.flush()?;
+
+        let mut byte = [0];
+        if stdin.read(&mut byte)? == 0 {
+            break None;
+        }
+        match byte[0] {
+            b'\r' | b'\n' => break matches.get(cursor).map(|s| s.to_string()),
+            // Ctrl-C, Ctrl-D
+            0x03 | 0x04 => break None,
+            // Backspace
+            0x08 | 0x7f => {
+                query.pop();
+                cursor = 0;
+            }
+            // Ctrl-P, Ctrl-N
+            0x10 => cursor = cursor.saturating_sub(1),
+            0x0e => cursor = (cursor + 1).min(shown.saturating_sub(1)),
+            0x1b => {
+                let mut seq = [0; 2];
+                stdin.read_exact(&mut seq)?;
+                match seq {
+                    [b'[', b'A'] => cursor = cursor.saturating_sub(1),
+                    [b'[', b'B'] => cursor = (cursor + 1).min(shown.saturating_sub(1)),
+                    _ => {}
+                }
+            }
+            b if b.is_ascii_control() => {}
+            b if b.is_ascii() => {
+                query.push(b as char);
+                cursor = 0;
+            }
+            b => {
+                // Read the rest of a multibyte character.
+                let len = match b {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    _ => 4,
+                };
+                let mut buf = [b, 0, 0, 0];
+                stdin.read_exact(&mut buf[1..len])?;
+                query.push_str(&String::from_utf8_lossy(&buf[..len]));
+                cursor = 0;
+            }
+        }
+    };
+
+    write!(stderr, "\r\x1b[J")?;
+    match result {
+        Some(item) => {
+            writeln!(stderr, "{prompt}: {item}")?;
+            Ok(item)
+        }
+        None => bail!("no item is selected"),
+    }
+}
+
+/// Lists `items` with numbers and lets the user choose one by number.
+#[cfg(not(unix))]
+fn tty_select(prompt: &str, items: &[String]) -> Result<String> {
+    let mut stderr = std::io::stderr();
+    for (i, item) in items.iter().enumerate() {
+        writeln!(stderr, "{:>3}: {item}", i + 1)?;
+    }
+    write!(stderr, "{prompt} [1-{}]: ", items.len())?;
+    stderr.flush()?;
+
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    line.trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| items.get(n.checked_sub(1)?))
+        .cloned()
+        .ok_or_else(|| anyhow!("no item is selected"))
+}
+
+/// Returns whether the characters of `query` appear in `item` in order, ignoring case.
+fn fuzzy_match(query: &str, item: &str) -> bool {
+    let mut chars = item.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| chars.any(|c| c == q))
+}
+
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Escapes `s` for an argument of the Assuan protocol.
+fn escape(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A session with a `pinentry` program over the Assuan protocol.
+struct Pinentry {
+    _child: tokio::process::Child,
+    stdin: tokio::process::ChildStdin,
+    stdout: BufReader<tokio::process::ChildStdout>,
+}
+
+impl Pinentry {
+    async fn spawn() -> Result<Self> {
+        let mut child = Command::new("pinentry")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("failed to run `pinentry`")?;
+        let mut pinentry = Self {
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            _child: child,
+        };
+        pinentry.response().await?;
+
+        if let Ok(tty) = std::env::var("GPG_TTY") {
+            pinentry.command(&format!("OPTION ttyname={tty}")).await?;
+        }
+
+        Ok(pinentry)
+    }
+
+    /// Sends `command` and returns the data of the successful response.
+    async fn command(&mut self, command: &str) -> Result<Option<String>> {
+        self.stdin
+            .write_all(format!("{command}\n").as_bytes())
+            .await?;
+        self.stdin.flush().await?;
+        self.response().await
+    }
+
+    async fn response(&mut self) -> Result<Option<String>> {
+        let mut data = None;
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).await? == 0 {
+                bail!("`pinentry` exited unexpectedly");
+            }
+
+            let line = line.trim_end();
+            if line == "OK" || line.starts_with("OK ") {
+                return Ok(data);
+            } else if let Some(error) = line.strip_prefix("ERR ") {
+                bail!("`pinentry` failed: {error}");
+            } else if let Some(d) = line.strip_prefix("D ") {
+                data = Some(
+                    d.replace("%0A", "\n")
+                        .replace("%0D", "\r")
+                        .replace("%25", "%"),
+                );
+            }
+        }
+    }
+}
+
+/// Changes the mode of the terminal on standard input until dropped.
+struct TermGuard {
+    #[cfg(unix)]
+    original: Option<libc::termios>,
+}
+
+impl TermGuard {
+    /// Disables the echo of the input.
+    fn disable_echo() -> Self {
+        #[cfg(unix)]
+        return Self::clear(libc::ECHO);
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    /// Passes each key to the program as it is typed, without echo or signals.
+    #[cfg(unix)]
+    fn raw() -> Self {
+        Self::clear(libc::ECHO | libc::ICANON | libc::ISIG)
+    }
+
+    #[cfg(unix)]
+    fn clear(flags: libc::tcflag_t) -> Self {
+        let mut termios = std::mem::MaybeUninit::uninit();
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } != 0 {
+            return Self { original: None };
+        }
+
+        let original = unsafe { termios.assume_init() };
+        let mut modified = original;
+        modified.c_lflag &= !flags;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &modified) };
+
+        Self {
+            original: Some(original),
+        }
+    }
+}
+
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(original) = &self.original {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original) };
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{bail, Context as _, Result};
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinHandle as TaskHandle;
+
+/// A pseudo-terminal to run a command on.
+pub struct Pty {
+    master: File,
+    slave: File,
+}
+
+impl Pty {
+    pub fn open() -> Result<Self> {
+        let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("failed to open a pseudo-terminal");
+        }
+        let master = unsafe { File::from_raw_fd(fd) };
+
+        if unsafe { libc::grantpt(fd) } != 0 || unsafe { libc::unlockpt(fd) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("failed to unlock the pseudo-terminal");
+        }
+        let name = unsafe { libc::ptsname(fd) };
+        if name.is_null() {
+            bail!("failed to get the name of the pseudo-terminal");
+        }
+        let name = unsafe { CStr::from_ptr(name) }
+            .to_string_lossy()
+            .into_owned();
+        let slave = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&name)
+            .with_context(|| format!("failed to open `{name}`"))?;
+
+        copy_window_size(&master);
+        Ok(Self { master, slave })
+    }
+
+    /// Makes `cmd` run in a new session with the pseudo-terminal as its controlling terminal and
+    /// standard I/O.
+    pub fn attach(&self, cmd: &mut Command) -> Result<()> {
+        cmd.stdin(Stdio::from(self.slave.try_clone()?))
+            .stdout(Stdio::from(self.slave.try_clone()?))
+            .stderr(Stdio::from(self.slave.try_clone()?));
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the master side of the pseudo-terminal, to play the user in tests.
+    #[cfg(test)]
+    pub fn master(&self) -> &File {
+        &self.master
+    }
+
+    /// Relays the standard I/O and the window size of this process to the pseudo-terminal until
+    /// the command closes it.
+    ///
+    /// This must be called after the command is spawned so that only the command has the terminal
+    /// open.
+    pub fn relay(self) -> Result<Relay> {
+        let Self { master, slave } = self;
+        drop(slave);
+
+        let guard = RawGuard::enable();
+
+        let mut input = master.try_clone()?;
+        // Reading the standard input blocks until a key is typed, so this thread is not joined.
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            let mut stdin = std::io::stdin().lock();
+            while let Ok(n @ 1..) = stdin.read(&mut buffer) {
+                if input.write_all(&buffer[..n]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut output = master.try_clone()?;
+        let output = std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            let mut stdout = std::io::stdout().lock();
+            // Reading fails with `EIO` once the command and its descendants close the terminal.
+            while let Ok(n @ 1..) = output.read(&mut buffer) {
+                if stdout.write_all(&buffer[..n]).is_err() || stdout.flush().is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut window_change = signal(SignalKind::window_change())?;
+        let resize = tokio::spawn(async move {
+            while window_change.recv().await.is_some() {
+                copy_window_size(&master);
+            }
+        });
+
+        Ok(Relay {
+            output,
+            resize,
+            _guard: guard,
+        })
+    }
+}
+
+/// The relay started by [`Pty::relay`].
+pub struct Relay {
+    output: JoinHandle<()>,
+    resize: TaskHandle<()>,
+    _guard: RawGuard,
+}
+
+impl Relay {
+    /// Waits for the remaining output of the command and restores the terminal.
+    pub fn finish(self) {
+        self.resize.abort();
+        let _ = self.output.join();
+    }
+}
+
+/// Sets the window size of the terminal on standard input to `master`, which notifies the command
+/// with `SIGWINCH`.
+fn copy_window_size(master: &File) {
+    let mut size = std::mem::MaybeUninit::<libc::winsize>::uninit();
+    if unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) } == 0 {
+        unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, size.as_ptr()) };
+    }
+}
+
+/// Puts the terminal on standard input into the raw mode until dropped, so that every key,
+/// including the ones generating signals, reaches the command through the pseudo-terminal.
+struct RawGuard {
+    original: Option<libc::termios>,
+}
+
+impl RawGuard {
+    fn enable() -> Self {
+        let mut termios = std::mem::MaybeUninit::uninit();
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } != 0 {
+            return Self { original: None };
+        }
+
+        let original = unsafe { termios.assume_init() };
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+
+        Self {
+            original: Some(original),
+        }
+    }
+}
+
+impl Drop for RawGuard {
+    fn drop(&mut self) {
+        if let Some(original) = &self.original {
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original) };
+        }
+    }
+}
@@ -0,0 +1,615 @@
+//! A minimal encoder of QR codes, which encodes bytes with the error correction level L.
+
+use anyhow::{bail, Result};
+
+/// The number of error correction codewords in each block for each version.
+const ECC_CODEWORDS_PER_BLOCK: [usize; 41] = [
+    0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30,
+    30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+];
+
+/// The number of error correction blocks for each version.
+const NUM_ERROR_CORRECTION_BLOCKS: [usize; 41] = [
+    0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14,
+    15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+];
+
+/// The bits of the error correction level L in the format information.
+const ECC_LEVEL_BITS: u32 = 1;
+
+/// The width of the light margin around the symbol required by the specification.
+const QUIET_ZONE: usize = 4;
+
+pub struct QrCode {
+    version: usize,
+    size: usize,
+    modules: Vec<bool>,
+    is_function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encodes `data` in the byte mode into the smallest symbol that fits it.
+    pub fn encode(data: &[u8]) -> Result<Self> {
+        let Some(version) = (1..=40)
+            .find(|&version| data_bits(version, data.len()) <= num_data_codewords(version) * 8)
+        else {
+            bail!("{} bytes are too long for a QR code", data.len());
+        };
+
+        let codewords = data_codewords(version, data);
+
+        let size = version * 4 + 17;
+        let mut qr = Self {
+            version,
+            size,
+            modules: vec![false; size * size],
+            is_function: vec![false; size * size],
+        };
+        qr.draw_function_patterns();
+        qr.draw_codewords(&add_ecc_and_interleave(version, &codewords));
+
+        // The mask with the lowest penalty makes the symbol the easiest to read.
+        let mut best = (u32::MAX, 0);
+        for mask in 0..8 {
+            qr.apply_mask(mask);
+            qr.draw_format_bits(mask);
+            let penalty = qr.penalty();
+            if penalty < best.0 {
+                best = (penalty, mask);
+            }
+            qr.apply_mask(mask);
+        }
+        qr.apply_mask(best.1);
+        qr.draw_format_bits(best.1);
+
+        Ok(qr)
+    }
+
+    /// Renders the symbol with two modules per character in the terminal, in black on white
+    /// regardless of the colors of the terminal.
+    pub fn render(&self) -> String {
+        let size = self.size + QUIET_ZONE * 2;
+        let dark = |x: usize, y: usize| {
+            x >= QUIET_ZONE
+                && y >= QUIET_ZONE
+                && x < self.size + QUIET_ZONE
+                && y < self.size + QUIET_ZONE
+                && self.module(x - QUIET_ZONE, y - QUIET_ZONE)
+        };
+
+        let mut output = String::new();
+        for y in (0..size).step_by(2) {
+            output.push_str("\x1b[30;47m");
+            for x in 0..size {
+                output.push(match (dark(x, y), dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            output.push_str("\x1b[0m\n");
+        }
+        output
+    }
+
+    fn module(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function_module(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.is_function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function_module(6, i, i % 2 == 0);
+            self.set_function_module(i, 6, i % 2 == 0);
+        }
+
+        self.draw_finder_pattern(3, 3);
+        self.draw_finder_pattern(size - 4, 3);
+        self.draw_finder_pattern(3, size - 4);
+
+        let positions = alignment_pattern_positions(self.version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // The corners except the bottom right one are occupied by the finder patterns.
+                if (i, j) != (0, 0) && (i, j) != (0, last) && (i, j) != (last, 0) {
+                    self.draw_alignment_pattern(x, y);
+                }
+            }
+        }
+
+        // Reserves the areas of the format information, which are drawn with each mask.
+        self.draw_format_bits(0);
+        self.draw_version();
+    }
+
+    fn draw_finder_pattern(&mut self, x: usize, y: usize) {
+        for dy in -4..=4_isize {
+            for dx in -4..=4_isize {
+                let (xx, yy) = (x as isize + dx, y as isize + dy);
+                if (0..self.size as isize).contains(&xx) && (0..self.size as isize).contains(&yy) {
+                    let distance = dx.abs().max(dy.abs());
+                    self.set_function_module(
+                        xx as usize,
+                        yy as usize,
+                        distance != 2 && distance != 4,
+                    );
+                }
+            }
+        }
+    }
+
+    fn draw_alignment_pattern(&mut self, x: usize, y: usize) {
+        for dy in -2..=2_isize {
+            for dx in -2..=2_isize {
+                let (xx, yy) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+                self.set_function_module(xx, yy, dx.abs().max(dy.abs()) != 1);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let bits = format_bits(mask);
+        let bit = |i: usize| bits >> i & 1 != 0;
+
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function_module(8, i, bit(i));
+        }
+        self.set_function_module(8, 7, bit(6));
+        self.set_function_module(8, 8, bit(7));
+        self.set_function_module(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function_module(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function_module(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function_module(8, size - 15 + i, bit(i));
+        }
+        self.set_function_module(8, size - 8, true);
+    }
+
+    fn draw_version(&mut self) {
+        if self.version < 7 {
+            return;
+        }
+
+        let bits = version_bits(self.version);
+        for i in 0..18 {
+            let dark = bits >> i & 1 != 0;
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            self.set_function_module(a, b, dark);
+            self.set_function_module(b, a, dark);
+        }
+    }
+
+    /// Places `data` in the zigzag order from the bottom right corner.
+    fn draw_codewords(&mut self, data: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            // The vertical timing pattern is skipped over.
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.is_function[y * size + x] && i < data.len() * 8 {
+                        self.modules[y * size + x] = data[i >> 3] >> (7 - (i & 7)) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Inverts the modules selected by `mask`, so applying it twice restores them.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let index = y * self.size + x;
+                self.modules[index] ^= invert && !self.is_function[index];
+            }
+        }
+    }
+
+    /// Returns the penalty of the patterns that are hard to read, as defined by the specification.
+    fn penalty(&self) -> u32 {
+        let size = self.size;
+        let mut penalty = 0;
+
+        let lines = (0..size).flat_map(|i| {
+            [
+                (0..size).map(|j| self.module(j, i)).collect::<Vec<_>>(),
+                (0..size).map(|j| self.module(i, j)).collect::<Vec<_>>(),
+            ]
+        });
+        for line in lines {
+            let mut run = 1;
+            for j in 1..=size {
+                if j < size && line[j] == line[j - 1] {
+                    run += 1;
+                    continue;
+                }
+                if run >= 5 {
+                    penalty += run - 2;
+                }
+                run = 1;
+            }
+
+            // Patterns like the finder patterns with a light area on either side.
+            const FINDER: [bool; 7] = [true, false, true, true, true, false, true];
+            for j in 0..=size - FINDER.len() {
+                if line[j..j + FINDER.len()] != FINDER {
+                    continue;
+                }
+                let light = |range: std::ops::Range<usize>| range.clone().all(|k| !line[k]);
+                if (j >= 4 && light(j - 4..j)) || (j + 11 <= size && light(j + 7..j + 11)) {
+                    penalty += 40;
+                }
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.module(x, y);
+                if color == self.module(x + 1, y)
+                    && color == self.module(x, y + 1)
+                    && color == self.module(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        let dark = self.modules.iter().filter(|&&dark| dark).count();
+        let total = size * size;
+        // The deviation from 50% in steps of 5%.
+        let k = (dark * 20)
+            .abs_diff(total * 10)
+            .div_ceil(total)
+            .saturating_sub(1);
+        penalty += k * 10;
+
+        penalty as u32
+    }
+}
+
+/// Returns the data codewords of `data` in the byte mode for `version`, followed by the terminator
+/// and the padding.
+fn data_codewords(version: usize, data: &[u8]) -> Vec<u8> {
+    let mut bits = Vec::new();
+    push_bits(&mut bits, 0b0100, 4);
+    push_bits(
+        &mut bits,
+        data.len() as u32,
+        if version < 10 { 8 } else { 16 },
+    );
+    for &byte in data {
+        push_bits(&mut bits, byte as u32, 8);
+    }
+    let capacity = num_data_codewords(version) * 8;
+    let terminator = (capacity - bits.len()).min(4);
+    push_bits(&mut bits, 0, terminator);
+    let padding = (8 - bits.len() % 8) % 8;
+    push_bits(&mut bits, 0, padding);
+    let mut codewords = bits
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+        .collect::<Vec<_>>();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() >= capacity / 8 {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    bits.extend((0..count).rev().map(|i| value >> i & 1 != 0));
+}
+
+/// Returns the format information of the error correction level L and `mask` with its BCH code,
+/// masked.
+fn format_bits(mask: u32) -> u32 {
+    let data = ECC_LEVEL_BITS << 3 | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+/// Returns the version information of `version` with its BCH code.
+fn version_bits(version: usize) -> u32 {
+    let version = version as u32;
+    let mut remainder = version;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    version << 12 | remainder
+}
+
+/// Returns the number of bits to encode `len` bytes in the byte mode.
+fn data_bits(version: usize, len: usize) -> usize {
+    4 + if version < 10 { 8 } else { 16 } + len * 8
+}
+
+/// Returns the number of modules for the data and the error correction.
+fn num_raw_data_modules(version: usize) -> usize {
+    let mut result = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let num_align = version / 7 + 2;
+        result -= (25 * num_align - 10) * num_align - 55;
+        if version >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+fn num_data_codewords(version: usize) -> usize {
+    num_raw_data_modules(version) / 8
+        - ECC_CODEWORDS_PER_BLOCK[version] * NUM_ERROR_CORRECTION_BLOCKS[version]
+}
+
+fn alignment_pattern_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+
+    let num_align = version / 7 + 2;
+    let step = (version * 8 + num_align * 3 + 5) / (num_align * 4 - 4) * 2;
+    let mut positions = (0..num_align - 1)
+        .map(|i| version * 4 + 10 - i * step)
+        .collect::<Vec<_>>();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Splits `data` into the blocks, appends the error correction codewords to each block, and
+/// interleaves them.
+fn add_ecc_and_interleave(version: usize, data: &[u8]) -> Vec<u8> {
+    let num_blocks = NUM_ERROR_CORRECTION_BLOCKS[version];
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[version];
+    let raw_codewords = num_raw_data_modules(version) / 8;
+    let num_short_blocks = num_blocks - raw_codewords % num_blocks;
+    let short_block_len = raw_codewords / num_blocks;
+
+    let divisor = reed_solomon_divisor(ecc_len);
+    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut rest = data;
+    for i in 0..num_blocks {
+        let data_len = short_block_len - ecc_len + usize::from(i >= num_short_blocks);
+        let (block_data, remaining) = rest.split_at(data_len);
+        rest = remaining;
+        blocks.push((block_data, reed_solomon_remainder(block_data, &divisor)));
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..short_block_len - ecc_len + 1 {
+        for (block_data, _) in &blocks {
+            if let Some(&codeword) = block_data.get(i) {
+                result.push(codeword);
+            }
+        }
+    }
+    for i in 0..ecc_len {
+        for (_, ecc) in &blocks {
+            result.push(ecc[i]);
+        }
+    }
+    result
+}
+
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0; degree];
+    result[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_multiply(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    result
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (x, &y) in result.iter_mut().zip(divisor) {
+            *x ^= gf_multiply(y, factor);
+        }
+    }
+    result
+}
+
+/// Multiplies in GF(2^8) modulo the polynomial 0x11D.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn galois_field() {
+        assert_eq!(gf_multiply(0x02, 0x80), 0x1D);
+        assert_eq!(gf_multiply(0x53, 0xCA), 0x8F);
+        assert_eq!(gf_multiply(0x8E, 0x01), 0x8E);
+        assert_eq!(gf_multiply(0x8E, 0x00), 0x00);
+    }
+
+    #[test]
+    fn reed_solomon() {
+        // The generator polynomial of degree 7 in the specification, whose coefficients are α^87,
+        // α^229, α^146, α^149, α^238, α^102 and α^21.
+        assert_eq!(reed_solomon_divisor(7), [127, 122, 154, 164, 11, 68, 117]);
+
+        // The data codewords of `HELLO WORLD` in 1-M, and their error correction codewords.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            reed_solomon_remainder(&data, &reed_solomon_divisor(10)),
+            [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+    }
+
+    #[test]
+    fn format_information() {
+        let expected = [
+            0b111011111000100,
+            0b111001011110011,
+            0b111110110101010,
+            0b111100010011101,
+            0b110011000101111,
+            0b110001100011000,
+            0b110110001000001,
+            0b110100101110110,
+        ];
+        for (mask, bits) in expected.into_iter().enumerate() {
+            assert_eq!(format_bits(mask as u32), bits, "mask {mask}");
+        }
+    }
+
+    #[test]
+    fn version_information() {
+        assert_eq!(version_bits(7), 0b000111110010010100);
+        assert_eq!(version_bits(8), 0b001000010110111100);
+        assert_eq!(version_bits(40), 0b101000110001101001);
+    }
+
+    #[test]
+    fn capacities() {
+        assert_eq!(num_raw_data_modules(1), 208);
+        assert_eq!(num_raw_data_modules(2), 359);
+        assert_eq!(num_raw_data_modules(7), 1568);
+        assert_eq!(num_raw_data_modules(40), 29648);
+
+        // The number of bytes that each version holds with the error correction level L.
+        for (version, bytes) in [(1, 17), (2, 32), (5, 106), (9, 230), (10, 271), (40, 2953)] {
+            assert_eq!(QrCode::encode(&vec![0; bytes]).unwrap().version, version);
+            if version < 40 {
+                assert_eq!(
+                    QrCode::encode(&vec![0; bytes + 1]).unwrap().version,
+                    version + 1
+                );
+            }
+        }
+        assert!(QrCode::encode(&[0; 2954]).is_err());
+    }
+
+    #[test]
+    fn alignment_patterns() {
+        assert!(alignment_pattern_positions(1).is_empty());
+        assert_eq!(alignment_pattern_positions(2), [6, 18]);
+        assert_eq!(alignment_pattern_positions(7), [6, 22, 38]);
+        assert_eq!(alignment_pattern_positions(32), [6, 34, 60, 86, 112, 138]);
+        assert_eq!(
+            alignment_pattern_positions(40),
+            [6, 30, 58, 86, 114, 142, 170]
+        );
+    }
+
+    #[test]
+    fn data() {
+        // The mode `0100`, the length `00000001`, `A`, the terminator and the padding.
+        let mut expected = vec![0x40, 0x14, 0x10];
+        expected.extend([0xEC, 0x11].into_iter().cycle().take(16));
+        assert_eq!(data_codewords(1, b"A"), expected);
+    }
+
+    #[test]
+    fn symbol() {
+        let qr = QrCode::encode(b"https://signin.aws.amazon.com/federation").unwrap();
+        assert_eq!((qr.version, qr.size), (3, 29));
+
+        // The finder pattern at the top left corner and its separator.
+        for y in 0..8_usize {
+            for x in 0..8_usize {
+                let ring = x.abs_diff(3).max(y.abs_diff(3));
+                assert_eq!(qr.module(x, y), ring != 2 && ring != 4);
+            }
+        }
+        // The timing patterns and the dark module.
+        for i in 8..qr.size - 8 {
+            assert_eq!(qr.module(i, 6), i % 2 == 0);
+            assert_eq!(qr.module(6, i), i % 2 == 0);
+        }
+        assert!(qr.module(8, qr.size - 8));
+
+        // Both copies of the format information are the same valid one.
+        let mut first = 0;
+        let mut second = 0;
+        for i in 0..15 {
+            let (x, y) = match i {
+                0..=5 => (8, i),
+                6 => (8, 7),
+                7 => (8, 8),
+                8 => (7, 8),
+                _ => (14 - i, 8),
+            };
+            first |= u32::from(qr.module(x, y)) << i;
+            let (x, y) = match i {
+                0..=7 => (qr.size - 1 - i, 8),
+                _ => (8, qr.size - 15 + i),
+            };
+            second |= u32::from(qr.module(x, y)) << i;
+        }
+        assert_eq!(first, second);
+        assert!((0..8).any(|mask| format_bits(mask) == first));
+
+        let rendered = qr.render();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), (qr.size + QUIET_ZONE * 2).div_ceil(2));
+        assert!(lines
+            .iter()
+            .all(|line| line.chars().filter(|c| " ▀▄█".contains(*c)).count() == 37));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{dirs, file};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// A running `serve` or `daemon` that other processes can attach to.
+#[derive(Deserialize, Serialize)]
+pub struct Instance {
+    pub pid: u32,
+    /// The ARN of the role whose credentials are provided.
+    pub role: Option<String>,
+    /// The server providing the credentials, if any.
+    pub server: Option<Server>,
+    /// The environment variables given to the commands to obtain the credentials.
+    pub env: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Server {
+    pub address: Address,
+    /// The token required to obtain the credentials.
+    pub token: String,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Address {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+/// Removes the registration of an instance when dropped.
+pub struct Registration {
+    path: PathBuf,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Returns the directory to keep the private files of the running instances in.
+pub fn dir() -> Result<PathBuf> {
+    dirs::runtime_dir().or_else(|_| dirs::cache_dir())
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        bail!("illegal instance name: `{name}`");
+    }
+    Ok(dir()?.join("instances").join(format!("{name}.json")))
+}
+
+/// Registers `instance` as `name`, failing if another instance with the name is running.
+pub async fn register(name: &str, instance: &Instance) -> Result<Registration> {
+    if find(name).await.is_ok() {
+        bail!("`{name}` is already running; specify another name with `--name`");
+    }
+
+    let path = path(name)?;
+    file::write_private(&path, &serde_json::to_vec(instance)?).await?;
+    Ok(Registration { path })
+}
+
+/// Returns the running instance registered as `name`.
+pub async fn find(name: &str) -> Result<Instance> {
+    let path = path(name)?;
+    let instance = tokio::fs::read(&path)
+        .await
+        .ok()
+        .and_then(|content| serde_json::from_slice::<Instance>(&content).ok())
+        .ok_or_else(|| anyhow!("`{name}` is not running"))?;
+
+    // The registration is left behind if the instance is killed.
+    if !is_running(instance.pid) {
+        let _ = tokio::fs::remove_file(&path).await;
+        bail!("`{name}` is not running");
+    }
+
+    Ok(instance)
+}
+
+/// Returns the running instances and their names.
+pub async fn list() -> Result<Vec<(String, Instance)>> {
+    let mut instances = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir()?.join("instances")).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(instances),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if let Ok(instance) = find(name).await {
+            instances.push((name.to_string(), instance));
+        }
+    }
+    instances.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(instances)
+}
+
+/// Returns whether the process `pid` is still running.
+pub fn is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    return unsafe { libc::kill(pid as libc::pid_t, 0) } == 0
+        || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    #[cfg(windows)]
+    return is_process_running(pid);
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+/// Returns whether the process `pid` exists and has not exited.
+#[cfg(windows)]
+fn is_process_running(pid: u32) -> bool {
+    use std::ffi::c_void;
+
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const STILL_ACTIVE: u32 = 259;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(access: u32, inherit_handle: i32, process_id: u32) -> *mut c_void;
+        fn GetExitCodeProcess(process: *mut c_void, exit_code: *mut u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            // A process of another user cannot be opened, but exists.
+            return std::io::Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED);
+        }
+        let mut exit_code = 0;
+        let running = GetExitCodeProcess(process, &mut exit_code) != 0 && exit_code == STILL_ACTIVE;
+        CloseHandle(process);
+        running
+    }
+}
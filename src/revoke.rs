@@ -0,0 +1,77 @@
+use crate::prompt;
+use anyhow::{bail, Result};
+use aws_config::SdkConfig;
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
+use std::time::SystemTime;
+
+/// The name of the inline policy, which is the same as the one attached by the IAM console so
+/// that either replaces the other.
+const POLICY_NAME: &str = "AWSRevokeOlderSessions";
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The name or the ARN of the role in the account of the current credentials whose sessions to
+    /// revoke.
+    #[arg(required_unless_present = "print_policy")]
+    role: Option<String>,
+
+    /// Print the policy that denies the sessions issued before now instead of attaching it.
+    #[arg(long)]
+    print_policy: bool,
+
+    /// Attach the policy without asking for confirmation.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+pub async fn run(config: &SdkConfig, args: Args) -> Result<()> {
+    // https://docs.aws.amazon.com/IAM/latest/UserGuide/id_roles_use_revoke-sessions.html
+    let now = DateTime::from(SystemTime::now());
+    let issued_before = DateTime::from_secs(now.secs()).fmt(Format::DateTime)?;
+    let policy = serde_json::json!({
+        "Version": "2012-10-17",
+        "Statement": [{
+            "Effect": "Deny",
+            "Action": ["*"],
+            "Resource": ["*"],
+            "Condition": {
+                "DateLessThan": { "aws:TokenIssueTime": issued_before },
+            },
+        }],
+    });
+    let policy = serde_json::to_string_pretty(&policy)?;
+
+    let Some(role) = args.role.filter(|_| !args.print_policy) else {
+        println!("{policy}");
+        return Ok(());
+    };
+
+    // The ARN of a role may contain its path.
+    let role_name = match role.split_once(":role/") {
+        Some((_, name)) => name.rsplit('/').next().unwrap_or(name),
+        None => role.as_str(),
+    };
+    if role_name.is_empty() {
+        bail!("`{role}` is not a role");
+    }
+
+    let message = format!(
+        "Do you want to deny all the sessions of `{role_name}` issued before {issued_before}, \
+         including the ones in use?"
+    );
+    if !args.yes && !prompt::confirm(&message).await? {
+        bail!("the policy was not attached");
+    }
+
+    aws_sdk_iam::Client::new(config)
+        .put_role_policy()
+        .role_name(role_name)
+        .policy_name(POLICY_NAME)
+        .policy_document(policy)
+        .send()
+        .await?;
+    eprintln!("Attached the inline policy `{POLICY_NAME}` to `{role_name}`");
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use crate::config::ConfigFile;
+use crate::{dirs, file, prompt};
+use anyhow::{anyhow, bail, Result};
+use aws_config::SdkConfig;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// How long the discovered roles are reused before they are listed again.
+const MAX_AGE: i64 = 24 * 60 * 60;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// List the roles with IAM even if they were listed recently.
+    #[arg(long)]
+    refresh: bool,
+}
+
+/// The roles discovered for a caller, cached locally.
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    /// The time the roles were listed, in seconds since the epoch.
+    fetched_at: i64,
+    roles: Vec<String>,
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, args: Args) -> Result<()> {
+    for role in discover(config, args.refresh).await? {
+        println!("{role}");
+    }
+    for chain in config_file.chains.keys() {
+        println!("{chain}");
+    }
+
+    Ok(())
+}
+
+/// Returns the ARNs of the roles discovered by `list-roles` for any caller, without calling AWS.
+pub async fn cached() -> Vec<String> {
+    let mut roles = Vec::new();
+    for entry in load().await.into_values() {
+        for role in entry.roles {
+            if !roles.contains(&role) {
+                roles.push(role);
+            }
+        }
+    }
+    roles
+}
+
+/// Lists the roles whose trust policy allows the caller to assume them.
+///
+/// Conditions of the trust policies are not evaluated, so `AssumeRole` may still be denied.
+async fn discover(config: &SdkConfig, refresh: bool) -> Result<Vec<String>> {
+    let identity = aws_sdk_sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await?;
+    let caller = Caller {
+        arn: identity
+            .arn()
+            .ok_or_else(|| anyhow!("caller ARN is not provided"))?,
+        account: identity
+            .account()
+            .ok_or_else(|| anyhow!("account is not provided"))?,
+    };
+
+    let mut cache = load().await;
+    if !refresh {
+        if let Some(entry) = cache.get(caller.arn) {
+            if Utc::now().timestamp() - entry.fetched_at < MAX_AGE {
+                return Ok(entry.roles.clone());
+            }
+        }
+    }
+
+    let iam = aws_sdk_iam::Client::new(config);
+    let mut stream = iam.list_roles().into_paginator().items().send();
+    let mut roles = Vec::new();
+    while let Some(role) = stream.next().await {
+        let role = role?;
+        let trusted = role
+            .assume_role_policy_document()
+            .and_then(|document| urlencoding::decode(document).ok())
+            .and_then(|document| serde_json::from_str(&document).ok())
+            .is_some_and(|policy| caller.is_trusted_by(&policy));
+        if trusted {
+            roles.push(role.arn().to_string());
+        }
+    }
+
+    cache.insert(
+        caller.arn.to_string(),
+        Entry {
+            fetched_at: Utc::now().timestamp(),
+            roles: roles.clone(),
+        },
+    );
+    if let Err(e) = file::write_private(&path()?, &serde_json::to_vec(&cache)?).await {
+        tracing::warn!("failed to cache the roles: {e:#}");
+    }
+
+    Ok(roles)
+}
+
+/// Returns the ARN of the role in the account of the caller that has all of `tags`.
+///
+/// If multiple roles match, the user is asked to choose one.
+pub async fn find_by_tags(config: &SdkConfig, tags: &[(String, String)]) -> Result<String> {
+    let iam = aws_sdk_iam::Client::new(config);
+    let mut stream = iam.list_roles().into_paginator().items().send();
+    let mut matches = Vec::new();
+    while let Some(role) = stream.next().await {
+        let role = role?;
+        // `ListRoles` does not return the tags of the roles.
+        let response = iam
+            .list_role_tags()
+            .role_name(role.role_name())
+            .send()
+            .await?;
+        let matched = tags.iter().all(|(key, value)| {
+            response
+                .tags()
+                .iter()
+                .any(|tag| tag.key() == key && tag.value() == value)
+        });
+        if matched {
+            matches.push(role.arn().to_string());
+        }
+    }
+
+    match matches.len() {
+        0 => bail!("no role has the tags"),
+        1 => Ok(matches.remove(0)),
+        _ => prompt::select("Role", matches).await,
+    }
+}
+
+fn path() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()?.join("roles.json"))
+}
+
+async fn load() -> BTreeMap<String, Entry> {
+    let Ok(path) = path() else {
+        return BTreeMap::new();
+    };
+    match tokio::fs::read(path).await {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_default(),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// The principal calling `AssumeRole`.
+struct Caller<'a> {
+    arn: &'a str,
+    account: &'a str,
+}
+
+impl Caller<'_> {
+    /// Returns whether a statement of the trust `policy` allows the caller to assume the role.
+    fn is_trusted_by(&self, policy: &Value) -> bool {
+        let statements = match &policy["Statement"] {
+            Value::Array(statements) => statements.iter().collect(),
+            statement => vec![statement],
+        };
+
+        statements.into_iter().any(|statement| {
+            statement["Effect"] == "Allow"
+                && strings(&statement["Action"])
+                    .any(|action| matches!(action, "sts:AssumeRole" | "sts:*" | "*"))
+                && match &statement["Principal"] {
+                    Value::String(principal) => principal == "*",
+                    principal => strings(&principal["AWS"]).any(|p| self.matches(p)),
+                }
+        })
+    }
+
+    fn matches(&self, principal: &str) -> bool {
+        if principal == "*" || principal == self.account || principal == self.arn {
+            return true;
+        }
+
+        let Some(account) = principal.split(':').nth(4) else {
+            return false;
+        };
+        if account != self.account {
+            return false;
+        }
+
+        // The account root trusts every principal of the account that IAM allows to assume the
+        // role, and a role principal trusts every session of the role.
+        let resource = principal.splitn(6, ':').nth(5).unwrap_or_default();
+        resource == "root"
+            || resource.strip_prefix("role/").is_some_and(|role| {
+                let name = role.rsplit('/').next().unwrap_or(role);
+                self.arn.contains(&format!(":assumed-role/{name}/"))
+            })
+    }
+}
+
+/// Iterates over a policy element that is either a string or an array of strings.
+fn strings(value: &Value) -> impl Iterator<Item = &str> {
+    let values = match value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+    values.into_iter().filter_map(Value::as_str)
+}
@@ -0,0 +1,181 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use base64::Engine as _;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+
+const ROLE_ATTRIBUTE: &str = "https://aws.amazon.com/SAML/Attributes/Role";
+
+/// The options that are not available when the SAML assertion is obtained from an IdP.
+pub const CONFLICTS: [&str; 8] = [
+    "saml_assertion",
+    "github_oidc",
+    "oidc",
+    "tag",
+    "transitive_tag_key",
+    "external_id",
+    "serial_number",
+    "source_identity",
+];
+
+/// A role that the SAML assertion permits to assume.
+#[derive(Debug)]
+pub struct Role {
+    pub role_arn: String,
+    pub principal_arn: String,
+}
+
+/// Returns the roles listed in the base64-encoded SAML `assertion`.
+pub fn roles(assertion: &str) -> Result<Vec<Role>> {
+    let xml = base64::engine::general_purpose::STANDARD
+        .decode(assertion.trim())
+        .context("malformed SAML assertion")?;
+    let xml = String::from_utf8(xml).context("malformed SAML assertion")?;
+
+    let mut roles = Vec::new();
+    let mut in_role = false;
+    let mut in_value = false;
+    let mut attribute_name = None;
+    for token in Tokenizer::from(xml.as_str()) {
+        match token.context("malformed SAML assertion")? {
+            Token::ElementStart { local, .. } if local.as_str() == "Attribute" => {
+                attribute_name = None;
+            }
+            Token::Attribute { local, value, .. }
+                if local.as_str() == "Name" && attribute_name.is_none() =>
+            {
+                attribute_name = Some(value.as_str() == ROLE_ATTRIBUTE);
+            }
+            Token::ElementEnd {
+                end: ElementEnd::Open,
+                ..
+            } if attribute_name.take() == Some(true) => in_role = true,
+            Token::ElementStart { local, .. } if in_role && local.as_str() == "AttributeValue" => {
+                in_value = true;
+            }
+            Token::Text { text } if in_value => {
+                // Either order of the role and the provider is accepted by AWS.
+                let value = text.as_str().trim();
+                let (first, second) = value
+                    .split_once(',')
+                    .ok_or_else(|| anyhow!("malformed role in SAML assertion: `{value}`"))?;
+                let (role_arn, principal_arn) = if first.contains(":saml-provider/") {
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+                roles.push(Role {
+                    role_arn: role_arn.trim().to_string(),
+                    principal_arn: principal_arn.trim().to_string(),
+                });
+            }
+            Token::ElementEnd {
+                end: ElementEnd::Close(_, local),
+                ..
+            } => match local.as_str() {
+                "AttributeValue" => in_value = false,
+                "Attribute" => in_role = false,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(roles)
+}
+
+/// Selects the role whose ARN or name is `role` from the roles in `assertion`.
+pub fn select(assertion: &str, role: &str) -> Result<Role> {
+    let roles = roles(assertion)?;
+    let names = roles
+        .iter()
+        .map(|r| r.role_arn.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    roles
+        .into_iter()
+        .find(|r| r.role_arn == role || r.role_arn.rsplit('/').next() == Some(role))
+        .ok_or_else(|| {
+            anyhow!("`{role}` is not permitted by the SAML assertion; available: {names}")
+        })
+}
+
+/// Extracts the value of the `SAMLResponse` input in the HTML form posted to AWS.
+pub fn form_value(html: &str) -> Result<String> {
+    for input in html.split("<input").skip(1) {
+        let tag = input.split('>').next().unwrap_or_default();
+        if attribute(tag, "name").as_deref() != Some("SAMLResponse") {
+            continue;
+        }
+
+        if let Some(value) = attribute(tag, "value") {
+            return Ok(value);
+        }
+    }
+
+    bail!("no SAML assertion in the response")
+}
+
+/// Returns the unescaped value of the HTML attribute `name` in `tag`.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!("{name}=");
+    let mut rest = tag;
+    while let Some(i) = rest.find(&pattern) {
+        let preceding = rest[..i].chars().next_back();
+        rest = &rest[i + pattern.len()..];
+        if preceding.is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = rest[1..].split(quote).next()?;
+        return Some(unescape(value));
+    }
+
+    None
+}
+
+/// Decodes the character references in `s`.
+fn unescape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        output.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => {
+                    let code = if let Some(hex) =
+                        entity.strip_prefix("#x").or(entity.strip_prefix("#X"))
+                    {
+                        u32::from_str_radix(hex, 16).ok()
+                    } else {
+                        entity.strip_prefix('#').and_then(|d| d.parse().ok())
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
@@ -0,0 +1,720 @@
+use crate::assume::{assume_role, AssumeArgs};
+use crate::cache::role_arn_of;
+use crate::config::ConfigFile;
+use crate::registry::{self, Address, Instance, Server};
+use crate::service::{self, ServiceManager};
+use crate::{attach, exec, file};
+use anyhow::{anyhow, bail, Context as _, Result};
+use aws_config::SdkConfig;
+use aws_sdk_sts::types::Credentials;
+use aws_smithy_types::date_time::Format;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::{mpsc, oneshot};
+
+/// How long before the expiration the credentials are renewed.
+pub const RENEW_BEFORE: Duration = Duration::from_secs(15 * 60);
+
+/// The shortest interval between the renewals of the credentials, even if they expire sooner.
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait before trying again to renew the credentials after failing.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The path for `attach` to obtain the credentials from, in the protocol of the container
+/// credentials provider.
+pub const ATTACH_PATH: &str = "/assume-role/credentials";
+
+/// The name of the role reported by the instance metadata service.
+const ROLE_NAME: &str = "assume-role";
+
+/// How long to wait for a server to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The maximum lifetime of a session token of IMDSv2 in seconds.
+const MAX_SESSION_TTL: u64 = 6 * 60 * 60;
+
+#[derive(clap::Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    control: Option<Control>,
+
+    #[command(flatten)]
+    assume: AssumeArgs,
+
+    /// The port of the credential server on the loopback interface. A free port is used if not
+    /// specified.
+    #[arg(long, value_name = "NUMBER", env = "ASSUME_ROLE_PORT")]
+    port: Option<u16>,
+
+    /// The name to attach to the server with.
+    #[arg(
+        long,
+        value_name = "NAME",
+        default_value = "default",
+        env = "ASSUME_ROLE_INSTANCE_NAME"
+    )]
+    name: String,
+
+    /// The protocol to provide the credentials with.
+    #[arg(long, value_enum, default_value_t, env = "ASSUME_ROLE_PROTOCOL")]
+    protocol: Protocol,
+
+    /// Serve the credentials on a unix domain socket only accessible by the current user instead of
+    /// TCP. The path of the socket is passed to the command in `ASSUME_ROLE_CREDENTIALS_SOCKET`.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "port", env = "ASSUME_ROLE_SOCKET")]
+    socket: bool,
+
+    /// Whether the instance metadata service requires the session token of IMDSv2, or also accepts
+    /// the requests of IMDSv1 without it.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STATE",
+        default_value_t,
+        env = "ASSUME_ROLE_HTTP_TOKENS"
+    )]
+    http_tokens: HttpTokens,
+
+    /// Keep serving the credentials without running a command until the server is stopped, as
+    /// the service written by `--install` does.
+    #[arg(long, conflicts_with_all = ["command", "command_string"])]
+    no_command: bool,
+
+    /// Write a service that runs the server with the same arguments at login instead of running it.
+    /// The server runs without a command, to be attached to by its `--name`.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MANAGER",
+        conflicts_with_all = ["command", "command_string"]
+    )]
+    install: Option<ServiceManager>,
+
+    #[command(flatten)]
+    options: exec::RunOptions,
+
+    /// A command and its arguments to run with the credential server. Runs current shell if not
+    /// specified.
+    command: Vec<String>,
+}
+
+/// The subcommands to control the running servers, which take precedence over a command of the
+/// same name; run it after `--` instead.
+#[derive(clap::Subcommand)]
+enum Control {
+    /// Show the role and the remaining lifetime of the credentials of the running servers.
+    Status(StatusArgs),
+
+    /// Stop a running server and its command.
+    Stop(StopArgs),
+}
+
+#[derive(clap::Args)]
+struct StatusArgs {
+    /// The name of the server. Shows all the running servers and daemons if not specified.
+    name: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct StopArgs {
+    /// The name of the server.
+    #[arg(default_value = "default")]
+    name: String,
+}
+
+/// The protocols of the endpoints that the SDKs obtain credentials from.
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum Protocol {
+    /// The instance metadata service of Amazon EC2, with the session token of IMDSv2.
+    #[default]
+    Imds,
+    /// The container credentials provider of Amazon ECS, with an authorization token. Takes
+    /// precedence over the instance metadata service in the SDKs.
+    Ecs,
+}
+
+/// The states of the session tokens of the instance metadata service, as the `HttpTokens` option
+/// of an Amazon EC2 instance.
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+enum HttpTokens {
+    /// Reject the requests without a session token.
+    #[default]
+    Required,
+    /// Accept the requests without a session token, though the ones with an invalid token are
+    /// still rejected.
+    Optional,
+}
+
+/// The endpoint of the server.
+struct Endpoint {
+    protocol: Protocol,
+    /// The token required to obtain the credentials, except from the instance metadata service.
+    token: String,
+    http_tokens: HttpTokens,
+    /// The session tokens issued by the instance metadata service and when they expire.
+    sessions: Mutex<HashMap<String, Instant>>,
+}
+
+/// The listener of the server.
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+/// A request for the current credentials from a connection to the server.
+type CredentialsRequest = oneshot::Sender<Credentials>;
+
+/// The credentials in the format of the instance metadata service, or of the container credentials
+/// provider without the fields specific to the former.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Document<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_updated: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    r#type: Option<&'a str>,
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    token: &'a str,
+    expiration: String,
+}
+
+impl Args {
+    /// Returns whether a subcommand to control the running servers is specified.
+    pub fn is_control(&self) -> bool {
+        self.control.is_some()
+    }
+}
+
+/// Runs the subcommand to control the running servers, which needs neither the config file nor
+/// the credentials.
+pub async fn control(args: Args) -> Result<()> {
+    match args.control {
+        Some(Control::Status(args)) => show_status(args).await,
+        Some(Control::Stop(args)) => stop(args).await,
+        None => bail!("no subcommand is specified"),
+    }
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, mut args: Args) -> Result<()> {
+    if let Some(manager) = args.install {
+        let description = format!("Serve the credentials of assume-role `{}`", args.name);
+        return service::install(manager, &args.name, &description, &["--no-command"]).await;
+    }
+
+    // The credentials must be assumed again, not taken from the cache, when they are renewed.
+    args.assume.require_min_ttl(RENEW_BEFORE);
+
+    let session = assume_role(config, config_file, args.assume.clone()).await?;
+    let credentials = session.credentials;
+    let assume = &args.assume;
+    let mut obtain = move || async move {
+        Ok(assume_role(config, config_file, assume.clone())
+            .await?
+            .credentials)
+    };
+
+    let token = token()?;
+    #[cfg(unix)]
+    let socket = if args.socket {
+        Some(socket_path().await?)
+    } else {
+        None
+    };
+    #[cfg(not(unix))]
+    let socket: Option<PathBuf> = None;
+
+    let (listener, address, mut env, location) = match &socket {
+        #[cfg(unix)]
+        Some(path) => {
+            let listener = UnixListener::bind(path)
+                .with_context(|| format!("failed to bind `{}`", path.display()))?;
+            std::fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+            let env = vec![(
+                "ASSUME_ROLE_CREDENTIALS_SOCKET",
+                path.to_string_lossy().into_owned(),
+            )];
+            let address = Address::Unix(path.clone());
+            (
+                Listener::Unix(listener),
+                address,
+                env,
+                path.display().to_string(),
+            )
+        }
+        _ => {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, args.port.unwrap_or(0))).await?;
+            let local_addr = listener.local_addr()?;
+            let url = format!("http://{local_addr}/");
+            let env = match args.protocol {
+                Protocol::Imds => vec![
+                    ("AWS_EC2_METADATA_SERVICE_ENDPOINT", url.clone()),
+                    ("AWS_EC2_METADATA_DISABLED", "false".to_string()),
+                ],
+                Protocol::Ecs => vec![("AWS_CONTAINER_CREDENTIALS_FULL_URI", url.clone())],
+            };
+            let address = Address::Tcp(local_addr.to_string());
+            (Listener::Tcp(listener), address, env, url)
+        }
+    };
+    if let Protocol::Ecs = args.protocol {
+        env.push(("AWS_CONTAINER_AUTHORIZATION_TOKEN", token.clone()));
+    }
+
+    let instance = Instance {
+        pid: std::process::id(),
+        role: session
+            .assumed_role_user
+            .and_then(|user| role_arn_of(&user.arn)),
+        server: Some(Server {
+            address,
+            token: token.clone(),
+        }),
+        env: env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+    };
+    let registration = registry::register(&args.name, &instance).await?;
+
+    let (sender, mut receiver) = mpsc::channel(16);
+    let endpoint = Endpoint {
+        protocol: args.protocol,
+        token,
+        http_tokens: args.http_tokens,
+        sessions: Mutex::default(),
+    };
+    tokio::spawn(accept(listener, Arc::new(endpoint), sender));
+    println!("Serving the credentials at {location}");
+
+    let result = tokio::select! {
+        result = async {
+            if args.no_command {
+                stopped().await?;
+                Ok(Some(ExitStatus::default()))
+            } else {
+                exec::execute_with_env(&env, &args.command, &args.options).await
+            }
+        } => result,
+        () = provide(&mut obtain, credentials, &mut receiver) => Err(anyhow!("the credential server stopped")),
+    };
+    if let Some(path) = &socket {
+        let _ = std::fs::remove_file(path);
+    }
+    drop(registration);
+    exec::exit(result?)
+}
+
+/// Waits for a signal to stop the server.
+#[cfg(unix)]
+async fn stopped() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::select! {
+        _ = interrupt.recv() => {}
+        _ = terminate.recv() => {}
+        _ = hangup.recv() => {}
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn stopped() -> Result<()> {
+    Ok(tokio::signal::ctrl_c().await?)
+}
+
+/// Shows the running instances.
+async fn show_status(args: StatusArgs) -> Result<()> {
+    let instances = match args.name {
+        Some(name) => vec![(name.clone(), registry::find(&name).await?)],
+        None => registry::list().await?,
+    };
+
+    let mut rows = Vec::new();
+    for (name, instance) in instances {
+        let (kind, remaining) = match &instance.server {
+            Some(server) => {
+                let remaining = match attach::fetch(server).await {
+                    Ok(credentials) => {
+                        let ttl = credentials.expiration.secs()
+                            - aws_smithy_types::DateTime::from(SystemTime::now()).secs();
+                        format!("{}h{:02}m{:02}s", ttl / 3600, ttl / 60 % 60, ttl % 60)
+                    }
+                    Err(e) => {
+                        tracing::warn!("failed to obtain the credentials from `{name}`: {e:#}");
+                        "-".to_string()
+                    }
+                };
+                ("serve", remaining)
+            }
+            None => ("daemon", "-".to_string()),
+        };
+        let role = instance.role.unwrap_or_else(|| "-".to_string());
+        rows.push((name, instance.pid.to_string(), kind, role, remaining));
+    }
+
+    let name_width = rows
+        .iter()
+        .map(|r| r.0.len())
+        .fold("NAME".len(), usize::max);
+    let pid_width = rows.iter().map(|r| r.1.len()).fold("PID".len(), usize::max);
+    let role_width = rows
+        .iter()
+        .map(|r| r.3.len())
+        .fold("ROLE ARN".len(), usize::max);
+    println!(
+        "{:name_width$}  {:pid_width$}  {:6}  {:role_width$}  REMAINING",
+        "NAME", "PID", "TYPE", "ROLE ARN"
+    );
+    for (name, pid, kind, role, remaining) in rows {
+        println!("{name:name_width$}  {pid:pid_width$}  {kind:6}  {role:role_width$}  {remaining}");
+    }
+
+    Ok(())
+}
+
+/// Stops a running instance by sending `SIGTERM`, which is forwarded to its command.
+async fn stop(args: StopArgs) -> Result<()> {
+    let instance = registry::find(&args.name).await?;
+
+    #[cfg(unix)]
+    {
+        if unsafe { libc::kill(instance.pid as libc::pid_t, libc::SIGTERM) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("failed to stop `{}`", args.name));
+        }
+
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while registry::is_running(instance.pid) {
+            if Instant::now() >= deadline {
+                bail!(
+                    "`{}` did not stop in {} seconds; its command may ignore `SIGTERM`",
+                    args.name,
+                    STOP_TIMEOUT.as_secs()
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        eprintln!("Stopped `{}`", args.name);
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = instance;
+        bail!("stopping a server is not supported on this platform")
+    }
+}
+
+/// Returns the path of the socket in a directory only accessible by the current user.
+#[cfg(unix)]
+async fn socket_path() -> Result<PathBuf> {
+    let dir = registry::dir()?;
+    file::create_private_dir(&dir).await?;
+    Ok(dir.join(format!("serve-{}.sock", std::process::id())))
+}
+
+/// Answers the requests for the credentials, and renews them before they expire for as long as the
+/// command runs.
+async fn provide<F, Fut>(
+    obtain: &mut F,
+    mut credentials: Credentials,
+    receiver: &mut mpsc::Receiver<CredentialsRequest>,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Credentials>>,
+{
+    let mut renew_at = renewal_time(&credentials);
+    loop {
+        let reply = tokio::select! {
+            request = receiver.recv() => match request {
+                Some(reply) => Some(reply),
+                None => return,
+            },
+            _ = tokio::time::sleep(renew_at.duration_since(SystemTime::now()).unwrap_or_default()) => None,
+        };
+
+        if renew_at <= SystemTime::now() {
+            match obtain().await {
+                Ok(renewed) => {
+                    credentials = renewed;
+                    renew_at = renewal_time(&credentials);
+                }
+                Err(e) => {
+                    tracing::warn!("failed to renew the credentials: {e:#}");
+                    renew_at = SystemTime::now() + RETRY_INTERVAL;
+                }
+            }
+        }
+        if let Some(reply) = reply {
+            let _ = reply.send(credentials.clone());
+        }
+    }
+}
+
+/// Returns the time to renew `credentials` obtained just now.
+pub fn renewal_time(credentials: &Credentials) -> SystemTime {
+    let now = SystemTime::now();
+    let expiration = SystemTime::try_from(credentials.expiration).unwrap_or(now);
+    renewal_time_at(expiration, now)
+}
+
+/// Returns the time to renew the credentials that expire at `expiration`, obtained at `now`:
+/// `RENEW_BEFORE` before the expiration, or halfway through a shorter lifetime so that they are
+/// not renewed again at once, and never sooner than `MIN_RENEW_INTERVAL` from `now`.
+fn renewal_time_at(expiration: SystemTime, now: SystemTime) -> SystemTime {
+    let lifetime = expiration.duration_since(now).unwrap_or_default();
+    let renew_in = lifetime - RENEW_BEFORE.min(lifetime / 2);
+    now + renew_in.max(MIN_RENEW_INTERVAL)
+}
+
+/// Accepts the connections to the server.
+async fn accept(
+    listener: Listener,
+    endpoint: Arc<Endpoint>,
+    sender: mpsc::Sender<CredentialsRequest>,
+) {
+    loop {
+        let (endpoint, sender) = (endpoint.clone(), sender.clone());
+        let result = match &listener {
+            Listener::Tcp(listener) => listener
+                .accept()
+                .await
+                .map(|(stream, _)| tokio::spawn(serve(stream, endpoint, sender))),
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener
+                .accept()
+                .await
+                .map(|(stream, _)| tokio::spawn(serve(stream, endpoint, sender))),
+        };
+        if let Err(e) = result {
+            tracing::warn!("failed to accept a connection: {e}");
+        }
+    }
+}
+
+async fn serve<S>(stream: S, endpoint: Arc<Endpoint>, sender: mpsc::Sender<CredentialsRequest>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let service = service_fn(move |request| {
+        let (endpoint, sender) = (endpoint.clone(), sender.clone());
+        async move {
+            let attach = request.uri().path() == ATTACH_PATH;
+            Ok::<_, Infallible>(match endpoint.protocol {
+                Protocol::Imds if !attach => imds(request, &endpoint, &sender).await,
+                _ => ecs(request, &endpoint.token, &sender).await,
+            })
+        }
+    });
+    if let Err(e) = Http::new()
+        .http1_only(true)
+        .serve_connection(stream, service)
+        .await
+    {
+        tracing::debug!("failed to serve a connection: {e}");
+    }
+}
+
+/// Handles a request to the instance metadata service, of which only the session token of IMDSv2
+/// and the credentials of the role are supported.
+async fn imds(
+    request: Request<Body>,
+    endpoint: &Endpoint,
+    sender: &mpsc::Sender<CredentialsRequest>,
+) -> Response<Body> {
+    // The instance metadata service rejects requests through proxies.
+    if request.headers().contains_key("x-forwarded-for") {
+        return status(StatusCode::FORBIDDEN);
+    }
+
+    let path = request.uri().path();
+    if path == "/latest/api/token" {
+        if request.method() != Method::PUT {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        let Some(ttl) = request
+            .headers()
+            .get("x-aws-ec2-metadata-token-ttl-seconds")
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+            .filter(|ttl| (1..=MAX_SESSION_TTL).contains(ttl))
+        else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        let Ok(session) = token() else {
+            return status(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+
+        let now = Instant::now();
+        let mut sessions = endpoint.sessions.lock().unwrap();
+        sessions.retain(|_, expires_at| *expires_at > now);
+        sessions.insert(session.clone(), now + Duration::from_secs(ttl));
+        return Response::builder()
+            .header("x-aws-ec2-metadata-token-ttl-seconds", ttl)
+            .body(Body::from(session))
+            .unwrap();
+    }
+
+    if request.method() != Method::GET {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    match request.headers().get("x-aws-ec2-metadata-token") {
+        Some(session) => {
+            let sessions = endpoint.sessions.lock().unwrap();
+            let valid = session
+                .to_str()
+                .ok()
+                .and_then(|session| sessions.get(session))
+                .is_some_and(|expires_at| *expires_at > Instant::now());
+            if !valid {
+                return status(StatusCode::UNAUTHORIZED);
+            }
+        }
+        None if matches!(endpoint.http_tokens, HttpTokens::Required) => {
+            return status(StatusCode::UNAUTHORIZED);
+        }
+        None => {}
+    }
+
+    match path.strip_prefix("/latest/meta-data/iam/security-credentials") {
+        Some("" | "/") => Response::new(Body::from(ROLE_NAME)),
+        Some(name) if name.strip_prefix('/') == Some(ROLE_NAME) => {
+            respond(sender, Protocol::Imds).await
+        }
+        _ => status(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Handles a request to the container credentials provider, which returns the credentials for any
+/// path.
+async fn ecs(
+    request: Request<Body>,
+    token: &str,
+    sender: &mpsc::Sender<CredentialsRequest>,
+) -> Response<Body> {
+    if request.method() != Method::GET {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    if !has_token(&request, "authorization", token) {
+        return status(StatusCode::UNAUTHORIZED);
+    }
+
+    respond(sender, Protocol::Ecs).await
+}
+
+fn has_token(request: &Request<Body>, header: &str, token: &str) -> bool {
+    request
+        .headers()
+        .get(header)
+        .is_some_and(|value| value.as_bytes() == token.as_bytes())
+}
+
+/// Returns the current credentials in the format of `protocol`.
+async fn respond(sender: &mpsc::Sender<CredentialsRequest>, protocol: Protocol) -> Response<Body> {
+    match credentials(sender)
+        .await
+        .and_then(|credentials| document(&credentials, protocol))
+    {
+        Ok(document) => Response::new(Body::from(document)),
+        Err(e) => {
+            tracing::warn!("failed to serve the credentials: {e:#}");
+            status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Asks the task providing the credentials for them.
+async fn credentials(sender: &mpsc::Sender<CredentialsRequest>) -> Result<Credentials> {
+    let (reply, receiver) = oneshot::channel();
+    sender
+        .send(reply)
+        .await
+        .map_err(|_| anyhow!("the credentials are no longer provided"))?;
+    Ok(receiver.await?)
+}
+
+fn document(credentials: &Credentials, protocol: Protocol) -> Result<String> {
+    let imds = matches!(protocol, Protocol::Imds);
+    let last_updated = aws_smithy_types::DateTime::from(SystemTime::now()).fmt(Format::DateTime)?;
+    Ok(serde_json::to_string(&Document {
+        code: imds.then_some("Success"),
+        last_updated: imds.then_some(last_updated),
+        r#type: imds.then_some("AWS-HMAC"),
+        access_key_id: credentials.access_key_id(),
+        secret_access_key: credentials.secret_access_key(),
+        token: credentials.session_token(),
+        expiration: credentials.expiration().fmt(Format::DateTime)?,
+    })?)
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Generates a random token to authorize the requests with.
+fn token() -> Result<String> {
+    let mut token = [0; 32];
+    ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut token)
+        .map_err(|_| anyhow!("failed to generate a token"))?;
+    Ok(hex::encode(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renew_in(lifetime: Duration) -> Duration {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        renewal_time_at(now + lifetime, now)
+            .duration_since(now)
+            .unwrap()
+    }
+
+    #[test]
+    fn renewal() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        assert_eq!(renew_in(minutes(60)), minutes(45));
+        assert_eq!(renew_in(minutes(12 * 60)), minutes(12 * 60 - 15));
+        assert_eq!(renew_in(minutes(30)), minutes(15));
+        // Shorter lifetimes are renewed halfway.
+        assert_eq!(renew_in(minutes(20)), minutes(10));
+        assert_eq!(renew_in(minutes(15)), Duration::from_secs(450));
+        // But not more often than the minimum interval.
+        assert_eq!(renew_in(minutes(1)), MIN_RENEW_INTERVAL);
+        assert_eq!(renew_in(Duration::ZERO), MIN_RENEW_INTERVAL);
+    }
+
+    #[test]
+    fn renewal_of_expired() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            renewal_time_at(now - Duration::from_secs(10), now),
+            now + MIN_RENEW_INTERVAL
+        );
+    }
+}
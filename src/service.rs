@@ -0,0 +1,491 @@
+//! Services that run assume-role at login, written for the service manager of the platform.
+
+use crate::{dirs, file};
+use anyhow::{bail, Context as _, Result};
+use std::path::{Path, PathBuf};
+
+/// The options whose values are secrets, which are left out of a service.
+const SECRET_OPTIONS: &[&str] = &["--token-code"];
+
+/// The options whose values may be relative paths, which are made absolute since a service runs in
+/// another directory.
+const PATH_OPTIONS: &[&str] = &[
+    "--policy",
+    "-p",
+    "--tags-file",
+    "--saml-assertion",
+    "--cache-dir",
+    "--cwd",
+];
+
+/// The environment variables of `PATH_OPTIONS` and of the AWS SDKs whose values are paths.
+const PATH_VARIABLES: &[&str] = &[
+    "ASSUME_ROLE_POLICY",
+    "ASSUME_ROLE_TAGS_FILE",
+    "ASSUME_ROLE_SAML_ASSERTION",
+    "ASSUME_ROLE_CACHE_DIR",
+    "ASSUME_ROLE_CWD",
+    "ASSUME_ROLE_CONFIG_FILE",
+    "AWS_CONFIG_FILE",
+    "AWS_SHARED_CREDENTIALS_FILE",
+];
+
+/// The environment variables of the AWS SDKs that select the source credentials without being
+/// secrets themselves.
+const AWS_VARIABLES: &[&str] = &[
+    "AWS_PROFILE",
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+    "AWS_CONFIG_FILE",
+    "AWS_SHARED_CREDENTIALS_FILE",
+];
+
+/// The service managers that can run assume-role at login.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ServiceManager {
+    /// A user unit of systemd.
+    Systemd,
+    /// A launch agent of launchd on macOS.
+    Launchd,
+}
+
+/// Writes a service named after `name` that runs this executable with the current arguments except
+/// `--install` and the secrets, followed by `extra_args`, and with the current `ASSUME_ROLE_*`
+/// environment variables.
+pub async fn install(
+    manager: ServiceManager,
+    name: &str,
+    description: &str,
+    extra_args: &[&str],
+) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let mut arguments = vec![std::env::current_exe()?.to_string_lossy().into_owned()];
+    arguments.extend(service_args(std::env::args().skip(1), &cwd)?);
+    arguments.extend(extra_args.iter().map(|arg| arg.to_string()));
+    let env = service_env(std::env::vars(), &cwd)?;
+
+    let (path, content, hint) = match manager {
+        ServiceManager::Systemd => {
+            let base = match std::env::var_os("XDG_CONFIG_HOME") {
+                Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+                _ => dirs::home_dir()?.join(".config"),
+            };
+            let unit = format!("assume-role-{name}.service");
+            let content = systemd_unit(description, &arguments, &env);
+            let hint = format!("systemctl --user enable --now {unit}");
+            (base.join("systemd").join("user").join(unit), content, hint)
+        }
+        ServiceManager::Launchd => {
+            let label = format!("com.github.dacci.assume-role.{name}");
+            let content = launchd_plist(&label, &arguments, &env);
+            let path = dirs::home_dir()?
+                .join("Library")
+                .join("LaunchAgents")
+                .join(format!("{label}.plist"));
+            let hint = format!("launchctl load -w {}", path.display());
+            (path, content, hint)
+        }
+    };
+
+    if let Some(parent) = path.parent() {
+        file::create_private_dir(parent).await?;
+    }
+    tokio::fs::write(&path, content)
+        .await
+        .with_context(|| format!("failed to write `{}`", path.display()))?;
+    eprintln!("Wrote `{}`; enable it with `{hint}`", path.display());
+
+    Ok(())
+}
+
+/// Returns `args` without `--install` and the secrets, and with the paths made absolute against
+/// `cwd`.
+fn service_args(args: impl IntoIterator<Item = String>, cwd: &Path) -> Result<Vec<String>> {
+    let mut result = Vec::new();
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            result.push(arg);
+            result.extend(iter);
+            break;
+        }
+
+        let (option, value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value.to_string())),
+            _ if arg.starts_with('-') && !arg.starts_with("--") && arg.len() > 2 => {
+                // A short option may be followed by its value, with or without `=`.
+                let (option, value) = arg.split_at(2);
+                (
+                    option,
+                    Some(value.strip_prefix('=').unwrap_or(value).to_string()),
+                )
+            }
+            _ => (arg.as_str(), None),
+        };
+        let takes_value = option == "--install"
+            || SECRET_OPTIONS.contains(&option)
+            || PATH_OPTIONS.contains(&option);
+        if !takes_value {
+            result.push(arg);
+            continue;
+        }
+        let Some(value) = value.or_else(|| iter.next()) else {
+            continue;
+        };
+
+        if option == "--install" {
+            continue;
+        }
+        if SECRET_OPTIONS.contains(&option) {
+            eprintln!("Left `{option}` out of the service, which cannot keep secrets");
+            continue;
+        }
+        result.push(option.to_string());
+        result.push(absolute_path(option, &value, cwd)?);
+    }
+    Ok(result)
+}
+
+/// Returns the variables of `vars` to set in a service: the ones of assume-role except the secrets
+/// and the ones of the AWS SDKs that select the source credentials, with the paths made absolute
+/// against `cwd`.
+fn service_env(
+    vars: impl IntoIterator<Item = (String, String)>,
+    cwd: &Path,
+) -> Result<Vec<(String, String)>> {
+    let mut env = Vec::new();
+    for (name, value) in vars {
+        let relevant = (name.starts_with("ASSUME_ROLE_")
+            && name != "ASSUME_ROLE_TOKEN_CODE"
+            && name != "ASSUME_ROLE_CREDENTIALS_SOCKET")
+            || AWS_VARIABLES.contains(&name.as_str());
+        if !relevant {
+            continue;
+        }
+        let value = if PATH_VARIABLES.contains(&name.as_str()) {
+            absolute_path(&name, &value, cwd)?
+        } else {
+            value
+        };
+        env.push((name, value));
+    }
+    env.sort();
+    Ok(env)
+}
+
+/// Returns `value` of `option` made absolute against `cwd` if it is a relative path, and not an
+/// inline policy.
+fn absolute_path(option: &str, value: &str, cwd: &Path) -> Result<String> {
+    if value == "-" {
+        bail!("`{option}` cannot read the standard input in a service");
+    }
+    if value.is_empty() || value.trim_start().starts_with('{') || Path::new(value).is_absolute() {
+        return Ok(value.to_string());
+    }
+    Ok(cwd.join(value).to_string_lossy().into_owned())
+}
+
+/// Returns a user unit of systemd that runs `arguments` with `env`.
+fn systemd_unit(description: &str, arguments: &[String], env: &[(String, String)]) -> String {
+    let exec_start = arguments
+        .iter()
+        .map(|arg| systemd_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let environment = env
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "Environment={}\n",
+                systemd_env_quote(&format!("{name}={value}"))
+            )
+        })
+        .collect::<String>();
+    format!(
+        "[Unit]\n\
+         Description={}\n\
+         \n\
+         [Service]\n\
+         {environment}\
+         ExecStart={exec_start}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        description.replace('%', "%%")
+    )
+}
+
+/// Returns a launch agent of launchd labeled `label` that runs `arguments` with `env`.
+fn launchd_plist(label: &str, arguments: &[String], env: &[(String, String)]) -> String {
+    let program_arguments = arguments
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+        .collect::<String>();
+    let environment = if env.is_empty() {
+        String::new()
+    } else {
+        let variables = env
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "        <key>{}</key>\n        <string>{}</string>\n",
+                    xml_escape(name),
+                    xml_escape(value)
+                )
+            })
+            .collect::<String>();
+        format!("    <key>EnvironmentVariables</key>\n    <dict>\n{variables}    </dict>\n")
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+{program_arguments}    </array>
+{environment}    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+</dict>
+</plist>
+"#,
+        xml_escape(label)
+    )
+}
+
+/// Quotes `arg` for a command line of a systemd unit, where `$` is expanded.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%");
+    if !escaped.is_empty()
+        && !escaped.chars().any(|c| {
+            c.is_whitespace() || c.is_control() || matches!(c, '"' | '\'' | '\\' | ';' | '$')
+        })
+    {
+        return escaped;
+    }
+
+    format!("\"{}\"", c_escape(&escaped).replace('$', "$$"))
+}
+
+/// Quotes an assignment of `Environment=` of a systemd unit, where `$` is not expanded.
+fn systemd_env_quote(assignment: &str) -> String {
+    format!("\"{}\"", c_escape(&assignment.replace('%', "%%")))
+}
+
+/// Escapes the backslashes, the double quotes and the control characters of `s` as in C.
+fn c_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn quote_exec_args() {
+        assert_eq!(systemd_quote("plain"), "plain");
+        assert_eq!(systemd_quote("--name=prod"), "--name=prod");
+        assert_eq!(systemd_quote("50%"), "50%%");
+        assert_eq!(systemd_quote(""), r#""""#);
+        assert_eq!(systemd_quote("a b"), r#""a b""#);
+        assert_eq!(systemd_quote("a;b"), r#""a;b""#);
+        assert_eq!(systemd_quote("it's"), r#""it's""#);
+        assert_eq!(
+            systemd_quote(r#"say "hi" to $HOME\"#),
+            r#""say \"hi\" to $$HOME\\""#
+        );
+        assert_eq!(systemd_quote("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(systemd_quote("\x1b%"), r#""\x1b%%""#);
+    }
+
+    #[test]
+    fn quote_env() {
+        assert_eq!(systemd_env_quote("A=b"), r#""A=b""#);
+        assert_eq!(systemd_env_quote("A=$b 5%"), r#""A=$b 5%%""#);
+        assert_eq!(systemd_env_quote(r#"A="b\c""#), r#""A=\"b\\c\"""#);
+    }
+
+    #[test]
+    fn args_without_install_and_secrets() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            service_args(
+                args(&[
+                    "serve",
+                    "--install",
+                    "systemd",
+                    "-r",
+                    "prod",
+                    "--token-code",
+                    "123456",
+                    "--name=prod",
+                    "--token-code=654321",
+                    "--install=launchd",
+                ]),
+                cwd
+            )
+            .unwrap(),
+            args(&["serve", "-r", "prod", "--name=prod"])
+        );
+    }
+
+    #[test]
+    fn args_with_absolute_paths() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            service_args(
+                args(&[
+                    "serve",
+                    "-p",
+                    "policy.json",
+                    "-p../shared.yaml",
+                    "-p=/etc/policy.json",
+                    "--policy",
+                    r#"{"Version":"2012-10-17"}"#,
+                    "--tags-file=tags.json",
+                    "--cache-dir",
+                    "cache",
+                    "-r",
+                    "a/b",
+                ]),
+                cwd
+            )
+            .unwrap(),
+            args(&[
+                "serve",
+                "-p",
+                "/work/policy.json",
+                "-p",
+                "/work/../shared.yaml",
+                "-p",
+                "/etc/policy.json",
+                "--policy",
+                r#"{"Version":"2012-10-17"}"#,
+                "--tags-file",
+                "/work/tags.json",
+                "--cache-dir",
+                "/work/cache",
+                "-r",
+                "a/b",
+            ])
+        );
+    }
+
+    #[test]
+    fn args_after_separator() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            service_args(args(&["serve", "--", "-p", "x", "--install"]), cwd).unwrap(),
+            args(&["serve", "--", "-p", "x", "--install"])
+        );
+    }
+
+    #[test]
+    fn args_from_stdin() {
+        let cwd = Path::new("/work");
+        assert!(service_args(args(&["serve", "-p", "-"]), cwd).is_err());
+        assert!(service_args(args(&["serve", "--saml-assertion=-"]), cwd).is_err());
+        // The secret is left out before it would be read.
+        assert!(service_args(args(&["serve", "--token-code", "-"]), cwd).is_ok());
+    }
+
+    #[test]
+    fn env() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("AWS_REGION", "us-east-1"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+            ("AWS_CONFIG_FILE", "aws/config"),
+            ("ASSUME_ROLE_TOKEN_CODE", "123456"),
+            ("ASSUME_ROLE_CREDENTIALS_SOCKET", "/run/serve.sock"),
+            ("ASSUME_ROLE_TAGS_FILE", "tags.json"),
+            ("ASSUME_ROLE_ROLE", "prod"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        assert_eq!(
+            service_env(vars, Path::new("/work")).unwrap(),
+            [
+                ("ASSUME_ROLE_ROLE", "prod"),
+                ("ASSUME_ROLE_TAGS_FILE", "/work/tags.json"),
+                ("AWS_CONFIG_FILE", "/work/aws/config"),
+                ("AWS_REGION", "us-east-1"),
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn unit() {
+        let unit = systemd_unit(
+            "Serve `prod`",
+            &args(&["/bin/assume-role", "serve", "-r", "a b"]),
+            &[("AWS_REGION".to_string(), "us-east-1".to_string())],
+        );
+        assert_eq!(
+            unit,
+            "[Unit]\n\
+             Description=Serve `prod`\n\
+             \n\
+             [Service]\n\
+             Environment=\"AWS_REGION=us-east-1\"\n\
+             ExecStart=/bin/assume-role serve -r \"a b\"\n\
+             Restart=on-failure\n\
+             \n\
+             [Install]\n\
+             WantedBy=default.target\n"
+        );
+    }
+
+    #[test]
+    fn plist() {
+        let plist = launchd_plist(
+            "com.github.dacci.assume-role.prod",
+            &args(&["/bin/assume-role", "-r", "a&b"]),
+            &[("AWS_REGION".to_string(), "us-east-1".to_string())],
+        );
+        assert!(plist.contains(
+            "    <array>\n\
+             \x20       <string>/bin/assume-role</string>\n\
+             \x20       <string>-r</string>\n\
+             \x20       <string>a&amp;b</string>\n\
+             \x20   </array>\n\
+             \x20   <key>EnvironmentVariables</key>\n\
+             \x20   <dict>\n\
+             \x20       <key>AWS_REGION</key>\n\
+             \x20       <string>us-east-1</string>\n\
+             \x20   </dict>\n\
+             \x20   <key>RunAtLoad</key>\n"
+        ));
+        assert!(!launchd_plist("label", &[], &[]).contains("EnvironmentVariables"));
+    }
+}
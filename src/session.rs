@@ -0,0 +1,102 @@
+use crate::assume::Session;
+use crate::config::ConfigFile;
+use crate::{cache, exec, mfa, source};
+use anyhow::Result;
+use aws_config::SdkConfig;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    mfa: mfa::MfaArgs,
+
+    /// The duration, in seconds, that the credentials should remain valid.
+    #[arg(long, value_name = "NUMBER", env = "ASSUME_ROLE_DURATION_SECONDS")]
+    duration_seconds: Option<i32>,
+
+    #[command(flatten)]
+    cache: cache::Options,
+
+    #[command(flatten)]
+    exec: exec::RunOptions,
+
+    /// A command and its arguments to run with the credentials. Runs current shell if not specified.
+    command: Vec<String>,
+}
+
+/// The parameters that identify a `GetSessionToken` request in the cache.
+#[derive(serde::Serialize)]
+struct Parameters<'a> {
+    access_key_id: Option<&'a str>,
+    duration_seconds: Option<i32>,
+    serial_number: Option<&'a str>,
+}
+
+pub async fn run(config: &SdkConfig, config_file: &ConfigFile, args: Args) -> Result<()> {
+    let (mfa, duration_seconds, cache) = (&args.mfa, args.duration_seconds, &args.cache);
+    exec::execute(
+        move || async move {
+            let session =
+                get_session_token(config, config_file, mfa.clone(), duration_seconds, cache)
+                    .await?;
+            Ok(session.credentials)
+        },
+        &args.command,
+        &args.exec,
+    )
+    .await
+}
+
+async fn get_session_token(
+    config: &SdkConfig,
+    config_file: &ConfigFile,
+    mut mfa: mfa::MfaArgs,
+    duration_seconds: Option<i32>,
+    options: &cache::Options,
+) -> Result<Session> {
+    mfa.apply_defaults(&config_file.mfa);
+
+    let access_key_id = source::access_key_id(config).await?;
+
+    let cache = options.local(config_file)?;
+    let key = cache::key(&Parameters {
+        access_key_id: access_key_id.as_deref(),
+        duration_seconds,
+        serial_number: mfa.serial_number.as_deref(),
+    })?;
+
+    let (session, _lock) = options.lookup(&cache, &key).await?;
+    if let Some(session) = session {
+        return Ok(session);
+    }
+
+    let mut mfa = mfa.resolve(config, config_file).await?;
+
+    let sts = aws_sdk_sts::Client::new(config);
+    let request = sts
+        .get_session_token()
+        .set_duration_seconds(duration_seconds)
+        .set_serial_number(mfa.serial_number.clone());
+
+    let response = loop {
+        match request
+            .clone()
+            .set_token_code(mfa.token_code.clone())
+            .send()
+            .await
+        {
+            Ok(response) => break response,
+            Err(e) if mfa.retry(&e).await? => continue,
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    let session = Session::new(response.credentials, None, None, None)?;
+
+    if !options.no_cache {
+        if let Err(e) = cache.store(&key, None, &session).await {
+            tracing::warn!("failed to cache credentials: {e:#}");
+        }
+    }
+
+    Ok(session)
+}
@@ -0,0 +1,218 @@
+use crate::config::Preset;
+use anyhow::{anyhow, bail, Context as _, Result};
+use aws_config::profile::profile_file::ProfileFiles;
+use aws_config::profile::{Profile, ProfileSet};
+use aws_config::sso::SsoCredentialsProvider;
+use aws_config::{BehaviorVersion, Region, SdkConfig};
+use aws_sdk_sts::config::{ProvideCredentials as _, SharedCredentialsProvider};
+use aws_types::os_shim_internal::{Env, Fs};
+
+#[derive(clap::Args)]
+pub struct SourceArgs {
+    /// The profile in the shared config files that defines the role to assume with `role_arn`,
+    /// `source_profile`, `mfa_serial`, `external_id`, `duration_seconds` and `role_session_name`.
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        conflicts_with_all = ["source_profile", "sso_start_url"],
+        env = "ASSUME_ROLE_PROFILE"
+    )]
+    profile: Option<String>,
+
+    /// The profile in the shared config files that provides the source credentials, the region and
+    /// the default MFA device.
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        conflicts_with = "sso_start_url",
+        env = "ASSUME_ROLE_SOURCE_PROFILE"
+    )]
+    source_profile: Option<String>,
+
+    /// The URL of the AWS access portal of IAM Identity Center to obtain the source credentials from.
+    /// The access token cached by `aws sso login` is used.
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        requires_all = ["sso_account_id", "sso_role_name"],
+        env = "ASSUME_ROLE_SSO_START_URL"
+    )]
+    sso_start_url: Option<String>,
+
+    /// The name of the `sso-session` section in the AWS config file. Enables refreshing the cached access token.
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        requires = "sso_start_url",
+        env = "ASSUME_ROLE_SSO_SESSION"
+    )]
+    sso_session: Option<String>,
+
+    /// The region of IAM Identity Center. Defaults to the region of the SDK.
+    #[arg(
+        long,
+        global = true,
+        value_name = "REGION",
+        requires = "sso_start_url",
+        env = "ASSUME_ROLE_SSO_REGION"
+    )]
+    sso_region: Option<String>,
+
+    /// The ID of the account that the permission set is provisioned to.
+    #[arg(
+        long,
+        global = true,
+        value_name = "ID",
+        requires = "sso_start_url",
+        env = "ASSUME_ROLE_SSO_ACCOUNT_ID"
+    )]
+    sso_account_id: Option<String>,
+
+    /// The name of the permission set to obtain the source credentials of.
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        requires = "sso_start_url",
+        env = "ASSUME_ROLE_SSO_ROLE_NAME"
+    )]
+    sso_role_name: Option<String>,
+}
+
+impl SourceArgs {
+    /// Loads the configuration of the SDK with the selected credentials.
+    pub async fn load(&self) -> Result<SdkConfig> {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(profile) = self.source_profile().await? {
+            loader = loader.profile_name(profile);
+        }
+        let config = loader.load().await;
+
+        let (Some(start_url), Some(account_id), Some(role_name)) = (
+            &self.sso_start_url,
+            &self.sso_account_id,
+            &self.sso_role_name,
+        ) else {
+            return Ok(config);
+        };
+
+        let region = self
+            .sso_region
+            .clone()
+            .map(Region::new)
+            .or_else(|| config.region().cloned())
+            .ok_or_else(|| anyhow!("the region of IAM Identity Center is not specified"))?;
+
+        let mut builder = SsoCredentialsProvider::builder()
+            .start_url(start_url)
+            .region(region)
+            .account_id(account_id)
+            .role_name(role_name);
+        builder.set_session_name(self.sso_session.clone());
+
+        Ok(config
+            .into_builder()
+            .credentials_provider(SharedCredentialsProvider::new(builder.build()))
+            .build())
+    }
+
+    /// Returns `mfa_serial` of the source profile, if selected.
+    pub async fn mfa_serial(&self) -> Result<Option<String>> {
+        let Some(name) = self.source_profile().await? else {
+            return Ok(None);
+        };
+
+        let profile = load_profile(&name).await?;
+        Ok(profile.get("mfa_serial").map(str::to_string))
+    }
+
+    /// Returns the preset of the role defined by `--profile`, named after the profile.
+    pub async fn preset(&self) -> Result<Option<(String, Preset)>> {
+        let Some(name) = &self.profile else {
+            return Ok(None);
+        };
+
+        let profile = load_profile(name).await?;
+        Ok(preset_of(&profile)?.map(|preset| (name.clone(), preset)))
+    }
+
+    /// Returns the profile that provides the source credentials.
+    ///
+    /// The profile of `--profile` is used itself unless it assumes a role, in which case its
+    /// `source_profile` is used instead since the role is assumed by this application.
+    async fn source_profile(&self) -> Result<Option<String>> {
+        let Some(name) = &self.profile else {
+            return Ok(self.source_profile.clone());
+        };
+
+        let profiles = load_profiles().await?;
+        let profile = profiles
+            .get_profile(name)
+            .ok_or_else(|| anyhow!("profile `{name}` is not found"))?;
+        if profile.get("role_arn").is_none() {
+            return Ok(Some(name.clone()));
+        }
+        match profile.get("source_profile") {
+            Some(source) if profiles.get_profile(source).is_some() => Ok(Some(source.to_string())),
+            Some(source) => bail!(
+                "`source_profile` of profile `{name}` refers to `{source}`, which is not found"
+            ),
+            None => bail!("profile `{name}` has no `source_profile`"),
+        }
+    }
+}
+
+/// Converts a profile that assumes a role with `role_arn` into a preset.
+pub fn preset_of(profile: &Profile) -> Result<Option<Preset>> {
+    let Some(role_arn) = profile.get("role_arn") else {
+        return Ok(None);
+    };
+
+    let duration_seconds = profile
+        .get("duration_seconds")
+        .map(str::parse)
+        .transpose()
+        .with_context(|| format!("invalid `duration_seconds` of profile `{}`", profile.name()))?;
+    Ok(Some(Preset {
+        role: role_arn.to_string(),
+        role_session_name: profile.get("role_session_name").map(str::to_string),
+        duration_seconds,
+        external_id: profile.get("external_id").map(str::to_string),
+        serial_number: profile.get("mfa_serial").map(str::to_string),
+        ..Default::default()
+    }))
+}
+
+/// Loads all profiles of the shared config files.
+pub async fn load_profiles() -> Result<ProfileSet> {
+    aws_config::profile::load(&Fs::real(), &Env::real(), &ProfileFiles::default(), None)
+        .await
+        .context("failed to load the shared config files")
+}
+
+async fn load_profile(name: &str) -> Result<Profile> {
+    load_profiles()
+        .await?
+        .get_profile(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("profile `{name}` is not found"))
+}
+
+/// Returns the access key ID of the source credentials, which identifies the sessions derived from
+/// them.
+pub async fn access_key_id(config: &SdkConfig) -> Result<Option<String>> {
+    match config.credentials_provider() {
+        Some(provider) => Ok(Some(
+            provider
+                .provide_credentials()
+                .await?
+                .access_key_id()
+                .to_string(),
+        )),
+        None => Ok(None),
+    }
+}
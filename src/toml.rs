@@ -0,0 +1,800 @@
+//! A parser and a serializer of TOML, covering what the config file needs.
+//!
+//! Documents are represented as JSON values. Date-times are kept as strings.
+
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+
+/// A parsed TOML document with the lines where its values are defined.
+pub struct Document {
+    pub table: Map<String, Value>,
+
+    /// The index of the line of each value, keyed by its path such as `presets.prod.role` or
+    /// `include[0].url`.
+    pub lines: HashMap<String, usize>,
+
+    /// The path and the index of the line of each table header, in order.
+    pub headers: Vec<(String, usize)>,
+
+    /// Whether the document has comments, which [`to_string`] drops.
+    pub comments: bool,
+}
+
+/// Parses `content`, returning the error prefixed with its line.
+pub fn parse(content: &str) -> Result<Document, String> {
+    let mut parser = Parser {
+        chars: content.chars().collect(),
+        pos: 0,
+        line: 0,
+        lines: HashMap::new(),
+        headers: Vec::new(),
+        comments: false,
+    };
+    let table = parser
+        .document()
+        .map_err(|e| format!("line {}: {e}", parser.line + 1))?;
+    Ok(Document {
+        table,
+        lines: parser.lines,
+        headers: parser.headers,
+        comments: parser.comments,
+    })
+}
+
+/// Serializes `table` as a TOML document. Null values are omitted.
+pub fn to_string(table: &Map<String, Value>) -> String {
+    let mut output = String::new();
+    write_table(&mut output, &mut Vec::new(), table);
+    output
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    lines: HashMap<String, usize>,
+    headers: Vec<(String, usize)>,
+    comments: bool,
+}
+
+impl Parser {
+    fn document(&mut self) -> Result<Map<String, Value>, String> {
+        let mut root = Map::new();
+        // The path of the current table and the name of its path, with the indices of the arrays
+        // of tables.
+        let mut current = Vec::new();
+        let mut name = String::new();
+        let mut defined = Vec::new();
+
+        loop {
+            self.skip_blank_lines();
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    let line = self.line;
+                    self.pos += 1;
+                    let array = self.eat('[');
+                    self.skip_spaces();
+                    current = self.key()?;
+                    self.skip_spaces();
+                    if !self.eat(']') || (array && !self.eat(']')) {
+                        return Err("unterminated table header".to_string());
+                    }
+                    self.end_of_line()?;
+
+                    let (table, mut path) = descend(&mut root, &current[..current.len() - 1])
+                        .map_err(|e| format!("{e} in `{}`", current.join(".")))?;
+                    let last = current.last().unwrap();
+                    push_key(&mut path, last);
+                    let entry = table.entry(last.clone());
+                    if array {
+                        match entry.or_insert_with(|| Value::Array(Vec::new())) {
+                            Value::Array(items) if items.iter().all(Value::is_object) => {
+                                path.push_str(&format!("[{}]", items.len()));
+                                items.push(Value::Object(Map::new()));
+                            }
+                            _ => {
+                                let name = current.join(".");
+                                return Err(format!("`{name}` is not an array of tables"));
+                            }
+                        }
+                    } else {
+                        if defined.contains(&path) {
+                            return Err(format!("table `{}` is defined twice", current.join(".")));
+                        }
+                        if !entry
+                            .or_insert_with(|| Value::Object(Map::new()))
+                            .is_object()
+                        {
+                            return Err(format!("`{}` is not a table", current.join(".")));
+                        }
+                        defined.push(path.clone());
+                    }
+                    self.lines.insert(path.clone(), line);
+                    self.headers.push((path.clone(), line));
+                    name = path;
+                }
+                Some(_) => {
+                    let (table, _) = descend(&mut root, &current)?;
+                    self.key_value(table, &name)?;
+                    self.end_of_line()?;
+                }
+            }
+        }
+
+        Ok(root)
+    }
+
+    /// Parses `key = value` into `table`, whose path is `name`.
+    fn key_value(&mut self, table: &mut Map<String, Value>, name: &str) -> Result<(), String> {
+        let line = self.line;
+        let keys = self.key()?;
+        self.skip_spaces();
+        if !self.eat('=') {
+            return Err(format!("expected `=` after `{}`", keys.join(".")));
+        }
+        self.skip_spaces();
+
+        // Dotted keys define the intermediate tables.
+        let mut table = table;
+        let mut path = name.to_string();
+        for key in &keys[..keys.len() - 1] {
+            push_key(&mut path, key);
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            table = entry
+                .as_object_mut()
+                .ok_or_else(|| format!("`{key}` is not a table"))?;
+        }
+        let last = keys.last().unwrap();
+        push_key(&mut path, last);
+        if table.contains_key(last) {
+            return Err(format!("`{}` is defined twice", keys.join(".")));
+        }
+        self.lines.insert(path.clone(), line);
+        let value = self.value(&path)?;
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+
+    /// Parses a dotted key.
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err("expected a key".to_string());
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            keys.push(key);
+            self.skip_spaces();
+            if !self.eat('.') {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Parses a value, whose path is `name`.
+    fn value(&mut self, name: &str) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.multiline_basic_string().map(Value::from)
+            }
+            Some('"') => self.basic_string().map(Value::from),
+            Some('\'') if self.starts_with("'''") => {
+                self.multiline_literal_string().map(Value::from)
+            }
+            Some('\'') => self.literal_string().map(Value::from),
+            Some('[') => self.array(name),
+            Some('{') => self.inline_table(name),
+            Some(_) => self.scalar(),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn array(&mut self, name: &str) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            let path = format!("{name}[{}]", items.len());
+            self.lines.insert(path.clone(), self.line);
+            items.push(self.value(&path)?);
+            self.skip_blank_lines();
+            if !self.eat(',') {
+                self.skip_blank_lines();
+                if self.eat(']') {
+                    return Ok(Value::Array(items));
+                }
+                return Err("expected `,` or `]` in an array".to_string());
+            }
+        }
+    }
+
+    fn inline_table(&mut self, name: &str) -> Result<Value, String> {
+        self.pos += 1;
+        let mut table = Map::new();
+        self.skip_spaces();
+        if self.eat('}') {
+            return Ok(Value::Object(table));
+        }
+        loop {
+            self.key_value(&mut table, name)?;
+            self.skip_spaces();
+            if self.eat('}') {
+                return Ok(Value::Object(table));
+            }
+            if !self.eat(',') {
+                return Err("expected `,` or `}` in an inline table".to_string());
+            }
+        }
+    }
+
+    /// Parses a boolean, a number or a date-time.
+    fn scalar(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || "_+-.:".contains(c))
+        {
+            self.pos += 1;
+        }
+        // The date and the time may be separated by a space.
+        if self.peek() == Some(' ')
+            && self
+                .chars
+                .get(self.pos + 1)
+                .is_some_and(char::is_ascii_digit)
+            && self.chars[start..self.pos].contains(&'-')
+        {
+            self.pos += 1;
+            while self
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || "+-.:".contains(c))
+            {
+                self.pos += 1;
+            }
+        }
+        let token = self.chars[start..self.pos].iter().collect::<String>();
+
+        match token.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "" => return Err("expected a value".to_string()),
+            _ => {}
+        }
+        if token.len() >= 8 && (token.as_bytes()[4] == b'-' || token.as_bytes()[2] == b':') {
+            return Ok(Value::String(token));
+        }
+
+        let digits = token.replace('_', "");
+        let (negative, unsigned) = match digits.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, digits.strip_prefix('+').unwrap_or(&digits)),
+        };
+        let integer = match unsigned.get(..2) {
+            Some("0x") => i64::from_str_radix(&unsigned[2..], 16).ok(),
+            Some("0o") => i64::from_str_radix(&unsigned[2..], 8).ok(),
+            Some("0b") => i64::from_str_radix(&unsigned[2..], 2).ok(),
+            _ if unsigned.bytes().all(|b| b.is_ascii_digit()) => unsigned.parse::<i64>().ok(),
+            _ => None,
+        };
+        if let Some(integer) = integer {
+            return Ok(Value::from(if negative { -integer } else { integer }));
+        }
+        digits
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| format!("invalid value `{token}`"))
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn multiline_basic_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_newline();
+        let mut s = String::new();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            match self.next() {
+                Some('\\') if self.peek().is_some_and(char::is_whitespace) => {
+                    // A backslash at the end of a line trims the whitespace that follows it.
+                    while self.peek().is_some_and(char::is_whitespace) {
+                        self.next();
+                    }
+                }
+                Some('\\') => s.push(self.escape()?),
+                Some(c) => s.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(s),
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, String> {
+        self.pos += 3;
+        self.skip_newline();
+        let mut s = String::new();
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            match self.next() {
+                Some(c) => s.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let c = match self.next() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('e') => '\u{1b}',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(u @ ('u' | 'U')) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let hex = self
+                    .chars
+                    .get(self.pos..self.pos + len)
+                    .map(|hex| hex.iter().collect::<String>())
+                    .ok_or("invalid escape sequence")?;
+                self.pos += len;
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid escape sequence `\\{u}{hex}`"))?
+            }
+            Some(c) => return Err(format!("invalid escape sequence `\\{c}`")),
+            None => return Err("unterminated string".to_string()),
+        };
+        Ok(c)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.next();
+        }
+        eaten
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 1;
+        }
+        self.eat('\n');
+    }
+
+    /// Skips whitespace, newlines and comments.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.next();
+                }
+                Some('#') => {
+                    self.comments = true;
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Skips the rest of the line, which may only have a comment.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            self.comments = true;
+            while self.peek().is_some_and(|c| c != '\n') {
+                self.pos += 1;
+            }
+        }
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(format!("unexpected `{c}`")),
+        }
+    }
+}
+
+/// Returns the table at `keys` under `root` and its path, creating the missing tables. The last
+/// item of an array of tables is used.
+fn descend<'a>(
+    root: &'a mut Map<String, Value>,
+    keys: &[String],
+) -> Result<(&'a mut Map<String, Value>, String), String> {
+    let mut table = root;
+    let mut path = String::new();
+    for key in keys {
+        push_key(&mut path, key);
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let entry = if is_table_array(entry) {
+            let items = entry.as_array_mut().unwrap();
+            path.push_str(&format!("[{}]", items.len() - 1));
+            items.last_mut().unwrap()
+        } else {
+            entry
+        };
+        table = entry
+            .as_object_mut()
+            .ok_or_else(|| format!("`{key}` is not a table"))?;
+    }
+    Ok((table, path))
+}
+
+fn push_key(path: &mut String, key: &str) {
+    if !path.is_empty() {
+        path.push('.');
+    }
+    path.push_str(key);
+}
+
+fn is_table_array(value: &Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| !items.is_empty() && items.iter().all(Value::is_object))
+}
+
+fn write_table(output: &mut String, path: &mut Vec<String>, table: &Map<String, Value>) {
+    // The plain values belong to the header above them, so they come before the sub-tables.
+    for (key, value) in table {
+        if !value.is_null() && !value.is_object() && !is_table_array(value) {
+            output.push_str(&format!("{} = {}\n", quote_key(key), inline(value)));
+        }
+    }
+
+    for (key, value) in table {
+        if let Value::Object(child) = value {
+            path.push(quote_key(key));
+            // A table of only tables is defined implicitly by the headers of its children.
+            if child.is_empty() || child.values().any(|v| !v.is_object() && !is_table_array(v)) {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                output.push_str(&format!("[{}]\n", path.join(".")));
+            }
+            write_table(output, path, child);
+            path.pop();
+        }
+    }
+
+    for (key, value) in table {
+        if is_table_array(value) {
+            path.push(quote_key(key));
+            for item in value.as_array().unwrap() {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+                output.push_str(&format!("[[{}]]\n", path.join(".")));
+                write_table(output, path, item.as_object().unwrap());
+            }
+            path.pop();
+        }
+    }
+}
+
+fn inline(value: &Value) -> String {
+    match value {
+        Value::String(s) => quote(s),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .filter(|v| !v.is_null())
+                .map(inline)
+                .collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(table) if table.is_empty() => "{}".to_string(),
+        Value::Object(table) => {
+            let entries = table
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| format!("{} = {}", quote_key(k), inline(v)))
+                .collect::<Vec<_>>();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+fn quote_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_values() {
+        let document = parse(
+            r#"
+# comment
+string = "a \"quoted\"\tvalue\u00e9" # comment
+literal = 'C:\path'
+multiline = """
+first \
+  second"""
+integers = [1_000, -2, +3, 0xff, 0o17, 0b101]
+float = 1.5
+bool = true
+date = 1979-05-27T07:32:00Z
+array = [
+  "a",  # comment
+  "b",
+]
+inline = { a = 1, b.c = "d" }
+"quoted key" = 1
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Object(document.table),
+            json!({
+                "string": "a \"quoted\"\tvalueé",
+                "literal": "C:\\path",
+                "multiline": "first second",
+                "integers": [1000, -2, 3, 255, 15, 5],
+                "float": 1.5,
+                "bool": true,
+                "date": "1979-05-27T07:32:00Z",
+                "array": ["a", "b"],
+                "inline": {"a": 1, "b": {"c": "d"}},
+                "quoted key": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tables() {
+        let document = parse(
+            r#"
+role = "prod"
+
+[presets.prod]
+role = "Admin"
+
+[presets.prod.tags]
+Team = "platform"
+
+[[chains.admin]]
+role = "Bastion"
+
+[[chains.admin]]
+role = "Admin"
+
+[chains.admin.tags]
+Team = "platform"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Object(document.table),
+            json!({
+                "role": "prod",
+                "presets": {"prod": {"role": "Admin", "tags": {"Team": "platform"}}},
+                "chains": {"admin": [
+                    {"role": "Bastion"},
+                    {"role": "Admin", "tags": {"Team": "platform"}},
+                ]},
+            })
+        );
+    }
+
+    #[test]
+    fn parse_lines() {
+        let document = parse(
+            r#"role = "prod"
+
+[presets.prod]
+policy_arns = [
+  "a",
+  "b",
+]
+
+[[include]]
+url = "https://example.com/"
+"#,
+        )
+        .unwrap();
+        let line = |name: &str| document.lines.get(name).copied();
+        assert_eq!(line("role"), Some(0));
+        assert_eq!(line("presets.prod"), Some(2));
+        assert_eq!(line("presets.prod.policy_arns"), Some(3));
+        assert_eq!(line("presets.prod.policy_arns[1]"), Some(5));
+        assert_eq!(line("include[0]"), Some(8));
+        assert_eq!(line("include[0].url"), Some(9));
+        assert_eq!(line("presets"), None);
+        assert_eq!(
+            document.headers,
+            [
+                ("presets.prod".to_string(), 2),
+                ("include[0]".to_string(), 8)
+            ]
+        );
+        assert!(!document.comments);
+    }
+
+    #[test]
+    fn parse_comments() {
+        assert!(parse("# comment\na = 1").unwrap().comments);
+        assert!(parse("a = 1 # comment").unwrap().comments);
+        assert!(!parse("a = \"# not a comment\"").unwrap().comments);
+        assert!(!parse("a = '''\n# not a comment\n'''").unwrap().comments);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |content: &str| parse(content).err().unwrap();
+        assert_eq!(error("a = 1\na = 2"), "line 2: `a` is defined twice");
+        assert_eq!(error("[a]\n[a]"), "line 2: table `a` is defined twice");
+        assert_eq!(
+            error("a = 1\n[[a]]"),
+            "line 2: `a` is not an array of tables"
+        );
+        assert_eq!(error("a = \"b"), "line 1: unterminated string");
+        assert_eq!(error("a = 1 b"), "line 1: unexpected `b`");
+        assert_eq!(error("a ="), "line 1: expected a value");
+        assert_eq!(
+            error("a = [1 2]"),
+            "line 1: expected `,` or `]` in an array"
+        );
+        assert_eq!(error("a = nan"), "line 1: invalid value `nan`");
+        assert!(parse("[a").is_err());
+        assert!(parse("= 1").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = json!({
+            "role": "prod",
+            "cache": {"age_recipients": ["age1"]},
+            "presets": {
+                "with.dot": {"role": "Admin", "tags": {"A": "b \"c\"\n"}},
+            },
+            "chains": {"admin": [{"role": "Bastion", "tags": {"Team": "x"}}, {"role": "Admin"}]},
+            "empty": {},
+        });
+        let Value::Object(table) = &value else {
+            unreachable!()
+        };
+        let content = to_string(table);
+        assert_eq!(
+            content,
+            r#"role = "prod"
+
+[cache]
+age_recipients = ["age1"]
+
+[[chains.admin]]
+role = "Bastion"
+
+[chains.admin.tags]
+Team = "x"
+
+[[chains.admin]]
+role = "Admin"
+
+[empty]
+
+[presets."with.dot"]
+role = "Admin"
+
+[presets."with.dot".tags]
+A = "b \"c\"\n"
+"#
+        );
+        assert_eq!(Value::Object(parse(&content).unwrap().table), value);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::{duration, toml};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// The keys allowed in each section of the config file.
+const SECTIONS: &[(&str, &[&str])] = &[
+    (
+        "cache",
+        &["backend", "age_recipients", "age_identity", "gpg_recipient"],
+    ),
+    ("entra", &["tenant_id", "app_id_uri", "username"]),
+    ("mfa", &["serial_number", "token_command"]),
+    ("oidc", &["token_source", "audience"]),
+    ("okta", &["app_url", "username", "factor"]),
+    ("prompt", &["backend"]),
+    ("approval", &["roles"]),
+];
+
+const PRESET_KEYS: &[&str] = &[
+    "role",
+    "group",
+    "role_session_name",
+    "duration_seconds",
+    "external_id",
+    "serial_number",
+    "tags",
+    "transitive_tag_keys",
+    "policy_arns",
+    "policy",
+    "source_identity",
+    "console_duration_seconds",
+    "console_issuer",
+];
+
+const HOP_KEYS: &[&str] = &["role", "external_id", "duration_seconds", "tags"];
+
+const INCLUDE_KEYS: &[&str] = &["url", "ttl"];
+
+/// A segment of the path to a value in the document.
+#[derive(Clone, Copy)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Checks the config file `document` and returns all problems found, each prefixed with the line
+/// of the value.
+pub fn validate(document: &toml::Document) -> Vec<String> {
+    let mut validator = Validator {
+        lines: &document.lines,
+        problems: Vec::new(),
+    };
+    validator.document(&document.table);
+    // The tables of the document are visited in the order of their names.
+    validator.problems.sort_by_key(|(line, _)| *line);
+    validator
+        .problems
+        .into_iter()
+        .map(|(_, problem)| problem)
+        .collect()
+}
+
+struct Validator<'a> {
+    lines: &'a HashMap<String, usize>,
+    problems: Vec<(Option<usize>, String)>,
+}
+
+impl Validator<'_> {
+    fn document(&mut self, document: &Map<String, Value>) {
+        for (key, value) in document {
+            let key = key.as_str();
+            let path = [Segment::Key(key)];
+            match key {
+                "role" => {}
+                "presets" => {
+                    self.each(&path, value, |v, path, preset| v.preset(path, preset, true))
+                }
+                "groups" => self.each(&path, value, |v, path, group| v.preset(path, group, false)),
+                "chains" => self.each(&path, value, |v, path, chain| v.chain(path, chain)),
+                "accounts" => self.each(&path, value, |v, path, id| {
+                    if !id.as_str().is_some_and(is_account_id) {
+                        v.report(path, "an account ID must be 12 digits");
+                    }
+                }),
+                "policies" => self.each(&path, value, |v, path, policy| {
+                    if !policy["Statement"].is_array() && !policy["Statement"].is_object() {
+                        v.report(path, "a policy must have `Statement`");
+                    }
+                }),
+                "include" => {
+                    for (i, include) in value.as_array().into_iter().flatten().enumerate() {
+                        let path = [path[0], Segment::Index(i)];
+                        self.keys(&path, include, INCLUDE_KEYS);
+                        self.include(&path, include);
+                    }
+                }
+                _ => match SECTIONS.iter().find(|(name, _)| *name == key) {
+                    Some((_, keys)) => self.keys(&path, value, keys),
+                    None => self.report(&path, &format!("unknown key `{key}`")),
+                },
+            }
+        }
+
+        if let Some(serial_number) = document
+            .get("mfa")
+            .and_then(|mfa| mfa["serial_number"].as_str())
+        {
+            self.serial_number(
+                &[Segment::Key("mfa"), Segment::Key("serial_number")],
+                serial_number,
+            );
+        }
+    }
+
+    /// Calls `f` with each entry of the table `value`.
+    fn each(
+        &mut self,
+        path: &[Segment],
+        value: &Value,
+        mut f: impl FnMut(&mut Self, &[Segment], &Value),
+    ) {
+        for (name, entry) in value.as_object().into_iter().flatten() {
+            let mut path = path.to_vec();
+            path.push(Segment::Key(name));
+            f(self, &path, entry);
+        }
+    }
+
+    /// Checks a preset, or a group if the role is not `required`.
+    fn preset(&mut self, path: &[Segment], preset: &Value, required: bool) {
+        self.keys(path, preset, PRESET_KEYS);
+
+        let mut path = path.to_vec();
+        if let Some(role) = preset["role"].as_str() {
+            path.push(Segment::Key("role"));
+            if role.starts_with("arn:") && !is_arn_of(role, "iam", "role/") {
+                self.report(&path, &format!("`{role}` is not an ARN of a role"));
+            }
+            path.pop();
+        } else if required && preset["group"].is_null() {
+            self.report(&path, "`role` is required");
+        }
+
+        path.push(Segment::Key("duration_seconds"));
+        self.duration_seconds(&path, &preset["duration_seconds"]);
+        path.pop();
+
+        path.push(Segment::Key("console_duration_seconds"));
+        self.duration_seconds(&path, &preset["console_duration_seconds"]);
+        path.pop();
+
+        if let Some(serial_number) = preset["serial_number"].as_str() {
+            path.push(Segment::Key("serial_number"));
+            self.serial_number(&path, serial_number);
+            path.pop();
+        }
+
+        path.push(Segment::Key("policy_arns"));
+        for (i, arn) in preset["policy_arns"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let arn = arn.as_str().unwrap_or_default();
+            if arn.starts_with("arn:") && !is_arn_of(arn, "iam", "policy/") {
+                path.push(Segment::Index(i));
+                self.report(&path, &format!("`{arn}` is not an ARN of a policy"));
+                path.pop();
+            }
+        }
+    }
+
+    fn chain(&mut self, path: &[Segment], chain: &Value) {
+        let Some(hops) = chain.as_array() else {
+            self.report(path, "a chain must be an array of roles");
+            return;
+        };
+        if hops.is_empty() {
+            self.report(path, "a chain must have at least one role");
+        }
+
+        for (i, hop) in hops.iter().enumerate() {
+            let mut path = path.to_vec();
+            path.push(Segment::Index(i));
+            self.keys(&path, hop, HOP_KEYS);
+
+            path.push(Segment::Key("role"));
+            match hop["role"].as_str() {
+                Some(role) if role.starts_with("arn:") => {
+                    if !is_arn_of(role, "iam", "role/") {
+                        self.report(&path, &format!("`{role}` is not an ARN of a role"));
+                    }
+                }
+                Some(_) if i + 1 == hops.len() => {
+                    self.report(&path, "the last role of a chain must be an ARN")
+                }
+                Some(_) => {}
+                None => self.report(&path[..path.len() - 1], "`role` is required"),
+            }
+            path.pop();
+
+            path.push(Segment::Key("duration_seconds"));
+            self.duration_seconds(&path, &hop["duration_seconds"]);
+        }
+    }
+
+    fn include(&mut self, path: &[Segment], include: &Value) {
+        let mut path = path.to_vec();
+        path.push(Segment::Key("url"));
+        match include["url"].as_str() {
+            Some(url) if url.starts_with("https://") || url.starts_with("s3://") => {}
+            Some(url) => self.report(&path, &format!("`{url}` is neither an HTTPS nor an S3 URL")),
+            None => self.report(&path[..path.len() - 1], "`url` is required"),
+        }
+        path.pop();
+
+        if let Some(ttl) = include["ttl"].as_str() {
+            if let Err(e) = duration::parse(ttl) {
+                path.push(Segment::Key("ttl"));
+                self.report(&path, &e);
+            }
+        }
+    }
+
+    /// Reports the keys of the table `value` that are not `allowed`.
+    fn keys(&mut self, path: &[Segment], value: &Value, allowed: &[&str]) {
+        let Some(table) = value.as_object() else {
+            self.report(path, "must be a table");
+            return;
+        };
+
+        for key in table.keys() {
+            if !allowed.contains(&key.as_str()) {
+                let mut path = path.to_vec();
+                path.push(Segment::Key(key));
+                self.report(
+                    &path,
+                    &format!(
+                        "unknown key `{key}`; expected one of {}",
+                        allowed.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn duration_seconds(&mut self, path: &[Segment], value: &Value) {
+        if let Some(seconds) = value.as_i64() {
+            if !(900..=43200).contains(&seconds) {
+                self.report(path, "the duration must be between 900 and 43200 seconds");
+            }
+        }
+    }
+
+    fn serial_number(&mut self, path: &[Segment], serial_number: &str) {
+        if serial_number.starts_with("arn:") && !is_arn_of(serial_number, "iam", "mfa/") {
+            self.report(
+                path,
+                &format!("`{serial_number}` is not an ARN of an MFA device"),
+            );
+        }
+    }
+
+    fn report(&mut self, path: &[Segment], message: &str) {
+        let name = name_of(path);
+        let line = self.lines.get(&name).copied();
+        let problem = match line {
+            Some(line) => format!("line {}: {name}: {message}", line + 1),
+            None => format!("{name}: {message}"),
+        };
+        self.problems.push((line, problem));
+    }
+}
+
+/// Returns the name of the value at `path` as recorded by [`toml::parse`], such as
+/// `include[0].url`.
+fn name_of(path: &[Segment]) -> String {
+    let name = path
+        .iter()
+        .map(|segment| match segment {
+            Segment::Key(key) => format!(".{key}"),
+            Segment::Index(i) => format!("[{i}]"),
+        })
+        .collect::<String>();
+    name.trim_start_matches('.').to_string()
+}
+
+/// Returns whether `s` is an ID of an AWS account, which is 12 digits.
+pub fn is_account_id(s: &str) -> bool {
+    s.len() == 12 && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Returns whether `arn` is in the form of `arn:PARTITION:SERVICE::ACCOUNT:RESOURCE` with the
+/// resource starting with `prefix`.
+pub fn is_arn_of(arn: &str, service: &str, prefix: &str) -> bool {
+    let parts = arn.splitn(6, ':').collect::<Vec<_>>();
+    matches!(
+        parts[..],
+        ["arn", partition, s, "", account, resource]
+            if !partition.is_empty()
+                && s == service
+                && (account == "aws" || is_account_id(account))
+                && resource.starts_with(prefix)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(content: &str) -> Vec<String> {
+        validate(&toml::parse(content).unwrap())
+    }
+
+    #[test]
+    fn valid() {
+        let content = r#"
+role = "prod"
+
+[cache]
+backend = "file"
+
+[accounts]
+prod = "111122223333"
+
+[presets.prod]
+role = "arn:aws:iam::111122223333:role/Admin"
+duration_seconds = 3600
+serial_number = "arn:aws:iam::111122223333:mfa/me"
+policy_arns = ["arn:aws:iam::aws:policy/ReadOnlyAccess", "ReadOnlyAccess"]
+
+[groups.all]
+tags = { Team = "platform" }
+
+[[chains.admin]]
+role = "Bastion"
+
+[[chains.admin]]
+role = "arn:aws-cn:iam::111122223333:role/Admin"
+
+[policies.read-only]
+Statement = [{ Effect = "Allow", Action = "s3:Get*", Resource = "*" }]
+
+[[include]]
+url = "s3://bucket/key.toml"
+ttl = "1d"
+"#;
+        assert_eq!(problems(content), Vec::<String>::new());
+    }
+
+    #[test]
+    fn problems_with_lines() {
+        let content = r#"unknown = 1
+
+[cache]
+backend = "file"
+colour = "red"
+
+[accounts]
+prod = "1111"
+
+[presets.prod]
+duration_seconds = 600
+policy_arns = [
+  "arn:aws:iam::aws:policy/ReadOnlyAccess",
+  "arn:aws:iam::aws:role/Admin",
+]
+
+[[chains.admin]]
+role = "arn:aws:iam::111122223333:role/Admin"
+
+[[chains.admin]]
+role = "Admin"
+
+[policies.empty]
+Version = "2012-10-17"
+
+[[include]]
+url = "ftp://example.com/"
+"#;
+        assert_eq!(
+            problems(content),
+            [
+                "line 1: unknown: unknown key `unknown`",
+                "line 5: cache.colour: unknown key `colour`; expected one of backend, \
+                 age_recipients, age_identity, gpg_recipient",
+                "line 8: accounts.prod: an account ID must be 12 digits",
+                "line 10: presets.prod: `role` is required",
+                "line 11: presets.prod.duration_seconds: the duration must be between 900 and \
+                 43200 seconds",
+                "line 14: presets.prod.policy_arns[1]: `arn:aws:iam::aws:role/Admin` is not an \
+                 ARN of a policy",
+                "line 21: chains.admin[1].role: the last role of a chain must be an ARN",
+                "line 23: policies.empty: a policy must have `Statement`",
+                "line 27: include[0].url: `ftp://example.com/` is neither an HTTPS nor an S3 URL",
+            ]
+        );
+    }
+
+    #[test]
+    fn not_tables() {
+        assert_eq!(
+            problems("cache = 1\nchains = { admin = \"Admin\" }"),
+            [
+                "line 1: cache: must be a table",
+                "line 2: chains.admin: a chain must be an array of roles",
+            ]
+        );
+    }
+
+    #[test]
+    fn arn_of() {
+        assert!(is_arn_of(
+            "arn:aws:iam::111122223333:role/Admin",
+            "iam",
+            "role/"
+        ));
+        assert!(is_arn_of(
+            "arn:aws:iam::aws:policy/ReadOnlyAccess",
+            "iam",
+            "policy/"
+        ));
+        assert!(is_arn_of(
+            "arn:aws:iam::111122223333:role/path/Admin",
+            "iam",
+            "role/"
+        ));
+        assert!(!is_arn_of("arn:aws:iam::1111:role/Admin", "iam", "role/"));
+        assert!(!is_arn_of(
+            "arn:aws:sts::111122223333:role/Admin",
+            "iam",
+            "role/"
+        ));
+        assert!(!is_arn_of(
+            "arn::iam::111122223333:role/Admin",
+            "iam",
+            "role/"
+        ));
+        assert!(!is_arn_of(
+            "arn:aws:iam:us-east-1:111122223333:role/Admin",
+            "iam",
+            "role/"
+        ));
+        assert!(!is_arn_of("arn:aws:iam::111122223333", "iam", "role/"));
+        assert!(!is_arn_of("Admin", "iam", "role/"));
+    }
+}
@@ -0,0 +1,16 @@
+use anyhow::Result;
+use aws_config::SdkConfig;
+
+#[derive(clap::Args)]
+pub struct Args {}
+
+pub async fn run(config: &SdkConfig, _args: Args) -> Result<()> {
+    let sts = aws_sdk_sts::Client::new(config);
+    let response = sts.get_caller_identity().send().await?;
+
+    println!("UserId:  {}", response.user_id().unwrap_or_default());
+    println!("Account: {}", response.account().unwrap_or_default());
+    println!("Arn:     {}", response.arn().unwrap_or_default());
+
+    Ok(())
+}